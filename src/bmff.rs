@@ -0,0 +1,151 @@
+//! Minimal reading and writing of ISOBMFF boxes.
+use crate::{HeifError, HeifErrorCode, HeifErrorSubCode, Result};
+
+pub(crate) struct BmffBox<'a> {
+    pub box_type: [u8; 4],
+    /// Content of the box without header.
+    pub payload: &'a [u8],
+    /// Whole box including header.
+    pub raw: &'a [u8],
+}
+
+/// Iterator over ISOBMFF boxes stored one after another.
+///
+/// Iteration stops on the first box with invalid size.
+pub(crate) struct Boxes<'a>(pub &'a [u8]);
+
+impl<'a> Iterator for Boxes<'a> {
+    type Item = BmffBox<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let data = self.0;
+        let size = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as u64;
+        let box_type: [u8; 4] = data.get(4..8)?.try_into().ok()?;
+        let (size, header_size) = match size {
+            0 => (data.len() as u64, 8),
+            1 => (u64::from_be_bytes(data.get(8..16)?.try_into().ok()?), 16),
+            _ => (size, 8),
+        };
+        if size < header_size || size > data.len() as u64 {
+            self.0 = &[];
+            return None;
+        }
+        let size = size as usize;
+        self.0 = &data[size..];
+        Some(BmffBox {
+            box_type,
+            payload: &data[header_size as usize..size],
+            raw: &data[..size],
+        })
+    }
+}
+
+/// Reader of big-endian values from payload of a box.
+pub(crate) struct ByteReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Not read part of data.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn bytes(&mut self, size: usize) -> Result<&'a [u8]> {
+        if size > self.data.len() {
+            return Err(HeifError {
+                code: HeifErrorCode::InvalidInput,
+                sub_code: HeifErrorSubCode::EndOfData,
+                message: "Unexpected end of box data".to_string(),
+            });
+        }
+        let (bytes, rest) = self.data.split_at(size);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        self.array().map(u16::from_be_bytes)
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        self.array().map(u32::from_be_bytes)
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        self.array().map(u64::from_be_bytes)
+    }
+
+    pub fn four_cc(&mut self) -> Result<[u8; 4]> {
+        self.array()
+    }
+
+    /// Reads unsigned integer with size in bytes that may be 0, 4 or 8.
+    pub fn uint(&mut self, size: u8) -> Result<u64> {
+        match size {
+            0 => Ok(0),
+            4 => self.u32().map(u64::from),
+            8 => self.u64(),
+            _ => Err(HeifError {
+                code: HeifErrorCode::InvalidInput,
+                sub_code: HeifErrorSubCode::Unspecified,
+                message: format!("Invalid size of integer field: {}", size),
+            }),
+        }
+    }
+
+    /// Reads null-terminated string without terminating null.
+    ///
+    /// The rest of data is returned if there is no null byte.
+    pub fn c_string(&mut self) -> &'a [u8] {
+        let size = self
+            .data
+            .iter()
+            .position(|&b| b == 0)
+            .map(|pos| pos + 1)
+            .unwrap_or(self.data.len());
+        let (string, rest) = self.data.split_at(size);
+        self.data = rest;
+        string.strip_suffix(&[0]).unwrap_or(string)
+    }
+}
+
+/// Writes a box which payload is created by the given closure.
+///
+/// Size of the payload must be less than 4 GiB.
+pub(crate) fn write_box(out: &mut Vec<u8>, box_type: &[u8; 4], payload: impl FnOnce(&mut Vec<u8>)) {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(box_type);
+    payload(out);
+    let size = (out.len() - start) as u32;
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+pub(crate) fn write_full_box(
+    out: &mut Vec<u8>,
+    box_type: &[u8; 4],
+    version: u8,
+    flags: u32,
+    payload: impl FnOnce(&mut Vec<u8>),
+) {
+    write_box(out, box_type, |out| {
+        out.push(version);
+        out.extend_from_slice(&flags.to_be_bytes()[1..]);
+        payload(out);
+    });
+}
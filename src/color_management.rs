@@ -0,0 +1,234 @@
+use lcms2::{CIExyY, CIExyYTRIPLE, Flags, Intent, PixelFormat, Profile, ToneCurve, Transform};
+
+use crate::{
+    color_profile_types, Channel, ColorConversionOptions, ColorProfileNCLX, ColorProfileRaw,
+    ColorSpace, HeifError, HeifErrorCode, HeifErrorSubCode, Image, Result, RgbChroma,
+    TransferCharacteristics,
+};
+
+/// Color profile into which pixels of an image are transformed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TargetColorProfile {
+    /// sRGB (IEC 61966-2-1).
+    Srgb,
+    /// Display P3 (DCI-P3 primaries, D65 white point and sRGB transfer function).
+    DisplayP3,
+    /// Data of ICC profile.
+    Icc(Vec<u8>),
+}
+
+impl From<&ColorProfileRaw> for TargetColorProfile {
+    fn from(profile: &ColorProfileRaw) -> Self {
+        Self::Icc(profile.data.clone())
+    }
+}
+
+impl TargetColorProfile {
+    fn lcms_profile(&self) -> Result<Profile> {
+        match self {
+            Self::Srgb => Ok(Profile::new_srgb()),
+            Self::DisplayP3 => rgb_profile(
+                [0.3127, 0.3290],
+                [[0.680, 0.320], [0.265, 0.690], [0.150, 0.060]],
+                &srgb_curve()?,
+            ),
+            Self::Icc(data) => icc_profile(data),
+        }
+    }
+}
+
+fn lcms_error(err: lcms2::Error) -> HeifError {
+    HeifError {
+        code: HeifErrorCode::UsageError,
+        sub_code: HeifErrorSubCode::InvalidParameterValue,
+        message: format!("Color management error: {}", err),
+    }
+}
+
+fn icc_profile(data: &[u8]) -> Result<Profile> {
+    Profile::new_icc(data).map_err(|err| HeifError {
+        code: HeifErrorCode::InvalidInput,
+        sub_code: HeifErrorSubCode::Unspecified,
+        message: format!("Invalid ICC profile: {}", err),
+    })
+}
+
+fn srgb_curve() -> Result<ToneCurve> {
+    ToneCurve::new_parametric(4, &[2.4, 1. / 1.055, 0.055 / 1.055, 1. / 12.92, 0.04045])
+        .map_err(lcms_error)
+}
+
+fn rgb_profile(white: [f64; 2], primaries: [[f64; 2]; 3], curve: &ToneCurve) -> Result<Profile> {
+    let xy_y = |[x, y]: [f64; 2]| CIExyY { x, y, Y: 1. };
+    let primaries = CIExyYTRIPLE {
+        Red: xy_y(primaries[0]),
+        Green: xy_y(primaries[1]),
+        Blue: xy_y(primaries[2]),
+    };
+    Profile::new_rgb(&xy_y(white), &primaries, &[curve, curve, curve]).map_err(lcms_error)
+}
+
+/// Build ICC profile which corresponds to the NCLX profile.
+fn nclx_profile(nclx: &ColorProfileNCLX) -> Result<Profile> {
+    let curve = match nclx.transfer_characteristics() {
+        TransferCharacteristics::Linear => ToneCurve::new(1.),
+        TransferCharacteristics::ITU_R_BT_470_6_System_M => ToneCurve::new(2.2),
+        TransferCharacteristics::ITU_R_BT_470_6_System_B_G => ToneCurve::new(2.8),
+        TransferCharacteristics::ITU_R_BT_709_5
+        | TransferCharacteristics::ITU_R_BT_601_6
+        | TransferCharacteristics::ITU_R_BT_2020_2_10bit
+        | TransferCharacteristics::ITU_R_BT_2020_2_12bit => {
+            ToneCurve::new_parametric(4, &[1. / 0.45, 1. / 1.099, 0.099 / 1.099, 1. / 4.5, 0.081])
+                .map_err(lcms_error)?
+        }
+        TransferCharacteristics::ITU_R_BT_2100_0_PQ
+        | TransferCharacteristics::ITU_R_BT_2100_0_HLG => {
+            return Err(HeifError {
+                code: HeifErrorCode::UnsupportedFeature,
+                sub_code: HeifErrorSubCode::UnsupportedColorConversion,
+                message: "Transforming of PQ and HLG images is not supported".to_string(),
+            });
+        }
+        _ => srgb_curve()?,
+    };
+    let white = [
+        nclx.color_primary_white_x() as f64,
+        nclx.color_primary_white_y() as f64,
+    ];
+    let primaries = [
+        [
+            nclx.color_primary_red_x() as f64,
+            nclx.color_primary_red_y() as f64,
+        ],
+        [
+            nclx.color_primary_green_x() as f64,
+            nclx.color_primary_green_y() as f64,
+        ],
+        [
+            nclx.color_primary_blue_x() as f64,
+            nclx.color_primary_blue_y() as f64,
+        ],
+    ];
+    if white[1] <= 0. || primaries.iter().any(|p| p[1] <= 0.) {
+        // Primaries are unspecified - use BT.709 primaries
+        return rgb_profile(
+            [0.3127, 0.3290],
+            [[0.640, 0.330], [0.300, 0.600], [0.150, 0.060]],
+            &curve,
+        );
+    }
+    rgb_profile(white, primaries, &curve)
+}
+
+/// Returns ICC profile of the image.
+/// Images without color profiles are considered as sRGB images.
+fn source_profile(image: &Image) -> Result<Profile> {
+    if let Some(raw) = image.color_profile_raw() {
+        if raw.typ == color_profile_types::PROF || raw.typ == color_profile_types::R_ICC {
+            return icc_profile(&raw.data);
+        }
+    }
+    match image.color_profile_nclx() {
+        Some(nclx) => nclx_profile(&nclx),
+        None => Ok(Profile::new_srgb()),
+    }
+}
+
+pub(crate) fn transform_image_to_profile(
+    src: &Image,
+    target: &TargetColorProfile,
+) -> Result<Image> {
+    let color_space = src.color_space().unwrap_or(ColorSpace::Undefined);
+    let has_alpha = src.has_channel(Channel::Alpha)
+        || matches!(
+            color_space,
+            ColorSpace::Rgb(RgbChroma::Rgba | RgbChroma::HdrRgbaBe | RgbChroma::HdrRgbaLe)
+        );
+    let bit_depth = [Channel::Y, Channel::R, Channel::Interleaved]
+        .into_iter()
+        .find_map(|channel| src.bits_per_pixel(channel))
+        .unwrap_or(8);
+    let is_hdr = bit_depth > 8;
+
+    let (work_chroma, pixel_format) = match (is_hdr, has_alpha) {
+        (false, false) => (RgbChroma::Rgb, PixelFormat::RGB_8),
+        (false, true) => (RgbChroma::Rgba, PixelFormat::RGBA_8),
+        (true, false) => (RgbChroma::HdrRgbLe, PixelFormat::RGB_16),
+        (true, true) => (RgbChroma::HdrRgbaLe, PixelFormat::RGBA_16),
+    };
+    let work_bit_depth = if is_hdr { 16 } else { 8 };
+    let rgb_image = src.convert(
+        ColorSpace::Rgb(work_chroma),
+        work_bit_depth,
+        &ColorConversionOptions::new(),
+    )?;
+
+    // Pixels without padding. 16-bit values are stored in native byte order.
+    let mut pixels: Vec<u8> = Vec::new();
+    let planes = rgb_image.planes();
+    let Some(plane) = planes.interleaved else {
+        return Err(HeifError {
+            code: HeifErrorCode::UsageError,
+            sub_code: HeifErrorSubCode::NonExistingImageChannelReferenced,
+            message: "Image has no interleaved channel".to_string(),
+        });
+    };
+    if is_hdr {
+        for row in plane.as_u16_rows()? {
+            pixels.extend(row.iter().flat_map(|v| v.to_ne_bytes()));
+        }
+    } else {
+        pixels.extend(plane.rows().flatten());
+    }
+
+    let src_profile = source_profile(src)?;
+    let dst_profile = target.lcms_profile()?;
+    let transform: Transform<u8, u8> = Transform::new_flags(
+        &src_profile,
+        pixel_format,
+        &dst_profile,
+        pixel_format,
+        Intent::Perceptual,
+        Flags::COPY_ALPHA,
+    )
+    .map_err(lcms_error)?;
+    transform.transform_in_place(&mut pixels);
+
+    let width = src.width();
+    let height = src.height();
+    let mut dst = if is_hdr {
+        let values: Vec<u16> = pixels
+            .chunks_exact(2)
+            .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+            .collect();
+        let image = if has_alpha {
+            Image::from_rgba16(width, height, &values, 16)?
+        } else {
+            Image::from_rgb16(width, height, &values, 16)?
+        };
+        if bit_depth < 16 {
+            image.convert(
+                ColorSpace::Rgb(work_chroma),
+                bit_depth,
+                &ColorConversionOptions::new(),
+            )?
+        } else {
+            image
+        }
+    } else if has_alpha {
+        Image::from_rgba8(width, height, &pixels)?
+    } else {
+        Image::from_rgb8(width, height, &pixels)?
+    };
+
+    let icc_data = match target {
+        TargetColorProfile::Icc(data) => data.clone(),
+        _ => dst_profile.icc().map_err(lcms_error)?,
+    };
+    dst.set_color_profile_raw(&ColorProfileRaw::new(color_profile_types::PROF, icc_data))?;
+    dst.set_premultiplied_alpha(src.is_premultiplied_alpha());
+    let (aspect_h, aspect_v) = src.pixel_aspect_ratio();
+    dst.set_pixel_aspect_ratio(aspect_h, aspect_v);
+    Ok(dst)
+}
@@ -1,6 +1,9 @@
 use four_cc::FourCC;
 use libheif_sys as lh;
 
+use crate::icc::parse_icc_profile;
+use crate::{HeifError, HeifErrorCode, HeifErrorSubCode, IccProfileInfo, Result};
+
 pub type ColorProfileType = FourCC;
 
 pub mod color_profile_types {
@@ -105,6 +108,7 @@ pub trait ColorProfile {
     fn profile_type(&self) -> ColorProfileType;
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ColorProfileRaw {
     pub(crate) typ: ColorProfileType,
     pub data: Vec<u8>,
@@ -114,6 +118,21 @@ impl ColorProfileRaw {
     pub fn new(typ: ColorProfileType, data: Vec<u8>) -> Self {
         Self { typ, data }
     }
+
+    /// Parse header and some tags of ICC profile.
+    ///
+    /// Returns error if the profile type is not `prof` or `rICC`
+    /// or data of the profile is invalid.
+    pub fn icc_info(&self) -> Result<IccProfileInfo> {
+        if self.typ != color_profile_types::PROF && self.typ != color_profile_types::R_ICC {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::InvalidParameterValue,
+                message: format!("Color profile of type '{}' is not ICC profile", self.typ),
+            });
+        }
+        parse_icc_profile(&self.data)
+    }
 }
 
 impl ColorProfile for ColorProfileRaw {
@@ -141,10 +160,155 @@ impl ColorProfile for ColorProfileNCLX {
     }
 }
 
+impl Clone for ColorProfileNCLX {
+    fn clone(&self) -> Self {
+        let inner = unsafe { lh::heif_nclx_color_profile_alloc() };
+        assert!(!inner.is_null(), "Failed to allocate NCLX color profile");
+        unsafe {
+            // Fields of newer versions of the struct may be absent in the allocated one.
+            let version = (*inner).version;
+            *inner = *self.inner;
+            (*inner).version = version.min(self.version());
+        }
+        Self { inner }
+    }
+}
+
+impl PartialEq for ColorProfileNCLX {
+    fn eq(&self, other: &Self) -> bool {
+        let a = self.inner_ref();
+        let b = other.inner_ref();
+        a.color_primaries == b.color_primaries
+            && a.transfer_characteristics == b.transfer_characteristics
+            && a.matrix_coefficients == b.matrix_coefficients
+            && a.full_range_flag == b.full_range_flag
+            && a.color_primary_red_x == b.color_primary_red_x
+            && a.color_primary_red_y == b.color_primary_red_y
+            && a.color_primary_green_x == b.color_primary_green_x
+            && a.color_primary_green_y == b.color_primary_green_y
+            && a.color_primary_blue_x == b.color_primary_blue_x
+            && a.color_primary_blue_y == b.color_primary_blue_y
+            && a.color_primary_white_x == b.color_primary_white_x
+            && a.color_primary_white_y == b.color_primary_white_y
+    }
+}
+
+/// Chromaticity coordinates of red, green, blue primaries and white point.
+fn primaries_coordinates(primaries: ColorPrimaries) -> Option<[[f32; 2]; 4]> {
+    const D65: [f32; 2] = [0.3127, 0.3290];
+    let coordinates = match primaries {
+        ColorPrimaries::ITU_R_BT_709_5 => [[0.640, 0.330], [0.300, 0.600], [0.150, 0.060], D65],
+        ColorPrimaries::ITU_R_BT_470_6_System_M => {
+            [[0.67, 0.33], [0.21, 0.71], [0.14, 0.08], [0.310, 0.316]]
+        }
+        ColorPrimaries::ITU_R_BT_470_6_System_B_G => {
+            [[0.64, 0.33], [0.29, 0.60], [0.15, 0.06], D65]
+        }
+        ColorPrimaries::ITU_R_BT_601_6 | ColorPrimaries::SMPTE_240M => {
+            [[0.630, 0.340], [0.310, 0.595], [0.155, 0.070], D65]
+        }
+        ColorPrimaries::GenericFilm => [
+            [0.681, 0.319],
+            [0.243, 0.692],
+            [0.145, 0.049],
+            [0.310, 0.316],
+        ],
+        ColorPrimaries::ITU_R_BT_2020_2_and_2100_0 => {
+            [[0.708, 0.292], [0.170, 0.797], [0.131, 0.046], D65]
+        }
+        ColorPrimaries::SMPTE_ST_428_1 => [[1.0, 0.0], [0.0, 1.0], [0.0, 0.0], [0.333333, 0.33333]],
+        ColorPrimaries::SMPTE_RP_431_2 => [
+            [0.680, 0.320],
+            [0.265, 0.690],
+            [0.150, 0.060],
+            [0.314, 0.351],
+        ],
+        ColorPrimaries::SMPTE_EG_432_1 => [[0.680, 0.320], [0.265, 0.690], [0.150, 0.060], D65],
+        ColorPrimaries::EBU_Tech_3213_E => [[0.630, 0.340], [0.295, 0.605], [0.155, 0.077], D65],
+        ColorPrimaries::Unspecified | ColorPrimaries::Unknown => return None,
+    };
+    Some(coordinates)
+}
+
 impl ColorProfileNCLX {
+    /// Creates NCLX color profile with default values of sRGB.
     pub fn new() -> Option<Self> {
         let inner = unsafe { lh::heif_nclx_color_profile_alloc() };
-        (!inner.is_null()).then_some(Self { inner })
+        if inner.is_null() {
+            return None;
+        }
+        let mut profile = Self { inner };
+        profile.set_color_primaries(ColorPrimaries::ITU_R_BT_709_5);
+        Some(profile)
+    }
+
+    fn with_params(
+        color_primaries: ColorPrimaries,
+        transfer_characteristics: TransferCharacteristics,
+        matrix_coefficients: MatrixCoefficients,
+        full_range: bool,
+    ) -> Option<Self> {
+        let mut profile = Self::new()?;
+        profile.set_color_primaries(color_primaries);
+        profile.set_transfer_characteristics(transfer_characteristics);
+        profile.set_matrix_coefficients(matrix_coefficients);
+        profile.set_full_range_flag(full_range as u8);
+        Some(profile)
+    }
+
+    /// sRGB: BT.709 primaries, sRGB transfer function,
+    /// BT.601 matrix coefficients and full range.
+    pub fn srgb() -> Option<Self> {
+        Self::with_params(
+            ColorPrimaries::ITU_R_BT_709_5,
+            TransferCharacteristics::IEC_61966_2_1,
+            MatrixCoefficients::ITU_R_BT_601_6,
+            true,
+        )
+    }
+
+    /// BT.709: BT.709 primaries, transfer function and
+    /// matrix coefficients with limited range.
+    pub fn rec709() -> Option<Self> {
+        Self::with_params(
+            ColorPrimaries::ITU_R_BT_709_5,
+            TransferCharacteristics::ITU_R_BT_709_5,
+            MatrixCoefficients::ITU_R_BT_709_5,
+            false,
+        )
+    }
+
+    /// Display P3: DCI-P3 primaries with D65 white point, sRGB transfer
+    /// function, BT.601 matrix coefficients and full range.
+    pub fn display_p3() -> Option<Self> {
+        Self::with_params(
+            ColorPrimaries::SMPTE_EG_432_1,
+            TransferCharacteristics::IEC_61966_2_1,
+            MatrixCoefficients::ITU_R_BT_601_6,
+            true,
+        )
+    }
+
+    /// BT.2100 PQ (HDR10): BT.2020 primaries, PQ transfer function,
+    /// BT.2020 non-constant luminance matrix coefficients and full range.
+    pub fn rec2100_pq() -> Option<Self> {
+        Self::with_params(
+            ColorPrimaries::ITU_R_BT_2020_2_and_2100_0,
+            TransferCharacteristics::ITU_R_BT_2100_0_PQ,
+            MatrixCoefficients::ITU_R_BT_2020_2_NonConstantLuminance,
+            true,
+        )
+    }
+
+    /// BT.2100 HLG: BT.2020 primaries, HLG transfer function,
+    /// BT.2020 non-constant luminance matrix coefficients and full range.
+    pub fn rec2100_hlg() -> Option<Self> {
+        Self::with_params(
+            ColorPrimaries::ITU_R_BT_2020_2_and_2100_0,
+            TransferCharacteristics::ITU_R_BT_2100_0_HLG,
+            MatrixCoefficients::ITU_R_BT_2020_2_NonConstantLuminance,
+            true,
+        )
     }
 
     #[inline(always)]
@@ -165,9 +329,18 @@ impl ColorProfileNCLX {
         ColorPrimaries::n(self.inner_ref().color_primaries).unwrap_or(ColorPrimaries::Unknown)
     }
 
+    /// Set color primaries. Chromaticity coordinates of primaries and
+    /// white point are also updated if they are known for the given value.
     pub fn set_color_primaries(&mut self, v: ColorPrimaries) {
         if v != ColorPrimaries::Unknown {
             self.inner_mut().color_primaries = v as _;
+            if let Some([red, green, blue, white]) = primaries_coordinates(v) {
+                let inner = self.inner_mut();
+                [inner.color_primary_red_x, inner.color_primary_red_y] = red;
+                [inner.color_primary_green_x, inner.color_primary_green_y] = green;
+                [inner.color_primary_blue_x, inner.color_primary_blue_y] = blue;
+                [inner.color_primary_white_x, inner.color_primary_white_y] = white;
+            }
         }
     }
 
@@ -176,44 +349,128 @@ impl ColorProfileNCLX {
             .unwrap_or(TransferCharacteristics::Unknown)
     }
 
+    pub fn set_transfer_characteristics(&mut self, v: TransferCharacteristics) {
+        if v != TransferCharacteristics::Unknown {
+            self.inner_mut().transfer_characteristics = v as _;
+        }
+    }
+
     pub fn matrix_coefficients(&self) -> MatrixCoefficients {
         MatrixCoefficients::n(self.inner_ref().matrix_coefficients)
             .unwrap_or(MatrixCoefficients::Unknown)
     }
 
+    pub fn set_matrix_coefficients(&mut self, v: MatrixCoefficients) {
+        if v != MatrixCoefficients::Unknown {
+            self.inner_mut().matrix_coefficients = v as _;
+        }
+    }
+
     pub fn full_range_flag(&self) -> u8 {
         self.inner_ref().full_range_flag
     }
 
+    pub fn set_full_range_flag(&mut self, v: u8) {
+        self.inner_mut().full_range_flag = v;
+    }
+
     pub fn color_primary_red_x(&self) -> f32 {
         self.inner_ref().color_primary_red_x
     }
 
+    pub fn set_color_primary_red_x(&mut self, v: f32) {
+        self.inner_mut().color_primary_red_x = v;
+    }
+
     pub fn color_primary_red_y(&self) -> f32 {
         self.inner_ref().color_primary_red_y
     }
 
+    pub fn set_color_primary_red_y(&mut self, v: f32) {
+        self.inner_mut().color_primary_red_y = v;
+    }
+
     pub fn color_primary_green_x(&self) -> f32 {
         self.inner_ref().color_primary_green_x
     }
 
+    pub fn set_color_primary_green_x(&mut self, v: f32) {
+        self.inner_mut().color_primary_green_x = v;
+    }
+
     pub fn color_primary_green_y(&self) -> f32 {
         self.inner_ref().color_primary_green_y
     }
 
+    pub fn set_color_primary_green_y(&mut self, v: f32) {
+        self.inner_mut().color_primary_green_y = v;
+    }
+
     pub fn color_primary_blue_x(&self) -> f32 {
         self.inner_ref().color_primary_blue_x
     }
 
+    pub fn set_color_primary_blue_x(&mut self, v: f32) {
+        self.inner_mut().color_primary_blue_x = v;
+    }
+
     pub fn color_primary_blue_y(&self) -> f32 {
         self.inner_ref().color_primary_blue_y
     }
 
+    pub fn set_color_primary_blue_y(&mut self, v: f32) {
+        self.inner_mut().color_primary_blue_y = v;
+    }
+
     pub fn color_primary_white_x(&self) -> f32 {
         self.inner_ref().color_primary_white_x
     }
 
+    pub fn set_color_primary_white_x(&mut self, v: f32) {
+        self.inner_mut().color_primary_white_x = v;
+    }
+
     pub fn color_primary_white_y(&self) -> f32 {
         self.inner_ref().color_primary_white_y
     }
+
+    pub fn set_color_primary_white_y(&mut self, v: f32) {
+        self.inner_mut().color_primary_white_y = v;
+    }
+}
+
+/// All color profiles of an image.
+///
+/// HEIF file can contain both ICC (`prof` or `rICC`) and NCLX `colr` boxes
+/// for the same image.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColorProfiles {
+    pub icc: Option<ColorProfileRaw>,
+    pub nclx: Option<ColorProfileNCLX>,
+}
+
+/// Color profile which describes colors of an image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EffectiveColorProfile<'a> {
+    Icc(&'a ColorProfileRaw),
+    Nclx(&'a ColorProfileNCLX),
+}
+
+impl ColorProfiles {
+    /// Returns the color profile that describes colors of the image.
+    ///
+    /// ICC profile takes precedence over NCLX profile if both are present.
+    /// In this case NCLX profile is still used by `libheif` to get
+    /// matrix coefficients and range of YCbCr values while converting them
+    /// into RGB.
+    pub fn effective(&self) -> Option<EffectiveColorProfile<'_>> {
+        if let Some(icc) = self.icc.as_ref() {
+            return Some(EffectiveColorProfile::Icc(icc));
+        }
+        self.nclx.as_ref().map(EffectiveColorProfile::Nclx)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.icc.is_none() && self.nclx.is_none()
+    }
 }
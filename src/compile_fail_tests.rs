@@ -0,0 +1,74 @@
+//! Checks that handles can't outlive data borrowed by their context
+//! and that contexts used from any thread can't capture data
+//! that isn't thread-safe.
+//!
+//! A handle may outlive the [`HeifContext`](crate::HeifContext) itself,
+//! but not the bytes or the reader the context was read from.
+//!
+//! Image handle outlives bytes:
+//!
+//! ```compile_fail,E0597
+//! use libheif_rs::HeifContext;
+//!
+//! let handle = {
+//!     let data = std::fs::read("./data/test.heif").unwrap();
+//!     let ctx = HeifContext::read_from_bytes(&data).unwrap();
+//!     ctx.primary_image_handle().unwrap()
+//! };
+//! drop(handle);
+//! ```
+//!
+//! Image handle outlives data of the reader:
+//!
+//! ```compile_fail,E0597
+//! use std::io::Cursor;
+//! use libheif_rs::{HeifContext, StreamReader};
+//!
+//! let handle = {
+//!     let data = std::fs::read("./data/test.heif").unwrap();
+//!     let reader = StreamReader::new(Cursor::new(data.as_slice()), data.len() as u64);
+//!     let ctx = HeifContext::read_from_reader(Box::new(reader)).unwrap();
+//!     ctx.primary_image_handle().unwrap()
+//! };
+//! drop(handle);
+//! ```
+//!
+//! Region items outlive bytes:
+//!
+//! ```compile_fail,E0597
+//! use libheif_rs::HeifContext;
+//!
+//! let regions = {
+//!     let data = std::fs::read("./data/test.heif").unwrap();
+//!     let ctx = HeifContext::read_from_bytes(&data).unwrap();
+//!     let handle = ctx.primary_image_handle().unwrap();
+//!     handle.region_items()
+//! };
+//! drop(regions);
+//! ```
+//!
+//! Track outlives bytes:
+//!
+//! ```compile_fail,E0597
+//! use libheif_rs::HeifContext;
+//!
+//! let track = {
+//!     let data = std::fs::read("./data/test.heif").unwrap();
+//!     let ctx = HeifContext::read_from_bytes(&data).unwrap();
+//!     ctx.track(1)
+//! };
+//! drop(track);
+//! ```
+//!
+//! Context that may be used from any thread can't be read from
+//! a reader that isn't `Send`:
+//!
+//! ```compile_fail,E0277
+//! use std::io::Cursor;
+//! use std::rc::Rc;
+//! use libheif_rs::{HeifContext, StreamReader};
+//!
+//! let data: Rc<[u8]> = std::fs::read("./data/test.heif").unwrap().into();
+//! let reader = StreamReader::new(Cursor::new(data.clone()), data.len() as u64);
+//! let ctx = HeifContext::read_from_reader_send(Box::new(reader)).unwrap();
+//! ```
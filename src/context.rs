@@ -1,34 +1,147 @@
+use std::marker::PhantomData;
 #[cfg(feature = "v1_18")]
 use std::num::NonZeroU16;
 use std::os::raw::c_void;
-use std::{ffi, ptr};
+#[cfg(feature = "v1_19")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "v1_19")]
+use std::sync::Mutex;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::{ffi, mem, ptr};
 
 use four_cc::FourCC;
 use libheif_sys as lh;
 
-use crate::encoder::get_encoding_options_ptr;
-use crate::reader::{Reader, HEIF_READER};
-use crate::utils::str_to_cstring;
+use crate::editing::{self, EditableFile, CDSC, THMB};
+use crate::encoder::{get_encoding_callbacks, get_encoding_options_ptr};
+use crate::image_handle::ItemIds;
+use crate::precompressed;
+#[cfg(feature = "v1_19")]
+use crate::precompressed::ItemProperties;
 #[cfg(feature = "v1_19")]
-use crate::SecurityLimits;
+use crate::progress::ProgressCallbacks;
+use crate::reader::{Reader, ReaderUserData, HEIF_READER};
+use crate::utils::str_to_cstring;
 #[cfg(feature = "v1_20")]
 use crate::Track;
 use crate::{
-    Encoder, EncodingOptions, HeifError, HeifErrorCode, HeifErrorSubCode, Image, ImageHandle,
-    ItemId, Result,
+    CompressionFormat, Encoder, EncodingOptions, HeifError, HeifErrorCode, HeifErrorSubCode, Image,
+    ImageHandle, ItemId, Result,
 };
+#[cfg(feature = "v1_19")]
+use crate::{ProgressStep, SecurityLimits};
 
 #[allow(dead_code)]
 enum Source<'a> {
     None,
     File,
     Memory(&'a [u8]),
-    Reader(Box<Box<dyn Reader + 'a>>),
+    Reader,
+    /// Content of a file opened for editing.
+    Editable(Vec<u8>),
+}
+
+/// State shared by a context and all handles created from it.
+///
+/// `libheif` serializes decoding of items and reading of their data,
+/// but doesn't synchronize modification of the file structure with
+/// other operations. So methods that modify the context or its items
+/// hold the write lock, and methods that decode images or read lists
+/// which may be modified (metadata blocks, thumbnails, region items)
+/// hold the read lock.
+///
+/// Handles also keep the reader alive, because `libheif` reads data
+/// of images through it even after the context is destroyed.
+#[derive(Clone, Default)]
+pub(crate) struct ContextShared<'a> {
+    lock: Arc<RwLock<()>>,
+    reader: Option<Arc<SharedReader<'a>>>,
+    #[cfg(feature = "v1_19")]
+    properties: Arc<PropertiesCache>,
+}
+
+impl ContextShared<'_> {
+    pub(crate) fn read(&self) -> RwLockReadGuard<'_, ()> {
+        self.lock.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock for methods that modify the context.
+    pub(crate) fn write(&self) -> RwLockWriteGuard<'_, ()> {
+        let guard = self.exclusive();
+        #[cfg(feature = "v1_19")]
+        self.properties.generation.fetch_add(1, Ordering::SeqCst);
+        guard
+    }
+
+    /// Lock for methods that don't modify the file structure,
+    /// but `libheif` doesn't allow calling them concurrently
+    /// with other operations (e.g. writing of the file).
+    fn exclusive(&self) -> RwLockWriteGuard<'_, ()> {
+        self.lock.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Properties of items parsed from the serialized context.
+///
+/// The cache is valid while the context isn't modified,
+/// i.e. until the next [`ContextShared::write()`] call.
+#[cfg(feature = "v1_19")]
+#[derive(Default)]
+struct PropertiesCache {
+    generation: AtomicU64,
+    /// Pointer to `libheif` context, generation and parsed properties.
+    cached: Mutex<Option<(usize, u64, Arc<ItemProperties>)>>,
+}
+
+/// Reader passed into `libheif` as user data of [`HEIF_READER`].
+///
+/// User data is stored as an untyped pointer, because [`ReaderUserData`]
+/// is invariant over `'a` and would make the context invariant too.
+struct SharedReader<'a> {
+    user_data: ptr::NonNull<c_void>,
+    phantom: PhantomData<Box<dyn Reader + 'a>>,
+}
+
+impl<'a> SharedReader<'a> {
+    fn new(user_data: ReaderUserData<'a>) -> Self {
+        let user_data = Box::new(user_data);
+        Self {
+            user_data: ptr::NonNull::from(Box::leak(user_data)).cast(),
+            phantom: PhantomData,
+        }
+    }
+
+    fn user_data(&self) -> *mut c_void {
+        self.user_data.as_ptr()
+    }
+
+    fn is_thread_bound(&self) -> bool {
+        let user_data = self.user_data.as_ptr() as *const ReaderUserData<'a>;
+        unsafe { (*user_data).is_thread_bound() }
+    }
+}
+
+// `libheif` calls the reader from threads that decode images through
+// the mutex of user data. A reader that isn't `Send` is bound to the thread
+// that created it: calls from other threads fail, and it is leaked
+// instead of being dropped in another thread.
+unsafe impl Send for SharedReader<'_> {}
+unsafe impl Sync for SharedReader<'_> {}
+
+impl<'a> Drop for SharedReader<'a> {
+    fn drop(&mut self) {
+        let user_data = self.user_data.as_ptr() as *mut ReaderUserData<'a>;
+        let user_data = unsafe { Box::from_raw(user_data) };
+        if !user_data.is_accessible() {
+            mem::forget(user_data);
+        }
+    }
 }
 
 pub struct HeifContext<'a> {
     pub(crate) inner: *mut lh::heif_context,
     source: Source<'a>,
+    pub(crate) shared: ContextShared<'a>,
 }
 
 impl HeifContext<'static> {
@@ -45,6 +158,7 @@ impl HeifContext<'static> {
             Ok(HeifContext {
                 inner: ctx,
                 source: Source::None,
+                shared: ContextShared::default(),
             })
         }
     }
@@ -56,19 +170,50 @@ impl HeifContext<'static> {
         Ok(context)
     }
 
+    /// Open a file for lossless editing of its items.
+    ///
+    /// The whole file is loaded into memory. Changes made by editing
+    /// methods, like [`HeifContext::remove_metadata()`], are applied
+    /// directly to the file structure, and coded data of remaining items
+    /// is written back untouched by [`HeifContext::write_to_bytes()`]
+    /// and [`HeifContext::write_to_file()`].
+    ///
+    /// Methods which encode new images return an error for such context.
+    pub fn edit_from_file(name: &str) -> Result<HeifContext<'static>> {
+        let bytes = std::fs::read(name).map_err(|e| HeifError {
+            code: HeifErrorCode::InputDoesNotExist,
+            sub_code: HeifErrorSubCode::Unspecified,
+            message: e.to_string(),
+        })?;
+        Self::edit_from_bytes(bytes)
+    }
+
+    /// Open bytes of a file for lossless editing of its items.
+    ///
+    /// See [`HeifContext::edit_from_file()`] for details.
+    pub fn edit_from_bytes(bytes: Vec<u8>) -> Result<HeifContext<'static>> {
+        let mut context = HeifContext::new()?;
+        context.read_editable(bytes)?;
+        Ok(context)
+    }
+}
+
+impl<'a> HeifContext<'a> {
     /// # Safety
     ///
     /// The given pointer must be valid.
     #[cfg(feature = "v1_18")]
-    pub(crate) unsafe fn from_ptr(ctx: *mut lh::heif_context) -> HeifContext<'static> {
+    pub(crate) unsafe fn from_ptr(
+        ctx: *mut lh::heif_context,
+        shared: ContextShared<'a>,
+    ) -> HeifContext<'a> {
         HeifContext {
             inner: ctx,
             source: Source::None,
+            shared,
         }
     }
-}
 
-impl<'a> HeifContext<'a> {
     /// Read a HEIF file from a named disk file.
     pub fn read_file(&mut self, name: &str) -> Result<()> {
         self.source = Source::File;
@@ -80,24 +225,70 @@ impl<'a> HeifContext<'a> {
     }
 
     /// Read a HEIF file from the reader.
+    ///
+    /// The reader is used to read data of images while decoding.
+    /// It is dropped after the context and all handles of its images.
+    ///
+    /// The reader isn't required to be `Send`, so it is bound to
+    /// the current thread:
+    /// - images of the context can be decoded only in the current thread,
+    ///   decoding in other threads fails with a read error;
+    /// - tiles of images are decoded in the current thread, regardless
+    ///   of [`HeifContext::set_max_decoding_threads()`];
+    /// - if the last handle of the context is dropped in another thread,
+    ///   the reader is leaked instead of being dropped.
+    ///
+    /// Use [`HeifContext::read_reader_send()`] to read a context that
+    /// may be used from any thread.
     pub fn read_reader(&mut self, reader: Box<dyn Reader + 'a>) -> Result<()> {
-        let mut reader_box = Box::new(reader);
-        let user_data = reader_box.as_mut() as *mut _ as *mut c_void;
+        self.read_shared_reader(SharedReader::new(ReaderUserData::new_thread_bound(reader)))?;
+        unsafe { lh::heif_context_set_max_decoding_threads(self.inner, 0) };
+        Ok(())
+    }
+
+    /// Read a HEIF file from the reader that may be used from any thread.
+    ///
+    /// The reader is used to read data of images while decoding,
+    /// so it is called from threads that decode images of the context.
+    /// Calls of the reader are serialized.
+    /// It is dropped after the context and all handles of its images.
+    pub fn read_reader_send(&mut self, reader: Box<dyn Reader + Send + 'a>) -> Result<()> {
+        self.read_shared_reader(SharedReader::new(ReaderUserData::new(reader)))
+    }
+
+    fn read_shared_reader(&mut self, reader: SharedReader<'a>) -> Result<()> {
         let err = unsafe {
-            lh::heif_context_read_from_reader(self.inner, &HEIF_READER, user_data, ptr::null())
+            lh::heif_context_read_from_reader(
+                self.inner,
+                &HEIF_READER,
+                reader.user_data(),
+                ptr::null(),
+            )
         };
         HeifError::from_heif_error(err)?;
-        self.source = Source::Reader(reader_box);
+        self.source = Source::Reader;
+        self.shared.reader = Some(Arc::new(reader));
         Ok(())
     }
 
     /// Create a new context from the reader.
+    ///
+    /// The reader is bound to the current thread,
+    /// see [`HeifContext::read_reader()`] for details.
     pub fn read_from_reader(reader: Box<dyn Reader + 'a>) -> Result<HeifContext<'a>> {
         let mut context = HeifContext::new()?;
         context.read_reader(reader)?;
         Ok(context)
     }
 
+    /// Create a new context from the reader that may be used
+    /// from any thread.
+    pub fn read_from_reader_send(reader: Box<dyn Reader + Send + 'a>) -> Result<HeifContext<'a>> {
+        let mut context = HeifContext::new()?;
+        context.read_reader_send(reader)?;
+        Ok(context)
+    }
+
     /// Create a new context from bytes.
     ///
     /// The provided memory buffer is not copied.
@@ -128,6 +319,74 @@ impl<'a> HeifContext<'a> {
         Ok(())
     }
 
+    fn read_editable(&mut self, bytes: Vec<u8>) -> Result<()> {
+        // Data is copied by libheif because image handles
+        // may outlive the current version of the file.
+        #[allow(deprecated)]
+        let err = unsafe {
+            lh::heif_context_read_from_memory(
+                self.inner,
+                bytes.as_ptr() as _,
+                bytes.len(),
+                ptr::null(),
+            )
+        };
+        HeifError::from_heif_error(err)?;
+        self.source = Source::Editable(bytes);
+        Ok(())
+    }
+
+    /// Applies changes to the file structure and re-reads the context.
+    ///
+    /// A new context is serialized, changed and read again, but it isn't
+    /// converted into editable one, so images may still be encoded into it.
+    /// Contexts created by other `read_*()` methods can't be edited.
+    fn edit<R>(&mut self, change: impl FnOnce(&mut EditableFile) -> Result<R>) -> Result<R> {
+        let new_bytes;
+        let bytes = match &self.source {
+            Source::Editable(bytes) => bytes.as_slice(),
+            Source::None => {
+                new_bytes = self.write_to_bytes()?;
+                new_bytes.as_slice()
+            }
+            _ => {
+                return Err(HeifError {
+                    code: HeifErrorCode::UsageError,
+                    sub_code: HeifErrorSubCode::Unspecified,
+                    message: "Context must be opened with HeifContext::edit_from_file() \
+                        or HeifContext::edit_from_bytes() to be edited"
+                        .to_string(),
+                })
+            }
+        };
+        let mut file = EditableFile::parse(bytes)?;
+        let result = change(&mut file)?;
+        let bytes = file.write();
+
+        let mut context = HeifContext::new()?;
+        #[cfg(feature = "v1_19")]
+        context.set_security_limits(&self.security_limits())?;
+        context.read_editable(bytes)?;
+        let _lock = self.shared.write();
+        std::mem::swap(&mut self.inner, &mut context.inner);
+        if let Source::Editable(_) = self.source {
+            self.source = std::mem::replace(&mut context.source, Source::None);
+        }
+        Ok(result)
+    }
+
+    fn check_not_editable(&self) -> Result<()> {
+        if matches!(self.source, Source::Editable(_)) {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::Unspecified,
+                message: "Context opened for editing doesn't support encoding of images"
+                    .to_string(),
+            });
+        }
+        Ok(())
+    }
+
     unsafe extern "C" fn vector_writer(
         _ctx: *mut lh::heif_context,
         data: *const c_void,
@@ -147,6 +406,9 @@ impl<'a> HeifContext<'a> {
     }
 
     pub fn write_to_bytes(&self) -> Result<Vec<u8>> {
+        if let Source::Editable(bytes) = &self.source {
+            return Ok(bytes.clone());
+        }
         let mut res = Vec::<u8>::new();
         let pointer_to_res = &mut res as *mut _ as *mut c_void;
 
@@ -155,13 +417,45 @@ impl<'a> HeifContext<'a> {
             write: Some(Self::vector_writer),
         };
 
+        let _lock = self.shared.exclusive();
         let err = unsafe { lh::heif_context_write(self.inner, &mut writer, pointer_to_res) };
         HeifError::from_heif_error(err)?;
         Ok(res)
     }
 
+    /// Returns properties of items of the context.
+    ///
+    /// `libheif` doesn't give access to some properties (e.g. codec
+    /// configuration boxes), so the context is serialized and parsed
+    /// once and the result is reused until the context is modified.
+    #[cfg(feature = "v1_19")]
+    pub(crate) fn item_properties(&self) -> Result<Arc<ItemProperties>> {
+        let cache = &self.shared.properties;
+        let context_ptr = self.inner as usize;
+        // The generation is read before serialization, so properties of
+        // the context modified after this point are never cached with it.
+        let generation = cache.generation.load(Ordering::SeqCst);
+        let lock_cache = || cache.cached.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((ptr, cached_generation, properties)) = lock_cache().as_ref() {
+            if *ptr == context_ptr && *cached_generation == generation {
+                return Ok(properties.clone());
+            }
+        }
+        let properties = Arc::new(ItemProperties::parse(&self.write_to_bytes()?));
+        *lock_cache() = Some((context_ptr, generation, properties.clone()));
+        Ok(properties)
+    }
+
     pub fn write_to_file(&self, name: &str) -> Result<()> {
+        if let Source::Editable(bytes) = &self.source {
+            return std::fs::write(name, bytes).map_err(|e| HeifError {
+                code: HeifErrorCode::EncodingError,
+                sub_code: HeifErrorSubCode::CannotWriteOutputData,
+                message: e.to_string(),
+            });
+        }
         let c_name = ffi::CString::new(name).unwrap();
+        let _lock = self.shared.exclusive();
         let err = unsafe { lh::heif_context_write_to_file(self.inner, c_name.as_ptr()) };
         HeifError::from_heif_error(err)
     }
@@ -210,58 +504,138 @@ impl<'a> HeifContext<'a> {
     }
 
     /// Get the image handle for a known image ID.
-    pub fn image_handle(&self, item_id: ItemId) -> Result<ImageHandle> {
+    pub fn image_handle(&self, item_id: ItemId) -> Result<ImageHandle<'a>> {
         let mut handle: *mut lh::heif_image_handle = ptr::null_mut();
         let err = unsafe { lh::heif_context_get_image_handle(self.inner, item_id, &mut handle) };
         HeifError::from_heif_error(err)?;
-        Ok(ImageHandle::new(handle))
+        Ok(ImageHandle::new(handle, self.shared.clone()))
     }
 
     /// Get a handle to the primary image of the HEIF file.
     ///
     /// This is the image that should be displayed primarily
     /// when there are several images in the file.
-    pub fn primary_image_handle(&self) -> Result<ImageHandle> {
+    pub fn primary_image_handle(&self) -> Result<ImageHandle<'a>> {
         let mut handle: *mut lh::heif_image_handle = ptr::null_mut();
         let err = unsafe { lh::heif_context_get_primary_image_handle(self.inner, &mut handle) };
         HeifError::from_heif_error(err)?;
-        Ok(ImageHandle::new(handle))
+        Ok(ImageHandle::new(handle, self.shared.clone()))
+    }
+
+    /// Returns an iterator over handles of top level images.
+    ///
+    /// IDs of images are read when the iterator is created,
+    /// handles are created lazily. The iterator doesn't allocate memory
+    /// unless the file has more than 16 top level images.
+    pub fn top_level_images(&self) -> impl Iterator<Item = Result<ImageHandle<'a>>> + '_ {
+        let count = unsafe { lh::heif_context_get_number_of_top_level_images(self.inner) };
+        ItemIds::new(count.max(0) as usize, |item_ids| unsafe {
+            lh::heif_context_get_list_of_top_level_image_IDs(
+                self.inner,
+                item_ids.as_mut_ptr(),
+                item_ids.len() as _,
+            )
+            .max(0) as usize
+        })
+        .map(move |item_id| self.image_handle(item_id))
     }
 
     /// Returns a vector with top level image handles.
-    pub fn top_level_image_handles(&self) -> Vec<ImageHandle> {
-        let item_ids = self.image_ids();
-        let mut handles = Vec::with_capacity(item_ids.len());
-        for item_id in item_ids {
-            if let Ok(handle) = self.image_handle(item_id) {
-                handles.push(handle);
-            }
-        }
-        handles
+    ///
+    /// Images whose handles can't be created are silently skipped.
+    #[deprecated(since = "3.0.0", note = "use 'image_handles' method instead.")]
+    pub fn top_level_image_handles(&self) -> Vec<ImageHandle<'a>> {
+        self.top_level_images().filter_map(Result::ok).collect()
+    }
+
+    /// Returns a vector with top level image handles.
+    ///
+    /// Returns the first error if some of the handles can't be created.
+    pub fn image_handles(&self) -> Result<Vec<ImageHandle<'a>>> {
+        self.top_level_images().collect()
     }
 
     /// Compress the input image.
     ///
     /// The first image added to the context is also automatically set as the primary image, but
     /// you can change the primary image later with [`HeifContext::set_primary_image`] method.
+    ///
+    /// Encoding is reported to the progress handler of `encoding_options`
+    /// as a single unit of work of [`ProgressStep::Total`] step.
+    /// The cancellation token of `encoding_options` is checked only before
+    /// the codec starts, a running codec can't be interrupted.
     pub fn encode_image(
         &mut self,
         image: &Image,
         encoder: &mut Encoder,
         encoding_options: Option<EncodingOptions>,
-    ) -> Result<ImageHandle> {
-        let mut handle: *mut lh::heif_image_handle = ptr::null_mut();
-        unsafe {
-            let err = lh::heif_context_encode_image(
-                self.inner,
-                image.inner,
-                encoder.inner,
-                get_encoding_options_ptr(&encoding_options),
-                &mut handle,
-            );
-            HeifError::from_heif_error(err)?;
+    ) -> Result<ImageHandle<'a>> {
+        self.check_not_editable()?;
+        let encode = || {
+            let mut handle: *mut lh::heif_image_handle = ptr::null_mut();
+            let _lock = self.shared.write();
+            unsafe {
+                let err = lh::heif_context_encode_image(
+                    self.inner,
+                    image.inner,
+                    encoder.inner,
+                    get_encoding_options_ptr(&encoding_options),
+                    &mut handle,
+                );
+                HeifError::from_heif_error(err)?;
+            }
+            Ok(ImageHandle::new(handle, self.shared.clone()))
+        };
+        match get_encoding_callbacks(&encoding_options) {
+            Some(callbacks) => callbacks.run_single_step(encode),
+            None => encode(),
         }
-        Ok(ImageHandle::new(handle))
+    }
+
+    /// Add an image from already compressed data without re-encoding it.
+    ///
+    /// Supported formats are [`CompressionFormat::Hevc`] and [`CompressionFormat::Av1`].
+    ///
+    /// * `config_record` - payload of `hvcC` or `av1C` box without the box header
+    ///   (`HEVCDecoderConfigurationRecord` or `AV1CodecConfigurationRecord`).
+    /// * `bitstream` - coded data in the format of image item data: for HEVC these are
+    ///   NAL units prefixed with their sizes (without start codes), for AV1
+    ///   these are OBUs including the sequence header OBU.
+    /// * `width` and `height` - size of the image. It may be less than the coded size,
+    ///   in this case the image is cropped by `clap` property.
+    ///
+    /// `libheif` builds the configuration box and other properties of the image
+    /// item from parameter sets (HEVC) or the sequence header (AV1) of coded data.
+    ///
+    /// Internally the data is passed into `libheif` by a special encoder plugin
+    /// with IDs `libheif-rs-precompressed-hevc` and `libheif-rs-precompressed-av1`
+    /// and the lowest priority. The plugin is registered on first use and stays
+    /// registered until `libheif` is deinitialized. It isn't returned by
+    /// [`LibHeif::encoder_descriptors()`](crate::LibHeif::encoder_descriptors)
+    /// and [`LibHeif::encoder_for_format()`](crate::LibHeif::encoder_for_format),
+    /// but the registration is global, so other users of `libheif` in the process
+    /// (e.g. C code that lists encoders or calls `heif_context_get_encoder_for_format()`
+    /// when there is no other encoder for the format) can see it. The plugin
+    /// only accepts data passed by this method, it fails to encode other images.
+    ///
+    /// Like with [`HeifContext::encode_image`], the first image added to
+    /// the context is also automatically set as the primary image.
+    pub fn add_precompressed_image(
+        &mut self,
+        format: CompressionFormat,
+        config_record: &[u8],
+        bitstream: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<ImageHandle<'a>> {
+        precompressed::add_precompressed_image(
+            self,
+            format,
+            config_record,
+            bitstream,
+            width,
+            height,
+        )
     }
 
     /// Encode the `image` as a scaled down thumbnail image.
@@ -274,6 +648,12 @@ impl<'a> HeifContext<'a> {
     /// The encoded thumbnail is automatically assigned to the
     /// `master_image_handle`. Hence, you do not have to call
     /// [`HeifContext::assign_thumbnail()`] method.
+    ///
+    /// Note: `libheif` scales the image by "nearest neighbor" method.
+    /// To get a smoother thumbnail, scale the image with help of
+    /// [`Image::scale()`] and another [`ScalingFilter`](crate::ScalingFilter),
+    /// then encode it with [`HeifContext::encode_image()`] and assign
+    /// as a thumbnail with [`HeifContext::assign_thumbnail()`].
     pub fn encode_thumbnail(
         &mut self,
         image: &Image,
@@ -281,21 +661,29 @@ impl<'a> HeifContext<'a> {
         bbox_size: u32,
         encoder: &mut Encoder,
         encoding_options: Option<EncodingOptions>,
-    ) -> Result<Option<ImageHandle>> {
-        let mut handle: *mut lh::heif_image_handle = ptr::null_mut();
-        unsafe {
-            let err = lh::heif_context_encode_thumbnail(
-                self.inner,
-                image.inner,
-                master_image_handle.inner,
-                encoder.inner,
-                get_encoding_options_ptr(&encoding_options),
-                bbox_size.min(i32::MAX as _) as _,
-                &mut handle,
-            );
-            HeifError::from_heif_error(err)?;
+    ) -> Result<Option<ImageHandle<'a>>> {
+        self.check_not_editable()?;
+        let encode = || {
+            let mut handle: *mut lh::heif_image_handle = ptr::null_mut();
+            let _lock = self.shared.write();
+            unsafe {
+                let err = lh::heif_context_encode_thumbnail(
+                    self.inner,
+                    image.inner,
+                    master_image_handle.inner,
+                    encoder.inner,
+                    get_encoding_options_ptr(&encoding_options),
+                    bbox_size.min(i32::MAX as _) as _,
+                    &mut handle,
+                );
+                HeifError::from_heif_error(err)?;
+            }
+            Ok(Some(ImageHandle::new(handle, self.shared.clone())))
+        };
+        match get_encoding_callbacks(&encoding_options) {
+            Some(callbacks) => callbacks.run_single_step(encode),
+            None => encode(),
         }
-        Ok(Some(ImageHandle::new(handle)))
     }
 
     /// Encodes an array of images into a grid.
@@ -310,6 +698,27 @@ impl<'a> HeifContext<'a> {
     /// * `encoding_options` - Optional, may be None.
     ///
     /// Returns an error if `tiles` slice is empty.
+    ///
+    /// # Progress and cancellation
+    ///
+    /// Progress is reported per tile only if the linked `libheif`
+    /// is **1.23 or newer** (and the crate is built with `v1_19` feature).
+    /// In this case, if `encoding_options` have a progress handler or
+    /// a cancellation token, tiles are encoded one by one, progress is
+    /// reported after encoding of each tile, and cancellation is checked
+    /// before encoding of each tile. If encoding is canceled, the context
+    /// contains an incomplete grid image and should be discarded.
+    ///
+    /// With older versions of `libheif` (1.22 and older, i.e. every version
+    /// matched by feature flags of the crate), the whole grid is encoded
+    /// by a single call of `libheif`. It is reported as a single unit of work
+    /// of [`ProgressStep::Total`] step, and the cancellation token is
+    /// checked only once before encoding. These versions don't apply
+    /// encoding options to separately added tiles and don't allow
+    /// to add tiles encoded as separate images, so encoding of tiles
+    /// one by one would change the result.
+    ///
+    /// In any case, a codec can't be interrupted while it encodes a tile.
     #[cfg(feature = "v1_18")]
     pub fn encode_grid(
         &mut self,
@@ -317,25 +726,107 @@ impl<'a> HeifContext<'a> {
         rows: NonZeroU16,
         encoder: &mut Encoder,
         encoding_options: Option<EncodingOptions>,
-    ) -> Result<Option<ImageHandle>> {
+    ) -> Result<Option<ImageHandle<'a>>> {
+        self.check_not_editable()?;
+        #[cfg(feature = "v1_19")]
+        if let Some(callbacks) = get_encoding_callbacks(&encoding_options) {
+            let per_tile_callbacks =
+                callbacks.has_progress_handler() || callbacks.cancellation_token().is_some();
+            if per_tile_callbacks && tiles_use_encoding_options() {
+                return self
+                    .encode_grid_by_tiles(tiles, rows, encoder, &encoding_options, callbacks)
+                    .map(Some);
+            }
+        }
+
+        let encode = || {
+            let mut handle: *mut lh::heif_image_handle = ptr::null_mut();
+            let mut tiles_inners: Vec<*mut lh::heif_image> =
+                tiles.iter().map(|img| img.inner).collect();
+            let rows = rows.get();
+            let columns = (tiles_inners.len() as u32 / rows as u32).min(u16::MAX as _) as u16;
+            let _lock = self.shared.write();
+            unsafe {
+                let err = lh::heif_context_encode_grid(
+                    self.inner,
+                    tiles_inners.as_mut_ptr(),
+                    rows,
+                    columns,
+                    encoder.inner,
+                    get_encoding_options_ptr(&encoding_options),
+                    &mut handle,
+                );
+                HeifError::from_heif_error(err)?;
+            }
+            Ok(Some(ImageHandle::new(handle, self.shared.clone())))
+        };
+        match get_encoding_callbacks(&encoding_options) {
+            Some(callbacks) => callbacks.run_single_step(encode),
+            None => encode(),
+        }
+    }
+
+    /// Encodes tiles one by one with help of `heif_context_add_image_tile()`.
+    ///
+    /// Encoding options are passed into `heif_context_add_grid_image()`,
+    /// `libheif` stores them in the grid item and applies to every tile.
+    #[cfg(feature = "v1_19")]
+    fn encode_grid_by_tiles(
+        &mut self,
+        tiles: &[Image],
+        rows: NonZeroU16,
+        encoder: &mut Encoder,
+        encoding_options: &Option<EncodingOptions>,
+        callbacks: &ProgressCallbacks,
+    ) -> Result<ImageHandle<'a>> {
+        let rows = rows.get() as u32;
+        let columns = (tiles.len() as u32 / rows).min(u16::MAX as _);
+        let Some(first_tile) = tiles.first().filter(|_| columns > 0) else {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::InvalidParameterValue,
+                message: "Number of tiles is less than number of rows".to_string(),
+            });
+        };
+        callbacks.check_cancellation()?;
+
         let mut handle: *mut lh::heif_image_handle = ptr::null_mut();
-        let mut tiles_inners: Vec<*mut lh::heif_image> =
-            tiles.iter().map(|img| img.inner).collect();
-        let rows = rows.get();
-        let columns = (tiles_inners.len() as u32 / rows as u32).min(u16::MAX as _) as u16;
-        unsafe {
-            let err = lh::heif_context_encode_grid(
+        let err = unsafe {
+            let _lock = self.shared.write();
+            lh::heif_context_add_grid_image(
                 self.inner,
-                tiles_inners.as_mut_ptr(),
-                rows,
+                first_tile.width() * columns,
+                first_tile.height() * rows,
                 columns,
-                encoder.inner,
-                get_encoding_options_ptr(&encoding_options),
+                rows,
+                get_encoding_options_ptr(encoding_options),
                 &mut handle,
-            );
+            )
+        };
+        HeifError::from_heif_error(err)?;
+        let grid_handle = ImageHandle::new(handle, self.shared.clone());
+
+        let tiles_count = rows * columns;
+        callbacks.start(ProgressStep::Total, tiles_count);
+        for (i, tile) in tiles.iter().take(tiles_count as usize).enumerate() {
+            callbacks.check_cancellation()?;
+            let i = i as u32;
+            let err = unsafe {
+                let _lock = self.shared.write();
+                lh::heif_context_add_image_tile(
+                    self.inner,
+                    grid_handle.inner,
+                    i % columns,
+                    i / columns,
+                    tile.inner,
+                    encoder.inner,
+                )
+            };
             HeifError::from_heif_error(err)?;
+            callbacks.progress(ProgressStep::Total, i + 1);
         }
-        Ok(Some(ImageHandle::new(handle)))
+        callbacks.end(ProgressStep::Total);
+        Ok(grid_handle)
     }
 
     /// Assign `master_image_handle` as the thumbnail image of `thumbnail_image_handle`.
@@ -344,6 +835,16 @@ impl<'a> HeifContext<'a> {
         master_image_handle: &ImageHandle,
         thumbnail_image_handle: &ImageHandle,
     ) -> Result<()> {
+        if let Source::Editable(_) = self.source {
+            return self.edit(|file| {
+                file.add_reference(
+                    THMB,
+                    thumbnail_image_handle.item_id(),
+                    master_image_handle.item_id(),
+                )
+            });
+        }
+        let _lock = self.shared.write();
         unsafe {
             let err = lh::heif_context_assign_thumbnail(
                 self.inner,
@@ -355,6 +856,10 @@ impl<'a> HeifContext<'a> {
     }
 
     pub fn set_primary_image(&mut self, image_handle: &mut ImageHandle) -> Result<()> {
+        if let Source::Editable(_) = self.source {
+            return self.edit(|file| file.set_primary_item_id(image_handle.item_id()));
+        }
+        let _lock = self.shared.write();
         unsafe {
             let err = lh::heif_context_set_primary_image(self.inner, image_handle.inner);
             HeifError::from_heif_error(err)
@@ -379,12 +884,25 @@ impl<'a> HeifContext<'a> {
     where
         T: Into<FourCC>,
     {
-        let c_item_type = str_to_cstring(&item_type.into().to_string(), "item_type")?;
+        let item_type = item_type.into();
+        if let Source::Editable(_) = self.source {
+            return self.edit(|file| {
+                file.add_metadata_item(
+                    image_handle.item_id(),
+                    item_type.0,
+                    content_type,
+                    data.to_vec(),
+                )
+                .map(|_| ())
+            });
+        }
+        let c_item_type = str_to_cstring(&item_type.to_string(), "item_type")?;
         let c_content_type = match content_type {
             Some(s) => Some(str_to_cstring(s, "content_type")?),
             None => None,
         };
         let c_content_type_ptr = c_content_type.map(|s| s.as_ptr()).unwrap_or(ptr::null());
+        let _lock = self.shared.write();
         let error = unsafe {
             lh::heif_context_add_generic_metadata(
                 self.inner,
@@ -400,6 +918,14 @@ impl<'a> HeifContext<'a> {
 
     /// Add EXIF metadata to an image.
     pub fn add_exif_metadata(&mut self, master_image: &ImageHandle, data: &[u8]) -> Result<()> {
+        if let Source::Editable(_) = self.source {
+            let data = editing::exif_item_data(data)?;
+            return self.edit(|file| {
+                file.add_metadata_item(master_image.item_id(), *b"Exif", None, data)
+                    .map(|_| ())
+            });
+        }
+        let _lock = self.shared.write();
         let error = unsafe {
             lh::heif_context_add_exif_metadata(
                 self.inner,
@@ -413,6 +939,18 @@ impl<'a> HeifContext<'a> {
 
     /// Add XMP metadata to an image.
     pub fn add_xmp_metadata(&mut self, master_image: &ImageHandle, data: &[u8]) -> Result<()> {
+        if let Source::Editable(_) = self.source {
+            return self.edit(|file| {
+                file.add_metadata_item(
+                    master_image.item_id(),
+                    *b"mime",
+                    Some("application/rdf+xml"),
+                    data.to_vec(),
+                )
+                .map(|_| ())
+            });
+        }
+        let _lock = self.shared.write();
         let error = unsafe {
             lh::heif_context_add_XMP_metadata(
                 self.inner,
@@ -424,6 +962,105 @@ impl<'a> HeifContext<'a> {
         HeifError::from_heif_error(error)
     }
 
+    /// Remove a metadata block attached to the image.
+    ///
+    /// If the metadata block is attached to other images too,
+    /// only its reference to the given image is removed.
+    /// A new context (see [`HeifContext::new()`]) is serialized, changed
+    /// and read again, so images may still be encoded into it.
+    ///
+    /// Image handles obtained before this call describe
+    /// the previous state of the file.
+    pub fn remove_metadata(
+        &mut self,
+        image_handle: &ImageHandle,
+        metadata_id: ItemId,
+    ) -> Result<()> {
+        self.edit(|file| file.detach_item(CDSC, metadata_id, image_handle.item_id()))
+    }
+
+    /// Replace data of a metadata block attached to the image.
+    ///
+    /// `data` is stored as is, so it must have the same format as data returned by
+    /// [`ImageHandle::metadata()`]. For example, Exif data must start with
+    /// 4-byte offset of the TIFF header.
+    /// A new context (see [`HeifContext::new()`]) is serialized, changed
+    /// and read again, so images may still be encoded into it.
+    ///
+    /// Image handles obtained before this call describe
+    /// the previous state of the file.
+    pub fn replace_metadata(
+        &mut self,
+        image_handle: &ImageHandle,
+        metadata_id: ItemId,
+        data: &[u8],
+    ) -> Result<()> {
+        self.edit(|file| {
+            check_metadata(file, image_handle, metadata_id)?;
+            file.set_item_data(metadata_id, data.to_vec())
+        })
+    }
+
+    /// Remove an item from the file.
+    ///
+    /// Items which depend on the removed item are removed too:
+    /// its thumbnails, auxiliary images (alpha, depth), metadata blocks
+    /// and region items, and also hidden items used only to construct it,
+    /// like tiles of a grid image. References, location of data and
+    /// properties that are not used anymore are removed on writing.
+    ///
+    /// The primary image can't be removed.
+    /// A new context (see [`HeifContext::new()`]) is serialized, changed
+    /// and read again, so images may still be encoded into it.
+    ///
+    /// Image handles obtained before this call describe
+    /// the previous state of the file.
+    pub fn remove_item(&mut self, item_id: ItemId) -> Result<()> {
+        self.edit(|file| file.remove_item(item_id))
+    }
+
+    /// Remove the thumbnail of the master image.
+    ///
+    /// If the thumbnail is assigned to other images too,
+    /// only its reference to the master image is removed.
+    /// See [`HeifContext::remove_item()`] for details.
+    pub fn remove_thumbnail(
+        &mut self,
+        master_image_handle: &ImageHandle,
+        thumbnail_image_handle: &ImageHandle,
+    ) -> Result<()> {
+        self.edit(|file| {
+            file.detach_item(
+                THMB,
+                thumbnail_image_handle.item_id(),
+                master_image_handle.item_id(),
+            )
+        })
+    }
+
+    /// Remove the region item of the image.
+    ///
+    /// If the region item is attached to other images too,
+    /// only its reference to the given image is removed.
+    /// See [`HeifContext::remove_item()`] for details.
+    #[cfg(feature = "v1_18")]
+    pub fn remove_region_item(
+        &mut self,
+        image_handle: &ImageHandle,
+        region_item_id: ItemId,
+    ) -> Result<()> {
+        self.edit(|file| {
+            if file.item_type(region_item_id) != Some(*b"rgan") {
+                return Err(HeifError {
+                    code: HeifErrorCode::UsageError,
+                    sub_code: HeifErrorSubCode::NonExistingItemReferenced,
+                    message: format!("Item {} is not a region item", region_item_id),
+                });
+            }
+            file.detach_item(CDSC, region_item_id, image_handle.item_id())
+        })
+    }
+
     /// If the maximum threads number is set to 0, the image tiles are
     /// decoded in the main thread. This is different from setting it to 1,
     /// which will generate a single background thread to decode the tiles.
@@ -432,8 +1069,20 @@ impl<'a> HeifContext<'a> {
     /// may still use multi-threaded decoding. You can use it, for example,
     /// in cases where you are decoding several images in parallel anyway you
     /// thus want to minimize parallelism in each decoder.
+    ///
+    /// Tiles of images of a context read by [`HeifContext::read_reader()`]
+    /// are always decoded in the main thread.
     pub fn set_max_decoding_threads(&mut self, max_threads: u32) {
+        if self
+            .shared
+            .reader
+            .as_ref()
+            .is_some_and(|r| r.is_thread_bound())
+        {
+            return;
+        }
         let max_threads = max_threads.min(libc::c_int::MAX as u32) as libc::c_int;
+        let _lock = self.shared.write();
         unsafe { lh::heif_context_set_max_decoding_threads(self.inner, max_threads) };
     }
 
@@ -451,6 +1100,7 @@ impl<'a> HeifContext<'a> {
     /// Overwrites the security limits of a context.
     #[cfg(feature = "v1_19")]
     pub fn set_security_limits(&mut self, limits: &SecurityLimits) -> Result<()> {
+        let _lock = self.shared.write();
         let err = unsafe { lh::heif_context_set_security_limits(self.inner, limits.as_inner()) };
         HeifError::from_heif_error(err)
     }
@@ -503,22 +1153,53 @@ impl<'a> HeifContext<'a> {
     /// Tracks never have a zero ID.
     /// This is why we can use this as a special value to find the first visual track.
     #[cfg(feature = "v1_20")]
-    pub fn track(&self, id: u32) -> Option<Track> {
+    pub fn track(&self, id: u32) -> Option<Track<'a>> {
         unsafe {
             let heif_track = lh::heif_context_get_track(self.inner, id);
             if heif_track.is_null() {
                 None
             } else {
-                Some(Track::from_heif_track(heif_track))
+                Some(Track::from_heif_track(heif_track, self.shared.clone()))
             }
         }
     }
 }
 
+/// `libheif` applies encoding options of a grid image to tiles
+/// added with `heif_context_add_image_tile()` since version 1.23.
+#[cfg(feature = "v1_19")]
+fn tiles_use_encoding_options() -> bool {
+    let version = unsafe { lh::heif_get_version_number() };
+    version >> 16 >= 0x0117
+}
+
+fn check_metadata(
+    file: &EditableFile,
+    image_handle: &ImageHandle,
+    metadata_id: ItemId,
+) -> Result<()> {
+    if file.has_reference(CDSC, metadata_id, image_handle.item_id()) {
+        Ok(())
+    } else {
+        Err(HeifError {
+            code: HeifErrorCode::UsageError,
+            sub_code: HeifErrorSubCode::NonExistingItemReferenced,
+            message: format!(
+                "Metadata block {} is not attached to the image {}",
+                metadata_id,
+                image_handle.item_id()
+            ),
+        })
+    }
+}
+
 impl Drop for HeifContext<'_> {
     fn drop(&mut self) {
         unsafe { lh::heif_context_free(self.inner) };
     }
 }
 
+// Methods that modify the context require `&mut self` or hold the write lock,
+// other methods only read the context or are synchronized by `libheif`.
 unsafe impl Send for HeifContext<'_> {}
+unsafe impl Sync for HeifContext<'_> {}
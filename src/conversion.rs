@@ -0,0 +1,490 @@
+use crate::scaling::PlaneBuffer;
+use crate::{
+    Channel, Chroma, ChromaDownsamplingAlgorithm, ChromaUpsamplingAlgorithm,
+    ColorConversionOptions, ColorSpace, HeifError, HeifErrorCode, HeifErrorSubCode, Image,
+    MatrixCoefficients, Result, RgbChroma,
+};
+
+/// Coefficients used to convert colors between RGB and YCbCr.
+#[derive(Debug, Copy, Clone)]
+enum Matrix {
+    /// `Y` contains G, `Cb` contains B, `Cr` contains R.
+    Identity,
+    YCgCo,
+    KrKb(f32, f32),
+}
+
+const BT_601: Matrix = Matrix::KrKb(0.299, 0.114);
+
+impl From<MatrixCoefficients> for Matrix {
+    fn from(value: MatrixCoefficients) -> Self {
+        match value {
+            MatrixCoefficients::RGB_GBR => Matrix::Identity,
+            MatrixCoefficients::YCgCo => Matrix::YCgCo,
+            MatrixCoefficients::ITU_R_BT_709_5 => Matrix::KrKb(0.2126, 0.0722),
+            MatrixCoefficients::US_FCC_T47 => Matrix::KrKb(0.30, 0.11),
+            MatrixCoefficients::SMPTE_240M => Matrix::KrKb(0.212, 0.087),
+            MatrixCoefficients::ITU_R_BT_2020_2_NonConstantLuminance
+            | MatrixCoefficients::ITU_R_BT_2020_2_ConstantLuminance => Matrix::KrKb(0.2627, 0.0593),
+            _ => BT_601,
+        }
+    }
+}
+
+/// Parameters of YCbCr representation of colors.
+#[derive(Debug, Copy, Clone)]
+struct YCbCrParams {
+    matrix: Matrix,
+    full_range: bool,
+}
+
+impl YCbCrParams {
+    /// Parameters from NCLX profile of the image.
+    /// BT.601 full range is used for images without NCLX profile.
+    fn of_image(image: &Image) -> Self {
+        match image.color_profile_nclx() {
+            Some(profile) => Self {
+                matrix: profile.matrix_coefficients().into(),
+                full_range: profile.full_range_flag() != 0,
+            },
+            None => Self {
+                matrix: BT_601,
+                full_range: true,
+            },
+        }
+    }
+
+    /// Convert normalized luma and chroma (in range `-0.5..=0.5`) into RGB.
+    fn ycbcr_to_rgb(self, y: f32, cb: f32, cr: f32) -> [f32; 3] {
+        match self.matrix {
+            Matrix::Identity => [cr, y, cb],
+            Matrix::YCgCo => {
+                let t = y - cb;
+                [t + cr, y + cb, t - cr]
+            }
+            Matrix::KrKb(kr, kb) => {
+                let kg = 1. - kr - kb;
+                let r = y + 2. * (1. - kr) * cr;
+                let b = y + 2. * (1. - kb) * cb;
+                let g = (y - kr * r - kb * b) / kg;
+                [r, g, b]
+            }
+        }
+    }
+
+    fn rgb_to_ycbcr(self, [r, g, b]: [f32; 3]) -> [f32; 3] {
+        match self.matrix {
+            Matrix::Identity => [g, b, r],
+            Matrix::YCgCo => [
+                0.25 * r + 0.5 * g + 0.25 * b,
+                -0.25 * r + 0.5 * g - 0.25 * b,
+                0.5 * (r - b),
+            ],
+            Matrix::KrKb(kr, kb) => {
+                let y = kr * r + (1. - kr - kb) * g + kb * b;
+                [y, (b - y) / (2. * (1. - kb)), (r - y) / (2. * (1. - kr))]
+            }
+        }
+    }
+
+    fn decode_luma(self, code: f32, bit_depth: u8) -> f32 {
+        if self.full_range {
+            code / max_value(bit_depth)
+        } else {
+            (code / range_scale(bit_depth) - 16.) / 219.
+        }
+    }
+
+    fn encode_luma(self, value: f32, bit_depth: u8) -> f32 {
+        if self.full_range {
+            value * max_value(bit_depth)
+        } else {
+            (value * 219. + 16.) * range_scale(bit_depth)
+        }
+    }
+
+    fn decode_chroma(self, code: f32, bit_depth: u8) -> f32 {
+        if matches!(self.matrix, Matrix::Identity) {
+            self.decode_luma(code, bit_depth)
+        } else if self.full_range {
+            (code - half_value(bit_depth)) / max_value(bit_depth)
+        } else {
+            (code / range_scale(bit_depth) - 128.) / 224.
+        }
+    }
+
+    fn encode_chroma(self, value: f32, bit_depth: u8) -> f32 {
+        if matches!(self.matrix, Matrix::Identity) {
+            self.encode_luma(value, bit_depth)
+        } else if self.full_range {
+            value * max_value(bit_depth) + half_value(bit_depth)
+        } else {
+            (value * 224. + 128.) * range_scale(bit_depth)
+        }
+    }
+}
+
+#[inline]
+fn max_value(bit_depth: u8) -> f32 {
+    ((1u32 << bit_depth) - 1) as f32
+}
+
+#[inline]
+fn half_value(bit_depth: u8) -> f32 {
+    (1u32 << (bit_depth - 1)) as f32
+}
+
+/// Scale of "limited range" values defined for 8-bit images.
+#[inline]
+fn range_scale(bit_depth: u8) -> f32 {
+    (1u32 << bit_depth) as f32 / 256.
+}
+
+/// Returns horizontal and vertical subsampling factors of chroma.
+fn chroma_subsampling(chroma: Chroma) -> (usize, usize) {
+    match chroma {
+        Chroma::C420 => (2, 2),
+        Chroma::C422 => (2, 1),
+        Chroma::C444 => (1, 1),
+    }
+}
+
+/// Pixels of an image as normalized RGB values with optional alpha.
+struct RgbaBuffer {
+    width: usize,
+    height: usize,
+    rgb: Vec<[f32; 3]>,
+    alpha: Option<Vec<f32>>,
+}
+
+pub(crate) fn convert_image(
+    src: &Image,
+    color_space: ColorSpace,
+    bit_depth: u8,
+    options: &ColorConversionOptions,
+) -> Result<Image> {
+    check_bit_depth(color_space, bit_depth)?;
+    let params = YCbCrParams::of_image(src);
+    let pixels = read_rgba(src, params, options.preferred_chroma_upsampling_algorithm)?;
+    let mut dst = Image::new(pixels.width as _, pixels.height as _, color_space)?;
+    write_rgba(
+        &mut dst,
+        &pixels,
+        color_space,
+        bit_depth,
+        params,
+        options.preferred_chroma_downsampling_algorithm,
+    )?;
+    src.copy_properties_to(&mut dst)?;
+    Ok(dst)
+}
+
+fn unsupported_color_space(color_space: ColorSpace) -> HeifError {
+    HeifError {
+        code: HeifErrorCode::UsageError,
+        sub_code: HeifErrorSubCode::UnsupportedColorConversion,
+        message: format!(
+            "Conversion of {:?} color space is not supported",
+            color_space
+        ),
+    }
+}
+
+fn check_bit_depth(color_space: ColorSpace, bit_depth: u8) -> Result<()> {
+    let supported = match color_space {
+        ColorSpace::Rgb(RgbChroma::Rgb | RgbChroma::Rgba) => bit_depth == 8,
+        ColorSpace::Rgb(
+            RgbChroma::HdrRgbBe | RgbChroma::HdrRgbaBe | RgbChroma::HdrRgbLe | RgbChroma::HdrRgbaLe,
+        ) => (9..=16).contains(&bit_depth),
+        ColorSpace::Rgb(RgbChroma::C444) | ColorSpace::YCbCr(_) | ColorSpace::Monochrome => {
+            (1..=16).contains(&bit_depth)
+        }
+        _ => return Err(unsupported_color_space(color_space)),
+    };
+    if supported {
+        Ok(())
+    } else {
+        Err(HeifError {
+            code: HeifErrorCode::UsageError,
+            sub_code: HeifErrorSubCode::UnsupportedBitDepth,
+            message: format!(
+                "Bit depth {} is not supported by {:?} color space",
+                bit_depth, color_space
+            ),
+        })
+    }
+}
+
+/// Returns values of the plane and its bit depth.
+fn read_plane(image: &Image, channel: Channel) -> Result<Option<(PlaneBuffer, u8)>> {
+    let Some(plane) = image.plane(channel) else {
+        return Ok(None);
+    };
+    let bit_depth = plane.bits_per_pixel.max(1);
+    Ok(Some((PlaneBuffer::read(&plane)?, bit_depth)))
+}
+
+fn read_required_plane(image: &Image, channel: Channel) -> Result<(PlaneBuffer, u8)> {
+    read_plane(image, channel)?.ok_or(HeifError {
+        code: HeifErrorCode::UsageError,
+        sub_code: HeifErrorSubCode::NonExistingImageChannelReferenced,
+        message: format!("Image has no {:?} channel", channel),
+    })
+}
+
+fn read_alpha(image: &Image) -> Result<Option<Vec<f32>>> {
+    Ok(read_plane(image, Channel::Alpha)?.map(|(buf, bit_depth)| {
+        let max = max_value(bit_depth);
+        buf.values.iter().map(|v| v / max).collect()
+    }))
+}
+
+fn read_rgba(
+    src: &Image,
+    params: YCbCrParams,
+    upsampling: ChromaUpsamplingAlgorithm,
+) -> Result<RgbaBuffer> {
+    let width = src.width() as usize;
+    let height = src.height() as usize;
+    let size = width * height;
+    let color_space = src.color_space().unwrap_or(ColorSpace::Undefined);
+    let (rgb, alpha) = match color_space {
+        ColorSpace::Rgb(RgbChroma::C444) => {
+            let mut rgb = vec![[0f32; 3]; size];
+            for (i, channel) in [Channel::R, Channel::G, Channel::B].into_iter().enumerate() {
+                let (buf, bit_depth) = read_required_plane(src, channel)?;
+                let max = max_value(bit_depth);
+                for (dst, v) in rgb.iter_mut().zip(&buf.values) {
+                    dst[i] = v / max;
+                }
+            }
+            (rgb, read_alpha(src)?)
+        }
+        ColorSpace::Rgb(_) => {
+            let (buf, bit_depth) = read_required_plane(src, Channel::Interleaved)?;
+            let max = max_value(bit_depth);
+            let pixels = buf.values.chunks_exact(buf.components);
+            let rgb = pixels
+                .clone()
+                .map(|p| [p[0] / max, p[1] / max, p[2] / max])
+                .collect();
+            let alpha = (buf.components == 4).then(|| pixels.map(|p| p[3] / max).collect());
+            (rgb, alpha)
+        }
+        ColorSpace::YCbCr(chroma) => {
+            let (y_buf, y_depth) = read_required_plane(src, Channel::Y)?;
+            let (cb_buf, cb_depth) = read_required_plane(src, Channel::Cb)?;
+            let (cr_buf, cr_depth) = read_required_plane(src, Channel::Cr)?;
+            let subsampling = chroma_subsampling(chroma);
+            let cb = upsample(&cb_buf, width, height, subsampling, upsampling);
+            let cr = upsample(&cr_buf, width, height, subsampling, upsampling);
+            let rgb = y_buf
+                .values
+                .iter()
+                .zip(cb.iter().zip(&cr))
+                .map(|(&y, (&cb, &cr))| {
+                    params.ycbcr_to_rgb(
+                        params.decode_luma(y, y_depth),
+                        params.decode_chroma(cb, cb_depth),
+                        params.decode_chroma(cr, cr_depth),
+                    )
+                })
+                .collect();
+            (rgb, read_alpha(src)?)
+        }
+        ColorSpace::Monochrome => {
+            let (y_buf, y_depth) = read_required_plane(src, Channel::Y)?;
+            let rgb = y_buf
+                .values
+                .iter()
+                .map(|&y| [params.decode_luma(y, y_depth); 3])
+                .collect();
+            (rgb, read_alpha(src)?)
+        }
+        _ => return Err(unsupported_color_space(color_space)),
+    };
+    Ok(RgbaBuffer {
+        width,
+        height,
+        rgb,
+        alpha,
+    })
+}
+
+/// Upsample values of chroma plane to the size of the image.
+fn upsample(
+    chroma: &PlaneBuffer,
+    width: usize,
+    height: usize,
+    (sx, sy): (usize, usize),
+    algorithm: ChromaUpsamplingAlgorithm,
+) -> Vec<f32> {
+    let get = |x: usize, y: usize| {
+        let x = x.min(chroma.width.saturating_sub(1));
+        let y = y.min(chroma.height.saturating_sub(1));
+        chroma
+            .values
+            .get(y * chroma.width + x)
+            .copied()
+            .unwrap_or(0.)
+    };
+    // Position of chroma sample related to luma samples.
+    let chroma_pos = |pos: usize, factor: usize| -> (usize, f32) {
+        let pos = ((pos as f32 + 0.5) / factor as f32 - 0.5).max(0.);
+        (pos as usize, pos.fract())
+    };
+    let mut values = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let value = match algorithm {
+                ChromaUpsamplingAlgorithm::Bilinear if sx > 1 || sy > 1 => {
+                    let (cx, fx) = chroma_pos(x, sx);
+                    let (cy, fy) = chroma_pos(y, sy);
+                    let top = get(cx, cy) * (1. - fx) + get(cx + 1, cy) * fx;
+                    let bottom = get(cx, cy + 1) * (1. - fx) + get(cx + 1, cy + 1) * fx;
+                    top * (1. - fy) + bottom * fy
+                }
+                _ => get(x / sx, y / sy),
+            };
+            values.push(value);
+        }
+    }
+    values
+}
+
+/// Downsample values of chroma with full resolution.
+fn downsample(
+    values: &[f32],
+    width: usize,
+    height: usize,
+    (sx, sy): (usize, usize),
+    algorithm: ChromaDownsamplingAlgorithm,
+) -> (Vec<f32>, usize, usize) {
+    let chroma_width = width.div_ceil(sx);
+    let chroma_height = height.div_ceil(sy);
+    let mut res = Vec::with_capacity(chroma_width * chroma_height);
+    for cy in 0..chroma_height {
+        for cx in 0..chroma_width {
+            let x = cx * sx;
+            let y = cy * sy;
+            let value = match algorithm {
+                ChromaDownsamplingAlgorithm::NearestNeighbor => values[y * width + x],
+                // Sharp YUV is not implemented, simple averaging is used instead.
+                _ => {
+                    let mut sum = 0.;
+                    let mut count = 0;
+                    for yy in y..(y + sy).min(height) {
+                        for xx in x..(x + sx).min(width) {
+                            sum += values[yy * width + xx];
+                            count += 1;
+                        }
+                    }
+                    sum / count as f32
+                }
+            };
+            res.push(value);
+        }
+    }
+    (res, chroma_width, chroma_height)
+}
+
+fn write_plane(dst: &mut Image, channel: Channel, buf: PlaneBuffer, bit_depth: u8) -> Result<()> {
+    dst.create_plane(channel, buf.width as _, buf.height as _, bit_depth)?;
+    let mut plane = dst.plane_mut(channel).ok_or(HeifError {
+        code: HeifErrorCode::UsageError,
+        sub_code: HeifErrorSubCode::NonExistingImageChannelReferenced,
+        message: format!("Image has no {:?} channel", channel),
+    })?;
+    buf.write(&mut plane, bit_depth)
+}
+
+fn write_rgba(
+    dst: &mut Image,
+    pixels: &RgbaBuffer,
+    color_space: ColorSpace,
+    bit_depth: u8,
+    params: YCbCrParams,
+    downsampling: ChromaDownsamplingAlgorithm,
+) -> Result<()> {
+    let width = pixels.width;
+    let height = pixels.height;
+    let max = max_value(bit_depth);
+    let plane = |values: Vec<f32>| PlaneBuffer {
+        width,
+        height,
+        components: 1,
+        values,
+    };
+
+    match color_space {
+        ColorSpace::Rgb(RgbChroma::C444) => {
+            for (i, channel) in [Channel::R, Channel::G, Channel::B].into_iter().enumerate() {
+                let values = pixels.rgb.iter().map(|p| p[i] * max).collect();
+                write_plane(dst, channel, plane(values), bit_depth)?;
+            }
+        }
+        ColorSpace::Rgb(chroma) => {
+            let has_alpha = matches!(
+                chroma,
+                RgbChroma::Rgba | RgbChroma::HdrRgbaBe | RgbChroma::HdrRgbaLe
+            );
+            let components = if has_alpha { 4 } else { 3 };
+            let mut values = Vec::with_capacity(width * height * components);
+            for (i, rgb) in pixels.rgb.iter().enumerate() {
+                values.extend(rgb.iter().map(|v| v * max));
+                if has_alpha {
+                    let alpha = pixels.alpha.as_ref().map_or(1., |a| a[i]);
+                    values.push(alpha * max);
+                }
+            }
+            let buf = PlaneBuffer {
+                width,
+                height,
+                components,
+                values,
+            };
+            write_plane(dst, Channel::Interleaved, buf, bit_depth)?;
+            // Alpha is already written into the interleaved plane.
+            return Ok(());
+        }
+        ColorSpace::YCbCr(chroma) => {
+            let ycbcr: Vec<[f32; 3]> = pixels.rgb.iter().map(|&p| params.rgb_to_ycbcr(p)).collect();
+            let y = ycbcr
+                .iter()
+                .map(|p| params.encode_luma(p[0], bit_depth))
+                .collect();
+            write_plane(dst, Channel::Y, plane(y), bit_depth)?;
+            let subsampling = chroma_subsampling(chroma);
+            for (i, channel) in [(1, Channel::Cb), (2, Channel::Cr)] {
+                let values: Vec<f32> = ycbcr
+                    .iter()
+                    .map(|p| params.encode_chroma(p[i], bit_depth))
+                    .collect();
+                let (values, chroma_width, chroma_height) =
+                    downsample(&values, width, height, subsampling, downsampling);
+                let buf = PlaneBuffer {
+                    width: chroma_width,
+                    height: chroma_height,
+                    components: 1,
+                    values,
+                };
+                write_plane(dst, channel, buf, bit_depth)?;
+            }
+        }
+        ColorSpace::Monochrome => {
+            let y = pixels
+                .rgb
+                .iter()
+                .map(|&p| params.encode_luma(params.rgb_to_ycbcr(p)[0], bit_depth))
+                .collect();
+            write_plane(dst, Channel::Y, plane(y), bit_depth)?;
+        }
+        _ => return Err(unsupported_color_space(color_space)),
+    }
+
+    if let Some(alpha) = &pixels.alpha {
+        let values = alpha.iter().map(|v| v * max).collect();
+        write_plane(dst, Channel::Alpha, plane(values), bit_depth)?;
+    }
+    Ok(())
+}
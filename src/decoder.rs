@@ -1,14 +1,25 @@
 use std::ffi::CString;
 use std::fmt::{Debug, Formatter};
+use std::os::raw::c_void;
 use std::ptr;
 use std::sync::Mutex;
 
 use libheif_sys as lh;
 
+#[cfg(feature = "rayon")]
+use crate::progress::ProgressHandler;
+use crate::progress::{end_progress, on_progress, start_progress, ProgressCallbacks};
 use crate::utils::{cstr_to_str, str_to_cstring};
 #[cfg(feature = "v1_20")]
 use crate::AlphaCompositionMode;
-use crate::{ChromaDownsamplingAlgorithm, ChromaUpsamplingAlgorithm, ColorProfileNCLX, HeifError};
+#[cfg(feature = "v1_19")]
+use crate::CancellationToken;
+#[cfg(feature = "lcms2")]
+use crate::TargetColorProfile;
+use crate::{
+    ChromaDownsamplingAlgorithm, ChromaUpsamplingAlgorithm, ColorProfileNCLX, HeifError,
+    ProgressStep,
+};
 static DECODER_MUTEX: Mutex<()> = Mutex::new(());
 
 #[derive(Debug)]
@@ -17,6 +28,11 @@ pub struct DecodingOptions {
     decoder_id: Option<CString>,
     #[allow(dead_code)]
     output_image_nclx_profile: Option<ColorProfileNCLX>,
+    #[cfg(feature = "lcms2")]
+    output_color_profile: Option<TargetColorProfile>,
+    // Boxed to have a stable address that is passed
+    // into `libheif` as `progress_user_data`.
+    callbacks: Box<ProgressCallbacks>,
 }
 
 impl DecodingOptions {
@@ -26,6 +42,9 @@ impl DecodingOptions {
             inner,
             decoder_id: None,
             output_image_nclx_profile: None,
+            #[cfg(feature = "lcms2")]
+            output_color_profile: None,
+            callbacks: Box::default(),
         })
     }
 }
@@ -50,6 +69,11 @@ impl Drop for DecodingOptions {
     }
 }
 
+// `libheif` only reads options while decoding, and the state
+// of the progress handler is protected by a mutex.
+unsafe impl Send for DecodingOptions {}
+unsafe impl Sync for DecodingOptions {}
+
 impl DecodingOptions {
     #[inline(always)]
     fn inner_ref(&self) -> &lh::heif_decoding_options {
@@ -177,6 +201,21 @@ impl DecodingOptions {
         inner.output_image_nclx_profile = profile_ptr;
     }
 
+    #[cfg(feature = "lcms2")]
+    pub fn output_color_profile(&self) -> Option<&TargetColorProfile> {
+        self.output_color_profile.as_ref()
+    }
+
+    /// Set a color profile into which pixels of decoded image are transformed
+    /// with help of [`Image::transform_to_profile()`](crate::Image::transform_to_profile).
+    ///
+    /// It is applied regardless of whether the source image has
+    /// `prof`, `rICC` or `nclx` color profile.
+    #[cfg(feature = "lcms2")]
+    pub fn set_output_color_profile(&mut self, profile: Option<TargetColorProfile>) {
+        self.output_color_profile = profile;
+    }
+
     #[cfg(feature = "v1_21")]
     /// 0 = let libheif decide (TODO, currently ignored)
     pub fn num_library_threads(&self) -> u32 {
@@ -204,15 +243,92 @@ impl DecodingOptions {
         let inner = self.inner_mut();
         inner.num_codec_threads = v.min(i32::MAX as u32) as _;
     }
+
+    /// Set a handler that receives progress of decoding.
+    ///
+    /// The handler is called with arguments `(step, current, max)`.
+    /// It is called with `current == 0` when `libheif` starts a step of
+    /// decoding (e.g. decoding of tiles of a grid image), after each
+    /// processed unit of work and with `current == max` when the step
+    /// is finished.
+    ///
+    /// Note: the handler may be called from background threads of `libheif`.
+    /// If the handler panics, the panic is caught and the handler
+    /// isn't called anymore.
+    pub fn set_progress_handler<F>(&mut self, handler: F)
+    where
+        F: FnMut(ProgressStep, u32, u32) + Send + 'static,
+    {
+        self.callbacks.set_progress_handler(Some(Box::new(handler)));
+        self.update_callbacks();
+    }
+
+    /// Remove the handler set by
+    /// [`DecodingOptions::set_progress_handler()`].
+    pub fn remove_progress_handler(&mut self) {
+        self.callbacks.set_progress_handler(None);
+        self.update_callbacks();
+    }
+
+    #[cfg(feature = "rayon")]
+    pub(crate) fn take_progress_handler(&mut self) -> Option<ProgressHandler> {
+        let handler = self.callbacks.take_progress_handler();
+        self.update_callbacks();
+        handler
+    }
+
+    /// Returns the token set by
+    /// [`DecodingOptions::set_cancellation_token()`].
+    #[cfg(feature = "v1_19")]
+    pub fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.callbacks.cancellation_token()
+    }
+
+    /// Set a token that `libheif` polls during decoding.
+    ///
+    /// After [`CancellationToken::cancel()`] is called, decoding is aborted
+    /// as soon as possible and returns an error with
+    /// code [`HeifErrorCode::Canceled`](crate::HeifErrorCode::Canceled).
+    #[cfg(feature = "v1_19")]
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.callbacks.set_cancellation_token(token);
+        self.update_callbacks();
+    }
+
+    fn update_callbacks(&mut self) {
+        let has_progress_handler = self.callbacks.has_progress_handler();
+        #[cfg(feature = "v1_19")]
+        let has_cancellation_token = self.callbacks.cancellation_token().is_some();
+        let user_data = self.callbacks.as_mut() as *mut ProgressCallbacks as *mut c_void;
+
+        let inner = self.inner_mut();
+        inner.progress_user_data = user_data;
+        if has_progress_handler {
+            inner.start_progress = Some(start_progress);
+            inner.on_progress = Some(on_progress);
+            inner.end_progress = Some(end_progress);
+        } else {
+            inner.start_progress = None;
+            inner.on_progress = None;
+            inner.end_progress = None;
+        }
+        #[cfg(feature = "v1_19")]
+        {
+            inner.cancel_decoding = if has_cancellation_token {
+                Some(crate::progress::cancel)
+            } else {
+                None
+            };
+        }
+    }
 }
 
 /// This function makes sure the decoding options
 /// won't be freed too early.
 pub(crate) fn get_decoding_options_ptr(
-    options: &Option<DecodingOptions>,
+    options: Option<&DecodingOptions>,
 ) -> *mut lh::heif_decoding_options {
     options
-        .as_ref()
         .map(|o| o.inner.as_ptr())
         .unwrap_or_else(ptr::null_mut)
 }
@@ -0,0 +1,263 @@
+use std::any::TypeId;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::{ptr, slice};
+
+use libheif_sys as lh;
+
+use crate::plugin_api::{
+    call_plugin, decoder_plugin_api_version, heif_error, HeifDecoderPlugin,
+    HeifDecoderPluginFormat, HeifDecoderPluginOptions, StaticData,
+};
+use crate::{CompressionFormat, HeifError, HeifErrorCode, Image, Result};
+
+/// Decoder of coded images implemented in Rust.
+///
+/// A plugin is registered with help of
+/// [`LibHeif::register_decoder_plugin()`](crate::LibHeif::register_decoder_plugin).
+/// After that `libheif` creates a new instance of the type for every decoded
+/// image or track, pushes coded data into it and calls
+/// [`DecoderPlugin::decode_image()`] until it returns an image.
+///
+/// A still image is decoded from a single chunk of data followed by
+/// [`DecoderPlugin::flush()`]. Frames of an image sequence are pushed
+/// one by one, and a decoder may need several frames before it returns
+/// the first image.
+///
+/// Instances may be created and used in different threads.
+pub trait DecoderPlugin: Default + Send + 'static {
+    /// Human-readable name of the plugin.
+    const NAME: &'static CStr;
+    /// Unique ID of the plugin that may be used to select the decoder with
+    /// help of [`DecodingOptions::set_decoder_id()`](crate::DecodingOptions::set_decoder_id).
+    const ID: &'static CStr;
+
+    /// Returns priority of the plugin for the given compression format.
+    /// The plugin with the largest priority is used to decode images.
+    ///
+    /// Built-in decoders of `libheif` have priority `100`.
+    /// Returns `0` if the format is not supported.
+    fn supports_format(format: CompressionFormat) -> u32;
+
+    /// Sets whether the decoder must return errors for
+    /// minor violations of the bitstream specification.
+    fn set_strict_decoding(&mut self, _strict: bool) {}
+
+    /// Pushes coded data of the next frame into the decoder.
+    ///
+    /// `user_data` is an opaque value that identifies the frame.
+    /// It must be returned by [`DecoderPlugin::decode_image()`]
+    /// together with the image decoded from this data.
+    fn push_data(&mut self, data: &[u8], user_data: usize) -> Result<()>;
+
+    /// Signals that no more data will be pushed,
+    /// so all buffered frames must be decoded.
+    ///
+    /// Note: `libheif` older than 1.21 doesn't call this method
+    /// while decoding of still images.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Returns the next decoded image with `user_data` of its frame,
+    /// or `None` if more data is needed to decode the next image.
+    fn decode_image(&mut self) -> Result<Option<(Image, usize)>>;
+}
+
+/// Plugin structures registered in `libheif`, by types of plugins.
+///
+/// Structures are leaked because `libheif` keeps pointers to them
+/// until deinitialization and allows registering them again after that.
+static REGISTERED_PLUGINS: Mutex<Vec<(TypeId, &'static StaticData<HeifDecoderPlugin>)>> =
+    Mutex::new(Vec::new());
+
+pub(crate) fn register_decoder_plugin<P: DecoderPlugin>() -> Result<()> {
+    let mut registered = REGISTERED_PLUGINS.lock().unwrap_or_else(|e| e.into_inner());
+    let type_id = TypeId::of::<P>();
+    let plugin = match registered.iter().find(|(id, _)| *id == type_id) {
+        Some(&(_, plugin)) => plugin,
+        None => {
+            let plugin: &'static _ = Box::leak(Box::new(StaticData(decoder_plugin::<P>())));
+            registered.push((type_id, plugin));
+            plugin
+        }
+    };
+    let plugin_ptr = &plugin.0 as *const HeifDecoderPlugin as *const lh::heif_decoder_plugin;
+    let err = unsafe { lh::heif_register_decoder_plugin(plugin_ptr) };
+    HeifError::from_heif_error(err)
+}
+
+fn decoder_plugin<P: DecoderPlugin>() -> HeifDecoderPlugin {
+    HeifDecoderPlugin {
+        plugin_api_version: decoder_plugin_api_version(),
+        get_plugin_name: Some(plugin_name::<P>),
+        init_plugin: None,
+        deinit_plugin: None,
+        does_support_format: Some(does_support_format::<P>),
+        new_decoder: Some(new_decoder::<P>),
+        free_decoder: Some(free_decoder::<P>),
+        push_data: Some(push_data::<P>),
+        decode_image: Some(decode_image::<P>),
+        set_strict_decoding: Some(set_strict_decoding::<P>),
+        id_name: P::ID.as_ptr(),
+        decode_next_image: Some(decode_next_image::<P>),
+        // Version 1.17.0
+        minimum_required_libheif_version: 0x01110000,
+        does_support_format2: Some(does_support_format2::<P>),
+        new_decoder2: Some(new_decoder2::<P>),
+        push_data2: Some(push_data2::<P>),
+        flush_data: Some(flush_data::<P>),
+        decode_next_image2: Some(decode_next_image2::<P>),
+    }
+}
+
+/// State of decoder passed into `libheif`.
+struct PluginDecoder<P> {
+    decoder: P,
+}
+
+#[inline]
+unsafe fn plugin_decoder<'a, P>(decoder: *mut c_void) -> &'a mut PluginDecoder<P> {
+    &mut *(decoder as *mut PluginDecoder<P>)
+}
+
+unsafe extern "C" fn plugin_name<P: DecoderPlugin>() -> *const c_char {
+    P::NAME.as_ptr()
+}
+
+unsafe extern "C" fn does_support_format<P: DecoderPlugin>(
+    format: lh::heif_compression_format,
+) -> c_int {
+    let Some(format) = CompressionFormat::n(format) else {
+        return 0;
+    };
+    let priority = panic::catch_unwind(|| P::supports_format(format)).unwrap_or(0);
+    priority.min(c_int::MAX as u32) as c_int
+}
+
+unsafe extern "C" fn does_support_format2<P: DecoderPlugin>(
+    format: *const HeifDecoderPluginFormat,
+) -> c_int {
+    match format.as_ref() {
+        Some(format) => does_support_format::<P>(format.format),
+        None => 0,
+    }
+}
+
+unsafe extern "C" fn new_decoder<P: DecoderPlugin>(decoder: *mut *mut c_void) -> lh::heif_error {
+    call_plugin(HeifErrorCode::DecoderPluginError, || {
+        let plugin_decoder = PluginDecoder {
+            decoder: P::default(),
+        };
+        *decoder = Box::into_raw(Box::new(plugin_decoder)) as _;
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn new_decoder2<P: DecoderPlugin>(
+    decoder: *mut *mut c_void,
+    options: *const HeifDecoderPluginOptions,
+) -> lh::heif_error {
+    let err = new_decoder::<P>(decoder);
+    if let Some(options) = options.as_ref() {
+        if err.code == lh::heif_error_code_heif_error_Ok {
+            set_strict_decoding::<P>(*decoder, options.strict_decoding);
+        }
+    }
+    err
+}
+
+unsafe extern "C" fn free_decoder<P: DecoderPlugin>(decoder: *mut c_void) {
+    let decoder = Box::from_raw(decoder as *mut PluginDecoder<P>);
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(decoder)));
+}
+
+unsafe extern "C" fn set_strict_decoding<P: DecoderPlugin>(decoder: *mut c_void, flag: c_int) {
+    let decoder = plugin_decoder::<P>(decoder);
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        decoder.decoder.set_strict_decoding(flag != 0)
+    }));
+}
+
+unsafe extern "C" fn push_data<P: DecoderPlugin>(
+    decoder: *mut c_void,
+    data: *const c_void,
+    size: usize,
+) -> lh::heif_error {
+    push_data2::<P>(decoder, data, size, 0)
+}
+
+unsafe extern "C" fn push_data2<P: DecoderPlugin>(
+    decoder: *mut c_void,
+    data: *const c_void,
+    size: usize,
+    user_data: usize,
+) -> lh::heif_error {
+    let decoder = plugin_decoder::<P>(decoder);
+    let data = if data.is_null() || size == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data as *const u8, size)
+    };
+    call_plugin(HeifErrorCode::DecoderPluginError, || {
+        decoder.decoder.push_data(data, user_data)
+    })
+}
+
+unsafe extern "C" fn flush_data<P: DecoderPlugin>(decoder: *mut c_void) -> lh::heif_error {
+    let decoder = plugin_decoder::<P>(decoder);
+    call_plugin(HeifErrorCode::DecoderPluginError, || {
+        decoder.decoder.flush()
+    })
+}
+
+/// Old versions of `libheif` expect an image after pushing
+/// of all data of the image without flushing.
+unsafe extern "C" fn decode_image<P: DecoderPlugin>(
+    decoder: *mut c_void,
+    out_img: *mut *mut lh::heif_image,
+) -> lh::heif_error {
+    let err = flush_data::<P>(decoder);
+    if err.code != lh::heif_error_code_heif_error_Ok {
+        *out_img = ptr::null_mut();
+        return err;
+    }
+    let err = decode_next_image2::<P>(decoder, out_img, ptr::null_mut(), ptr::null());
+    if err.code == lh::heif_error_code_heif_error_Ok && out_img.read().is_null() {
+        return heif_error(
+            lh::heif_error_code_heif_error_Decoder_plugin_error,
+            lh::heif_suberror_code_heif_suberror_Unspecified,
+            c"Decoder didn't return an image",
+        );
+    }
+    err
+}
+
+unsafe extern "C" fn decode_next_image<P: DecoderPlugin>(
+    decoder: *mut c_void,
+    out_img: *mut *mut lh::heif_image,
+    limits: *const c_void,
+) -> lh::heif_error {
+    decode_next_image2::<P>(decoder, out_img, ptr::null_mut(), limits)
+}
+
+unsafe extern "C" fn decode_next_image2<P: DecoderPlugin>(
+    decoder: *mut c_void,
+    out_img: *mut *mut lh::heif_image,
+    out_user_data: *mut usize,
+    _limits: *const c_void,
+) -> lh::heif_error {
+    let decoder = plugin_decoder::<P>(decoder);
+    *out_img = ptr::null_mut();
+    call_plugin(HeifErrorCode::DecoderPluginError, || {
+        if let Some((image, user_data)) = decoder.decoder.decode_image()? {
+            *out_img = image.into_heif_image();
+            if !out_user_data.is_null() {
+                *out_user_data = user_data;
+            }
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,940 @@
+//! Lossless editing of items of HEIF files.
+//!
+//! `libheif` can't remove items from a context, and it doesn't keep
+//! coded data of items read from a file when the context is written.
+//! So items are edited in boxes of the file itself, and data of
+//! remaining items is copied into the new file as is.
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use crate::bmff::{write_box, write_full_box, Boxes, ByteReader};
+use crate::{HeifError, HeifErrorCode, HeifErrorSubCode, ItemId, Result};
+
+pub(crate) const CDSC: [u8; 4] = *b"cdsc";
+pub(crate) const THMB: [u8; 4] = *b"thmb";
+const MIME: [u8; 4] = *b"mime";
+/// Types of references from items that describe the referenced item,
+/// like thumbnails, auxiliary images and metadata.
+const DESCRIBING_REFERENCES: [[u8; 4]; 3] = [*b"thmb", *b"auxl", *b"cdsc"];
+/// Types of references to items that are used to construct the referencing item,
+/// like tiles of a grid image and masks of regions.
+const INPUT_REFERENCES: [[u8; 4]; 2] = [*b"dimg", *b"mask"];
+
+enum ItemData {
+    None,
+    /// Ranges of extents in the source file.
+    File(Vec<Range<usize>>),
+    /// Data stored inside `idat` box.
+    Idat(Vec<u8>),
+    /// New data of the item.
+    Owned(Vec<u8>),
+}
+
+impl ItemData {
+    fn len(&self, source: &[u8]) -> usize {
+        match self {
+            Self::None => 0,
+            Self::File(ranges) => ranges.iter().map(|r| source[r.clone()].len()).sum(),
+            Self::Idat(data) | Self::Owned(data) => data.len(),
+        }
+    }
+
+    /// Returns true if data must be stored in `mdat` box.
+    fn is_in_mdat(&self) -> bool {
+        matches!(self, Self::File(_) | Self::Owned(_))
+    }
+}
+
+struct Item {
+    id: ItemId,
+    /// Whole `infe` box.
+    infe: Vec<u8>,
+    item_type: [u8; 4],
+    data: ItemData,
+}
+
+impl Item {
+    fn is_hidden(&self) -> bool {
+        // The first bit of flags of `infe` box.
+        self.infe.get(11).is_some_and(|flags| flags & 1 != 0)
+    }
+}
+
+struct Location {
+    construction_method: u16,
+    data_reference_index: u16,
+    base_offset: u64,
+    /// Offsets and lengths of extents.
+    extents: Vec<(u64, u64)>,
+}
+
+struct Reference {
+    ref_type: [u8; 4],
+    from: ItemId,
+    to: Vec<ItemId>,
+}
+
+#[derive(Clone, Copy)]
+struct PropertyAssociation {
+    essential: bool,
+    /// 1-based index of property inside `ipco` box.
+    index: u16,
+}
+
+struct EntityGroup {
+    group_type: [u8; 4],
+    version: u8,
+    flags: u32,
+    group_id: u32,
+    entity_ids: Vec<u32>,
+    /// Data of the group stored after IDs of entities.
+    extra: Vec<u8>,
+}
+
+/// Structure of a HEIF file with still images.
+pub(crate) struct EditableFile<'a> {
+    source: &'a [u8],
+    ftyp: &'a [u8],
+    /// Unknown top-level boxes that are kept as is.
+    top_level_boxes: Vec<&'a [u8]>,
+    hdlr: &'a [u8],
+    primary_item_id: ItemId,
+    items: Vec<Item>,
+    references: Vec<Reference>,
+    properties: Vec<&'a [u8]>,
+    associations: Vec<(ItemId, Vec<PropertyAssociation>)>,
+    groups: Vec<EntityGroup>,
+    /// Unknown children of `meta` box that are kept as is.
+    meta_boxes: Vec<&'a [u8]>,
+}
+
+impl<'a> EditableFile<'a> {
+    pub fn parse(source: &'a [u8]) -> Result<Self> {
+        let mut ftyp = None;
+        let mut meta = None;
+        let mut top_level_boxes = Vec::new();
+        let mut parsed_size = 0;
+        for bmff_box in Boxes(source) {
+            parsed_size += bmff_box.raw.len();
+            match &bmff_box.box_type {
+                b"ftyp" => ftyp = Some(bmff_box.raw),
+                b"meta" => meta = Some(bmff_box.payload),
+                // Data of items is copied from `mdat` separately.
+                b"mdat" | b"free" | b"skip" => {}
+                b"moov" | b"moof" => {
+                    return Err(unsupported(
+                        "Editing of files with image sequences is not supported",
+                    ))
+                }
+                _ => top_level_boxes.push(bmff_box.raw),
+            }
+        }
+        if parsed_size != source.len() {
+            return Err(invalid_data(
+                HeifErrorSubCode::InvalidBoxSize,
+                "Invalid size of top-level box",
+            ));
+        }
+        let ftyp = ftyp.ok_or_else(|| invalid_data(HeifErrorSubCode::NoFtypBox, "No ftyp box"))?;
+        let meta = meta.ok_or_else(|| invalid_data(HeifErrorSubCode::NoMetaBox, "No meta box"))?;
+        let mut file = Self {
+            source,
+            ftyp,
+            top_level_boxes,
+            hdlr: &[],
+            primary_item_id: 0,
+            items: Vec::new(),
+            references: Vec::new(),
+            properties: Vec::new(),
+            associations: Vec::new(),
+            groups: Vec::new(),
+            meta_boxes: Vec::new(),
+        };
+        file.parse_meta(meta)?;
+        Ok(file)
+    }
+
+    fn parse_meta(&mut self, meta: &'a [u8]) -> Result<()> {
+        let mut reader = ByteReader::new(meta);
+        // Version and flags.
+        reader.u32()?;
+        let mut hdlr = None;
+        let mut pitm = None;
+        let mut iinf = None;
+        let mut locations = HashMap::new();
+        let mut idat: &[u8] = &[];
+        for bmff_box in Boxes(reader.remaining()) {
+            match &bmff_box.box_type {
+                b"hdlr" => hdlr = Some(bmff_box.raw),
+                b"pitm" => pitm = Some(bmff_box.payload),
+                b"iinf" => iinf = Some(bmff_box.payload),
+                b"iloc" => locations = parse_iloc(bmff_box.payload)?,
+                b"iref" => self.references = parse_iref(bmff_box.payload)?,
+                b"iprp" => self.parse_iprp(bmff_box.payload)?,
+                b"idat" => idat = bmff_box.payload,
+                b"grpl" => self.groups = parse_grpl(bmff_box.payload)?,
+                _ => self.meta_boxes.push(bmff_box.raw),
+            }
+        }
+        self.hdlr = hdlr.ok_or_else(|| invalid_data(HeifErrorSubCode::NoHdlrBox, "No hdlr box"))?;
+
+        let pitm = pitm.ok_or_else(|| invalid_data(HeifErrorSubCode::NoPitmBox, "No pitm box"))?;
+        let mut reader = ByteReader::new(pitm);
+        let version = reader.u8()?;
+        reader.bytes(3)?;
+        self.primary_item_id = if version == 0 {
+            reader.u16()? as ItemId
+        } else {
+            reader.u32()?
+        };
+
+        let iinf = iinf.ok_or_else(|| invalid_data(HeifErrorSubCode::NoIinfBox, "No iinf box"))?;
+        let mut reader = ByteReader::new(iinf);
+        let version = reader.u8()?;
+        reader.bytes(3)?;
+        if version == 0 {
+            reader.u16()?;
+        } else {
+            reader.u32()?;
+        }
+        for infe in Boxes(reader.remaining()).filter(|b| &b.box_type == b"infe") {
+            let mut reader = ByteReader::new(infe.payload);
+            let version = reader.u8()?;
+            reader.bytes(3)?;
+            let id = if version < 3 {
+                reader.u16()? as ItemId
+            } else {
+                reader.u32()?
+            };
+            let item_type = if version >= 2 {
+                // Skip protection index.
+                reader.u16()?;
+                reader.four_cc()?
+            } else {
+                [0; 4]
+            };
+            let data = match locations.remove(&id) {
+                Some(location) => self.item_data(&location, idat)?,
+                None => ItemData::None,
+            };
+            self.items.push(Item {
+                id,
+                infe: infe.raw.to_vec(),
+                item_type,
+                data,
+            });
+        }
+        Ok(())
+    }
+
+    fn item_data(&self, location: &Location, idat: &[u8]) -> Result<ItemData> {
+        let data = match location.construction_method {
+            0 => self.source,
+            1 => idat,
+            _ => {
+                return Err(HeifError {
+                    code: HeifErrorCode::UnsupportedFeature,
+                    sub_code: HeifErrorSubCode::UnsupportedItemConstructionMethod,
+                    message: format!(
+                        "Unsupported construction method of item: {}",
+                        location.construction_method
+                    ),
+                })
+            }
+        };
+        if location.data_reference_index != 0 {
+            return Err(unsupported("Items with external data are not supported"));
+        }
+        let mut ranges = Vec::with_capacity(location.extents.len());
+        for &(offset, length) in &location.extents {
+            let range = location
+                .base_offset
+                .checked_add(offset)
+                .and_then(|start| {
+                    // Zero length means that extent ends at the end of data.
+                    let end = if length == 0 {
+                        data.len() as u64
+                    } else {
+                        start.checked_add(length)?
+                    };
+                    (end <= data.len() as u64 && start <= end)
+                        .then_some(start as usize..end as usize)
+                })
+                .ok_or_else(|| {
+                    invalid_data(
+                        HeifErrorSubCode::EndOfData,
+                        "Extent of item is outside of data",
+                    )
+                })?;
+            ranges.push(range);
+        }
+        Ok(if location.construction_method == 0 {
+            ItemData::File(ranges)
+        } else {
+            ItemData::Idat(ranges.into_iter().flat_map(|r| &idat[r]).copied().collect())
+        })
+    }
+
+    fn parse_iprp(&mut self, iprp: &'a [u8]) -> Result<()> {
+        for bmff_box in Boxes(iprp) {
+            match &bmff_box.box_type {
+                b"ipco" => self.properties = Boxes(bmff_box.payload).map(|b| b.raw).collect(),
+                b"ipma" => {
+                    for (item_id, properties) in parse_ipma(bmff_box.payload)? {
+                        match self.associations.iter_mut().find(|(id, _)| *id == item_id) {
+                            Some((_, associated)) => associated.extend(properties),
+                            None => self.associations.push((item_id, properties)),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "v1_18")]
+    pub fn item_type(&self, item_id: ItemId) -> Option<[u8; 4]> {
+        self.item(item_id).map(|item| item.item_type)
+    }
+
+    fn item(&self, item_id: ItemId) -> Option<&Item> {
+        self.items.iter().find(|item| item.id == item_id)
+    }
+
+    fn item_mut(&mut self, item_id: ItemId) -> Result<&mut Item> {
+        self.items
+            .iter_mut()
+            .find(|item| item.id == item_id)
+            .ok_or_else(|| non_existing_item(item_id))
+    }
+
+    pub fn has_reference(&self, ref_type: [u8; 4], from: ItemId, to: ItemId) -> bool {
+        self.references
+            .iter()
+            .any(|r| r.ref_type == ref_type && r.from == from && r.to.contains(&to))
+    }
+
+    /// Replaces data of the item.
+    ///
+    /// Content encoding of `mime` item is removed because
+    /// the new data is stored as is.
+    pub fn set_item_data(&mut self, item_id: ItemId, data: Vec<u8>) -> Result<()> {
+        let item = self.item_mut(item_id)?;
+        if item.item_type == MIME {
+            item.infe = without_content_encoding(&item.infe)?;
+        }
+        item.data = ItemData::Owned(data);
+        Ok(())
+    }
+
+    /// Adds a hidden metadata item that describes the image.
+    pub fn add_metadata_item(
+        &mut self,
+        image_id: ItemId,
+        item_type: [u8; 4],
+        content_type: Option<&str>,
+        data: Vec<u8>,
+    ) -> Result<ItemId> {
+        if self.item(image_id).is_none() {
+            return Err(non_existing_item(image_id));
+        }
+        let item_id = self.new_item_id()?;
+        let mut infe = Vec::new();
+        let version = if item_id > u16::MAX as ItemId { 3 } else { 2 };
+        // Flags with hidden bit.
+        write_full_box(&mut infe, b"infe", version, 1, |out| {
+            if version == 2 {
+                out.extend_from_slice(&(item_id as u16).to_be_bytes());
+            } else {
+                out.extend_from_slice(&item_id.to_be_bytes());
+            }
+            // Protection index.
+            out.extend_from_slice(&[0, 0]);
+            out.extend_from_slice(&item_type);
+            // Empty name.
+            out.push(0);
+            if item_type == MIME {
+                out.extend_from_slice(content_type.unwrap_or_default().as_bytes());
+                out.push(0);
+            }
+        });
+        self.items.push(Item {
+            id: item_id,
+            infe,
+            item_type,
+            data: ItemData::Owned(data),
+        });
+        self.add_reference(CDSC, item_id, image_id)?;
+        Ok(item_id)
+    }
+
+    fn new_item_id(&self) -> Result<ItemId> {
+        // Items and entity groups share the same space of IDs.
+        let max_id = self
+            .items
+            .iter()
+            .map(|item| item.id)
+            .chain(self.groups.iter().map(|group| group.group_id))
+            .max()
+            .unwrap_or(0);
+        max_id.checked_add(1).ok_or_else(|| HeifError {
+            code: HeifErrorCode::UsageError,
+            sub_code: HeifErrorSubCode::Unspecified,
+            message: "No free item IDs".to_string(),
+        })
+    }
+
+    pub fn add_reference(&mut self, ref_type: [u8; 4], from: ItemId, to: ItemId) -> Result<()> {
+        for item_id in [from, to] {
+            if self.item(item_id).is_none() {
+                return Err(non_existing_item(item_id));
+            }
+        }
+        match self
+            .references
+            .iter_mut()
+            .find(|r| r.ref_type == ref_type && r.from == from)
+        {
+            Some(reference) => reference.to.push(to),
+            None => self.references.push(Reference {
+                ref_type,
+                from,
+                to: vec![to],
+            }),
+        }
+        Ok(())
+    }
+
+    pub fn set_primary_item_id(&mut self, item_id: ItemId) -> Result<()> {
+        if self.item(item_id).is_none() {
+            return Err(non_existing_item(item_id));
+        }
+        self.primary_item_id = item_id;
+        Ok(())
+    }
+
+    /// Removes the reference between two items. The referencing item is removed
+    /// if it has no other references of this type.
+    pub fn detach_item(&mut self, ref_type: [u8; 4], from: ItemId, to: ItemId) -> Result<()> {
+        let Some(reference) = self
+            .references
+            .iter_mut()
+            .find(|r| r.ref_type == ref_type && r.from == from && r.to.contains(&to))
+        else {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::NonExistingItemReferenced,
+                message: format!(
+                    "Item {} has no '{}' reference to item {}",
+                    from,
+                    String::from_utf8_lossy(&ref_type),
+                    to
+                ),
+            });
+        };
+        if reference.to.len() > 1 {
+            reference.to.retain(|&id| id != to);
+            return Ok(());
+        }
+        self.remove_item(from)
+    }
+
+    /// Removes the item together with items that depend on it:
+    /// items describing it (thumbnails, auxiliary images, metadata)
+    /// and hidden items used only to construct it (tiles of grid images).
+    pub fn remove_item(&mut self, item_id: ItemId) -> Result<()> {
+        let mut removed = HashSet::from([item_id]);
+        loop {
+            let count = removed.len();
+            for reference in &self.references {
+                if DESCRIBING_REFERENCES.contains(&reference.ref_type)
+                    && reference.to.iter().all(|id| removed.contains(id))
+                {
+                    removed.insert(reference.from);
+                }
+            }
+            let inputs: HashSet<ItemId> = self
+                .references
+                .iter()
+                .filter(|r| INPUT_REFERENCES.contains(&r.ref_type) && removed.contains(&r.from))
+                .flat_map(|r| r.to.iter().copied())
+                .collect();
+            for input_id in inputs {
+                let is_used = self.references.iter().any(|r| {
+                    !removed.contains(&r.from)
+                        && !DESCRIBING_REFERENCES.contains(&r.ref_type)
+                        && r.to.contains(&input_id)
+                });
+                let is_hidden = self.item(input_id).is_some_and(|item| item.is_hidden());
+                if !is_used && is_hidden {
+                    removed.insert(input_id);
+                }
+            }
+            if removed.len() == count {
+                break;
+            }
+        }
+        self.remove_items(&removed)
+    }
+
+    /// Removes items together with their references, locations and
+    /// properties that are not used by other items.
+    pub fn remove_items(&mut self, item_ids: &HashSet<ItemId>) -> Result<()> {
+        if item_ids.contains(&self.primary_item_id) {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::NoOrInvalidPrimaryItem,
+                message: "Primary image can't be removed".to_string(),
+            });
+        }
+        if let Some(&item_id) = item_ids.iter().find(|&&id| self.item(id).is_none()) {
+            return Err(non_existing_item(item_id));
+        }
+        self.items.retain(|item| !item_ids.contains(&item.id));
+        self.references.retain_mut(|r| {
+            r.to.retain(|id| !item_ids.contains(id));
+            !item_ids.contains(&r.from) && !r.to.is_empty()
+        });
+        self.associations
+            .retain(|(item_id, _)| !item_ids.contains(item_id));
+        self.groups.retain_mut(|group| {
+            group.entity_ids.retain(|id| !item_ids.contains(id));
+            !group.entity_ids.is_empty()
+        });
+        self.remove_unused_properties();
+        Ok(())
+    }
+
+    fn remove_unused_properties(&mut self) {
+        let mut used = vec![false; self.properties.len()];
+        for association in self.associations.iter().flat_map(|(_, a)| a) {
+            if let Some(is_used) = used.get_mut((association.index as usize).wrapping_sub(1)) {
+                *is_used = true;
+            }
+        }
+        // New 1-based indexes of properties.
+        let mut new_indexes = Vec::with_capacity(used.len());
+        let mut next_index = 1;
+        for &is_used in &used {
+            new_indexes.push(next_index);
+            next_index += is_used as u16;
+        }
+        for association in self.associations.iter_mut().flat_map(|(_, a)| a) {
+            if let Some(&index) = new_indexes.get((association.index as usize).wrapping_sub(1)) {
+                association.index = index;
+            }
+        }
+        let mut used = used.into_iter();
+        self.properties.retain(|_| used.next().unwrap_or(true));
+    }
+
+    /// Writes the file with the current set of items.
+    pub fn write(&self) -> Vec<u8> {
+        let mdat_size: u64 = self
+            .items
+            .iter()
+            .filter(|item| item.data.is_in_mdat())
+            .map(|item| item.data.len(self.source) as u64)
+            .sum();
+        let mdat_header_size = if mdat_size + 8 > u32::MAX as u64 {
+            16
+        } else {
+            8
+        };
+        let prefix_size =
+            self.ftyp.len() + self.top_level_boxes.iter().map(|b| b.len()).sum::<usize>();
+        let mut offset_size = 4;
+        // Size of the meta box doesn't depend on offsets of data.
+        let mut meta_size = self.write_meta(0, offset_size).len();
+        if (prefix_size + meta_size + mdat_header_size) as u64 + mdat_size > u32::MAX as u64 {
+            offset_size = 8;
+            meta_size = self.write_meta(0, offset_size).len();
+        }
+        let data_offset = (prefix_size + meta_size + mdat_header_size) as u64;
+
+        let mut out = Vec::with_capacity(data_offset as usize + mdat_size as usize);
+        out.extend_from_slice(self.ftyp);
+        for bmff_box in &self.top_level_boxes {
+            out.extend_from_slice(bmff_box);
+        }
+        out.extend(self.write_meta(data_offset, offset_size));
+        if mdat_header_size == 16 {
+            out.extend_from_slice(&1u32.to_be_bytes());
+            out.extend_from_slice(b"mdat");
+            out.extend_from_slice(&(mdat_size + 16).to_be_bytes());
+        } else {
+            out.extend_from_slice(&(mdat_size as u32 + 8).to_be_bytes());
+            out.extend_from_slice(b"mdat");
+        }
+        for item in &self.items {
+            match &item.data {
+                ItemData::File(ranges) => {
+                    for range in ranges {
+                        out.extend_from_slice(&self.source[range.clone()]);
+                    }
+                }
+                ItemData::Owned(data) => out.extend_from_slice(data),
+                ItemData::None | ItemData::Idat(_) => {}
+            }
+        }
+        out
+    }
+
+    fn write_meta(&self, data_offset: u64, offset_size: u8) -> Vec<u8> {
+        let large_ids = self.items.iter().any(|item| item.id > u16::MAX as ItemId);
+        let mut meta = Vec::new();
+        write_full_box(&mut meta, b"meta", 0, 0, |out| {
+            out.extend_from_slice(self.hdlr);
+            write_full_box(out, b"pitm", large_ids as u8, 0, |out| {
+                write_id(out, self.primary_item_id, large_ids);
+            });
+            self.write_iloc(out, data_offset, offset_size);
+            let large_count = self.items.len() > u16::MAX as usize;
+            write_full_box(out, b"iinf", large_count as u8, 0, |out| {
+                write_id(out, self.items.len() as u32, large_count);
+                for item in &self.items {
+                    out.extend_from_slice(&item.infe);
+                }
+            });
+            if !self.references.is_empty() {
+                write_full_box(out, b"iref", large_ids as u8, 0, |out| {
+                    for reference in &self.references {
+                        write_box(out, &reference.ref_type, |out| {
+                            write_id(out, reference.from, large_ids);
+                            out.extend_from_slice(&(reference.to.len() as u16).to_be_bytes());
+                            for &to in &reference.to {
+                                write_id(out, to, large_ids);
+                            }
+                        });
+                    }
+                });
+            }
+            if !self.properties.is_empty() {
+                self.write_iprp(out, large_ids);
+            }
+            let idat: Vec<u8> = self
+                .items
+                .iter()
+                .filter_map(|item| match &item.data {
+                    ItemData::Idat(data) => Some(data.as_slice()),
+                    _ => None,
+                })
+                .flatten()
+                .copied()
+                .collect();
+            if !idat.is_empty() {
+                write_box(out, b"idat", |out| out.extend_from_slice(&idat));
+            }
+            if !self.groups.is_empty() {
+                write_box(out, b"grpl", |out| {
+                    for group in &self.groups {
+                        write_full_box(out, &group.group_type, group.version, group.flags, |out| {
+                            out.extend_from_slice(&group.group_id.to_be_bytes());
+                            out.extend_from_slice(&(group.entity_ids.len() as u32).to_be_bytes());
+                            for id in &group.entity_ids {
+                                out.extend_from_slice(&id.to_be_bytes());
+                            }
+                            out.extend_from_slice(&group.extra);
+                        });
+                    }
+                });
+            }
+            for bmff_box in &self.meta_boxes {
+                out.extend_from_slice(bmff_box);
+            }
+        });
+        meta
+    }
+
+    fn write_iloc(&self, out: &mut Vec<u8>, data_offset: u64, offset_size: u8) {
+        let located: Vec<&Item> = self
+            .items
+            .iter()
+            .filter(|item| !matches!(item.data, ItemData::None))
+            .collect();
+        let large_ids = located.iter().any(|item| item.id > u16::MAX as ItemId)
+            || located.len() > u16::MAX as usize;
+        let length_size: u8 = if located
+            .iter()
+            .any(|item| item.data.len(self.source) > u32::MAX as usize)
+        {
+            8
+        } else {
+            4
+        };
+        // Version 1 is required to store construction method.
+        let version = if large_ids { 2 } else { 1 };
+        write_full_box(out, b"iloc", version, 0, |out| {
+            out.push(offset_size << 4 | length_size);
+            // Sizes of base offset and index.
+            out.push(0);
+            write_id(out, located.len() as u32, large_ids);
+            let mut mdat_offset = data_offset;
+            let mut idat_offset = 0;
+            for item in located {
+                let length = item.data.len(self.source) as u64;
+                write_id(out, item.id, large_ids);
+                let (construction_method, offset) = if item.data.is_in_mdat() {
+                    mdat_offset += length;
+                    (0u16, mdat_offset - length)
+                } else {
+                    idat_offset += length;
+                    (1, idat_offset - length)
+                };
+                out.extend_from_slice(&construction_method.to_be_bytes());
+                // Data reference index.
+                out.extend_from_slice(&[0, 0]);
+                // Count of extents.
+                out.extend_from_slice(&1u16.to_be_bytes());
+                write_uint(out, offset, offset_size);
+                write_uint(out, length, length_size);
+            }
+        });
+    }
+
+    fn write_iprp(&self, out: &mut Vec<u8>, large_ids: bool) {
+        let large_indexes = self.properties.len() > 0x7f;
+        write_box(out, b"iprp", |out| {
+            write_box(out, b"ipco", |out| {
+                for property in &self.properties {
+                    out.extend_from_slice(property);
+                }
+            });
+            write_full_box(out, b"ipma", large_ids as u8, large_indexes as u32, |out| {
+                out.extend_from_slice(&(self.associations.len() as u32).to_be_bytes());
+                for (item_id, properties) in &self.associations {
+                    write_id(out, *item_id, large_ids);
+                    out.push(properties.len() as u8);
+                    for property in properties {
+                        if large_indexes {
+                            let value = (property.essential as u16) << 15 | property.index;
+                            out.extend_from_slice(&value.to_be_bytes());
+                        } else {
+                            out.push((property.essential as u8) << 7 | property.index as u8);
+                        }
+                    }
+                }
+            });
+        });
+    }
+}
+
+fn parse_iloc(iloc: &[u8]) -> Result<HashMap<ItemId, Location>> {
+    let mut reader = ByteReader::new(iloc);
+    let version = reader.u8()?;
+    reader.bytes(3)?;
+    if version > 2 {
+        return Err(unsupported_version("iloc", version));
+    }
+    let sizes = reader.u8()?;
+    let (offset_size, length_size) = (sizes >> 4, sizes & 0xf);
+    let sizes = reader.u8()?;
+    let base_offset_size = sizes >> 4;
+    let index_size = if version > 0 { sizes & 0xf } else { 0 };
+    let count = if version < 2 {
+        reader.u16()? as u32
+    } else {
+        reader.u32()?
+    };
+    let mut locations = HashMap::new();
+    for _ in 0..count {
+        let item_id = if version < 2 {
+            reader.u16()? as ItemId
+        } else {
+            reader.u32()?
+        };
+        let construction_method = if version > 0 { reader.u16()? & 0xf } else { 0 };
+        let data_reference_index = reader.u16()?;
+        let base_offset = reader.uint(base_offset_size)?;
+        let extent_count = reader.u16()?;
+        let mut extents = Vec::with_capacity(extent_count as usize);
+        for _ in 0..extent_count {
+            reader.uint(index_size)?;
+            let offset = reader.uint(offset_size)?;
+            let length = reader.uint(length_size)?;
+            extents.push((offset, length));
+        }
+        locations.insert(
+            item_id,
+            Location {
+                construction_method,
+                data_reference_index,
+                base_offset,
+                extents,
+            },
+        );
+    }
+    Ok(locations)
+}
+
+fn parse_iref(iref: &[u8]) -> Result<Vec<Reference>> {
+    let mut reader = ByteReader::new(iref);
+    let version = reader.u8()?;
+    reader.bytes(3)?;
+    let read_id = |reader: &mut ByteReader| -> Result<ItemId> {
+        if version == 0 {
+            Ok(reader.u16()? as ItemId)
+        } else {
+            reader.u32()
+        }
+    };
+    let mut references = Vec::new();
+    for bmff_box in Boxes(reader.remaining()) {
+        let mut reader = ByteReader::new(bmff_box.payload);
+        let from = read_id(&mut reader)?;
+        let count = reader.u16()?;
+        let to = (0..count)
+            .map(|_| read_id(&mut reader))
+            .collect::<Result<_>>()?;
+        references.push(Reference {
+            ref_type: bmff_box.box_type,
+            from,
+            to,
+        });
+    }
+    Ok(references)
+}
+
+fn parse_ipma(ipma: &[u8]) -> Result<Vec<(ItemId, Vec<PropertyAssociation>)>> {
+    let mut reader = ByteReader::new(ipma);
+    let version = reader.u8()?;
+    let flags = reader.bytes(3)?;
+    let large_indexes = flags[2] & 1 != 0;
+    let count = reader.u32()?;
+    let mut associations = Vec::new();
+    for _ in 0..count {
+        let item_id = if version < 1 {
+            reader.u16()? as ItemId
+        } else {
+            reader.u32()?
+        };
+        let count = reader.u8()?;
+        let mut properties = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let property = if large_indexes {
+                let value = reader.u16()?;
+                PropertyAssociation {
+                    essential: value & 0x8000 != 0,
+                    index: value & 0x7fff,
+                }
+            } else {
+                let value = reader.u8()?;
+                PropertyAssociation {
+                    essential: value & 0x80 != 0,
+                    index: (value & 0x7f) as u16,
+                }
+            };
+            properties.push(property);
+        }
+        associations.push((item_id, properties));
+    }
+    Ok(associations)
+}
+
+fn parse_grpl(grpl: &[u8]) -> Result<Vec<EntityGroup>> {
+    let mut groups = Vec::new();
+    for bmff_box in Boxes(grpl) {
+        let mut reader = ByteReader::new(bmff_box.payload);
+        let version = reader.u8()?;
+        let flags = reader.bytes(3)?;
+        let group_id = reader.u32()?;
+        let count = reader.u32()?;
+        let entity_ids = (0..count).map(|_| reader.u32()).collect::<Result<_>>()?;
+        groups.push(EntityGroup {
+            group_type: bmff_box.box_type,
+            version,
+            flags: u32::from_be_bytes([0, flags[0], flags[1], flags[2]]),
+            group_id,
+            entity_ids,
+            extra: reader.remaining().to_vec(),
+        });
+    }
+    Ok(groups)
+}
+
+/// Returns `infe` box of `mime` item without content encoding.
+fn without_content_encoding(infe: &[u8]) -> Result<Vec<u8>> {
+    let payload = Boxes(infe)
+        .next()
+        .map(|b| b.payload)
+        .ok_or_else(|| invalid_data(HeifErrorSubCode::NoInfeBox, "Invalid infe box"))?;
+    let mut reader = ByteReader::new(payload);
+    let version = reader.u8()?;
+    reader.bytes(3)?;
+    // ID, protection index and item type.
+    reader.bytes(if version < 3 { 8 } else { 10 })?;
+    let _name = reader.c_string();
+    let _content_type = reader.c_string();
+    let size = payload.len() - reader.remaining().len();
+    let mut result = Vec::with_capacity(size + 8);
+    write_box(&mut result, b"infe", |out| {
+        out.extend_from_slice(&payload[..size])
+    });
+    Ok(result)
+}
+
+/// Returns Exif data prefixed with offset of the TIFF header,
+/// like `libheif` stores it.
+pub(crate) fn exif_item_data(data: &[u8]) -> Result<Vec<u8>> {
+    let offset = data
+        .windows(4)
+        .position(|magic| magic == b"MM\0*" || magic == b"II*\0")
+        .ok_or_else(|| HeifError {
+            code: HeifErrorCode::UsageError,
+            sub_code: HeifErrorSubCode::InvalidParameterValue,
+            message: "Could not find location of TIFF header in Exif metadata".to_string(),
+        })?;
+    let mut item_data = Vec::with_capacity(data.len() + 4);
+    item_data.extend_from_slice(&(offset as u32).to_be_bytes());
+    item_data.extend_from_slice(data);
+    Ok(item_data)
+}
+
+fn write_id(out: &mut Vec<u8>, id: u32, large: bool) {
+    if large {
+        out.extend_from_slice(&id.to_be_bytes());
+    } else {
+        out.extend_from_slice(&(id as u16).to_be_bytes());
+    }
+}
+
+fn write_uint(out: &mut Vec<u8>, value: u64, size: u8) {
+    if size == 8 {
+        out.extend_from_slice(&value.to_be_bytes());
+    } else {
+        out.extend_from_slice(&(value as u32).to_be_bytes());
+    }
+}
+
+fn invalid_data(sub_code: HeifErrorSubCode, message: &str) -> HeifError {
+    HeifError {
+        code: HeifErrorCode::InvalidInput,
+        sub_code,
+        message: message.to_string(),
+    }
+}
+
+fn unsupported(message: &str) -> HeifError {
+    HeifError {
+        code: HeifErrorCode::UnsupportedFeature,
+        sub_code: HeifErrorSubCode::Unspecified,
+        message: message.to_string(),
+    }
+}
+
+fn unsupported_version(box_type: &str, version: u8) -> HeifError {
+    HeifError {
+        code: HeifErrorCode::UnsupportedFeature,
+        sub_code: HeifErrorSubCode::UnsupportedDataVersion,
+        message: format!("Unsupported version of {} box: {}", box_type, version),
+    }
+}
+
+pub(crate) fn non_existing_item(item_id: ItemId) -> HeifError {
+    HeifError {
+        code: HeifErrorCode::UsageError,
+        sub_code: HeifErrorSubCode::NonExistingItemReferenced,
+        message: format!("Item with ID {} doesn't exist", item_id),
+    }
+}
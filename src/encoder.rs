@@ -2,14 +2,19 @@ use std::collections::HashMap;
 use std::ffi::CString;
 use std::fmt::{Debug, Formatter};
 use std::marker::PhantomData;
+use std::os::raw::c_char;
 use std::ptr;
 use std::sync::Mutex;
 
 use libheif_sys as lh;
 
+use crate::progress::ProgressCallbacks;
 use crate::utils::cstr_to_str;
+#[cfg(feature = "v1_19")]
+use crate::CancellationToken;
 use crate::{
-    ColorConversionOptions, HeifError, HeifErrorCode, HeifErrorSubCode, ImageOrientation, Result,
+    ColorConversionOptions, EncoderConfig, HeifError, HeifErrorCode, HeifErrorSubCode,
+    ImageOrientation, ProgressStep, Result,
 };
 
 static ENCODER_MUTEX: Mutex<()> = Mutex::new(());
@@ -68,20 +73,122 @@ pub enum EncoderQuality {
 
 pub type EncoderParametersTypes = HashMap<String, EncoderParameterType>;
 
+/// Description of encoder's parameter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncoderParameterInfo {
+    pub name: String,
+    pub parameter_type: EncoderParameterType,
+    /// Value of the parameter used by the encoder by default.
+    /// It is `None` if the parameter has no default value
+    /// or the encoder failed to return it.
+    pub default_value: Option<EncoderParameterValue>,
+    /// Minimal value of integer parameter.
+    pub minimum: Option<i32>,
+    /// Maximal value of integer parameter.
+    pub maximum: Option<i32>,
+    /// Valid values of integer parameter. Empty if any value
+    /// in range from `minimum` to `maximum` is allowed.
+    pub valid_int_values: Vec<i32>,
+    /// Valid values of string parameter. Empty if any value is allowed.
+    pub valid_string_values: Vec<String>,
+}
+
+impl EncoderParameterInfo {
+    /// Checks that the value may be used for the parameter.
+    pub fn validate(&self, value: &EncoderParameterValue) -> Result<()> {
+        let invalid_value = |message: String| HeifError {
+            code: HeifErrorCode::UsageError,
+            sub_code: HeifErrorSubCode::InvalidParameterValue,
+            message,
+        };
+        match value {
+            EncoderParameterValue::Int(v) => {
+                if self.parameter_type != EncoderParameterType::Int {
+                    return Err(invalid_value(format!(
+                        "Parameter '{}' has type {:?}, but integer value was given",
+                        self.name, self.parameter_type
+                    )));
+                }
+                let out_of_range = self.minimum.is_some_and(|min| *v < min)
+                    || self.maximum.is_some_and(|max| *v > max);
+                if out_of_range {
+                    let bound = |b: Option<i32>| b.map(|b| b.to_string()).unwrap_or_default();
+                    return Err(invalid_value(format!(
+                        "Value {} of parameter '{}' is out of range [{}, {}]",
+                        v,
+                        self.name,
+                        bound(self.minimum),
+                        bound(self.maximum)
+                    )));
+                }
+                if !self.valid_int_values.is_empty() && !self.valid_int_values.contains(v) {
+                    return Err(invalid_value(format!(
+                        "Value {} of parameter '{}' is not one of {:?}",
+                        v, self.name, self.valid_int_values
+                    )));
+                }
+            }
+            EncoderParameterValue::Bool(_) => {
+                if self.parameter_type != EncoderParameterType::Bool {
+                    return Err(invalid_value(format!(
+                        "Parameter '{}' has type {:?}, but boolean value was given",
+                        self.name, self.parameter_type
+                    )));
+                }
+            }
+            EncoderParameterValue::String(v) => {
+                if self.parameter_type != EncoderParameterType::String {
+                    return Err(invalid_value(format!(
+                        "Parameter '{}' has type {:?}, but string value was given",
+                        self.name, self.parameter_type
+                    )));
+                }
+                if !self.valid_string_values.is_empty() && !self.valid_string_values.contains(v) {
+                    return Err(invalid_value(format!(
+                        "Value '{}' of parameter '{}' is not one of {:?}",
+                        v, self.name, self.valid_string_values
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 pub struct Encoder<'a> {
     pub(crate) inner: *mut lh::heif_encoder,
-    pub(crate) parameters_types: EncoderParametersTypes,
+    pub(crate) parameters_info: HashMap<String, EncoderParameterInfo>,
     phantom: PhantomData<&'a mut lh::heif_encoder>,
 }
 
 impl<'a> Encoder<'a> {
     pub(crate) fn new(c_encoder: &'a mut lh::heif_encoder) -> Result<Self> {
-        let parameters_types = parameters_types(c_encoder)?;
-        Ok(Self {
+        let parameters_info = parameters_info(c_encoder)?;
+        let mut encoder = Self {
             inner: c_encoder,
-            parameters_types,
+            parameters_info,
             phantom: PhantomData::default(),
-        })
+        };
+        // Just created encoder has default values of parameters.
+        // Reading of them is best-effort: a default value that
+        // can't be read is left as `None`.
+        let mut default_values = Vec::new();
+        for info in encoder.parameters_info.values() {
+            let c_param_name = CString::new(info.name.as_str()).unwrap();
+            let has_default =
+                unsafe { lh::heif_encoder_has_default(encoder.inner, c_param_name.as_ptr()) };
+            if has_default != 0 {
+                if let Ok(value) = encoder.parameter_value(&info.name, info.parameter_type) {
+                    default_values.push((info.name.clone(), value));
+                }
+            }
+        }
+        for (name, value) in default_values {
+            if let Some(info) = encoder.parameters_info.get_mut(&name) {
+                info.default_value = Some(value);
+            }
+        }
+        Ok(encoder)
     }
 }
 
@@ -91,6 +198,11 @@ impl<'a> Drop for Encoder<'a> {
     }
 }
 
+// An instance of encoder is owned by the structure and isn't shared with
+// other instances. It isn't `Sync` because some methods with `&self`,
+// like `set_parameter_value()`, change the state of the encoder.
+unsafe impl Send for Encoder<'_> {}
+
 impl<'a> Encoder<'a> {
     /// Name of encoder.
     pub fn name(&self) -> String {
@@ -101,6 +213,94 @@ impl<'a> Encoder<'a> {
         cstr_to_str(res).unwrap_or("").to_owned()
     }
 
+    /// ID of encoder plugin (see [`EncoderDescriptor::id()`]).
+    ///
+    /// It is found by name of the encoder among descriptors
+    /// of all registered encoders.
+    pub fn id(&self) -> Option<String> {
+        let name = self.name();
+        let count = unsafe {
+            lh::heif_get_encoder_descriptors(
+                CompressionFormat::Undefined as _,
+                ptr::null(),
+                ptr::null_mut(),
+                0,
+            )
+        };
+        let mut descriptors_ptr = Vec::with_capacity(count.max(0) as usize);
+        unsafe {
+            let count = lh::heif_get_encoder_descriptors(
+                CompressionFormat::Undefined as _,
+                ptr::null(),
+                descriptors_ptr.as_mut_ptr(),
+                count,
+            );
+            descriptors_ptr.set_len(count.max(0) as usize);
+        }
+        descriptors_ptr
+            .into_iter()
+            .filter_map(|d_ptr| unsafe { d_ptr.as_ref().map(EncoderDescriptor::new) })
+            .find(|descriptor| descriptor.name() == name)
+            .map(|descriptor| descriptor.id().to_string())
+    }
+
+    /// Set values of parameters from typed configuration of encoder.
+    ///
+    /// Returns an error if the configuration is intended for another
+    /// encoder. Parameters that are absent in the linked version of
+    /// encoder plugin are skipped, their names are returned.
+    ///
+    /// All values are validated before setting any of them. If some values
+    /// are rejected, the encoder is left unchanged and an error with
+    /// sub-code [`HeifErrorSubCode::InvalidParameterValue`] is returned.
+    /// Its message lists all rejected values and skipped parameters.
+    pub fn apply_config(&mut self, config: &impl EncoderConfig) -> Result<Vec<&'static str>> {
+        let expected_id = config.encoder_id();
+        let id = self.id();
+        if id.as_deref() != Some(expected_id) {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::InvalidParameterValue,
+                message: format!(
+                    "Configuration of '{}' encoder can't be applied to '{}' encoder",
+                    expected_id,
+                    id.unwrap_or_else(|| self.name())
+                ),
+            });
+        }
+        let mut skipped = Vec::new();
+        let mut rejected = Vec::new();
+        let mut values = Vec::new();
+        for (name, value) in config.parameters() {
+            match self.parameters_info.get(name) {
+                Some(info) => match info.validate(&value) {
+                    Ok(()) => values.push((name, value)),
+                    Err(err) => rejected.push(err.message),
+                },
+                None => skipped.push(name),
+            }
+        }
+        if !rejected.is_empty() {
+            let mut message = format!(
+                "Configuration of '{}' encoder has rejected values: {}",
+                expected_id,
+                rejected.join("; ")
+            );
+            if !skipped.is_empty() {
+                message.push_str(&format!(". Skipped parameters: {}", skipped.join(", ")));
+            }
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::InvalidParameterValue,
+                message,
+            });
+        }
+        for (name, value) in values {
+            self.set_parameter_value(name, value)?;
+        }
+        Ok(skipped)
+    }
+
     pub fn set_quality(&mut self, quality: EncoderQuality) -> Result<()> {
         let err = match quality {
             EncoderQuality::LossLess => unsafe { lh::heif_encoder_set_lossless(self.inner, 1) },
@@ -165,14 +365,26 @@ impl<'a> Encoder<'a> {
     }
 
     pub fn parameters_names(&self) -> Vec<String> {
-        self.parameters_types.keys().cloned().collect()
+        self.parameters_info.keys().cloned().collect()
+    }
+
+    /// Description of encoder's parameter.
+    pub fn parameter_info(&self, name: &str) -> Option<&EncoderParameterInfo> {
+        self.parameters_info.get(name)
+    }
+
+    /// Descriptions of all parameters of the encoder sorted by name.
+    pub fn parameters_info(&self) -> Vec<&EncoderParameterInfo> {
+        let mut infos: Vec<_> = self.parameters_info.values().collect();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        infos
     }
 
     /// Get value of encoder's parameter.
     pub fn parameter(&self, name: &str) -> Result<Option<EncoderParameterValue>> {
-        match self.parameters_types.get(name) {
-            Some(param_type) => {
-                let value = self.parameter_value(name, *param_type)?;
+        match self.parameters_info.get(name) {
+            Some(info) => {
+                let value = self.parameter_value(name, info.parameter_type)?;
                 Ok(Some(value))
             }
             None => Ok(None),
@@ -180,7 +392,13 @@ impl<'a> Encoder<'a> {
     }
 
     /// Set value of encoder's parameter.
+    ///
+    /// The value is validated with help of [`EncoderParameterInfo::validate()`]
+    /// before passing it into the encoder.
     pub fn set_parameter_value(&self, name: &str, value: EncoderParameterValue) -> Result<()> {
+        if let Some(info) = self.parameters_info.get(name) {
+            info.validate(&value)?;
+        }
         let c_param_name = CString::new(name).unwrap();
         let err = match value {
             EncoderParameterValue::Bool(v) => unsafe {
@@ -203,8 +421,10 @@ impl<'a> Encoder<'a> {
     }
 }
 
-fn parameters_types(c_encoder: &mut lh::heif_encoder) -> Result<EncoderParametersTypes> {
-    let mut res = EncoderParametersTypes::new();
+fn parameters_info(
+    c_encoder: &mut lh::heif_encoder,
+) -> Result<HashMap<String, EncoderParameterInfo>> {
+    let mut res = HashMap::new();
     unsafe {
         let mut param_pointers = lh::heif_encoder_list_parameters(c_encoder);
         if !param_pointers.is_null() {
@@ -222,7 +442,23 @@ fn parameters_types(c_encoder: &mut lh::heif_encoder) -> Result<EncoderParameter
                 };
                 let c_param_name = lh::heif_encoder_parameter_get_name(raw_param);
                 let name = cstr_to_str(c_param_name).unwrap_or("").to_string();
-                res.insert(name, param_type);
+                let mut info = EncoderParameterInfo {
+                    name: name.clone(),
+                    parameter_type: param_type,
+                    default_value: None,
+                    minimum: None,
+                    maximum: None,
+                    valid_int_values: Vec::new(),
+                    valid_string_values: Vec::new(),
+                };
+                match param_type {
+                    EncoderParameterType::Int => read_valid_int_values(raw_param, &mut info)?,
+                    EncoderParameterType::String => {
+                        info.valid_string_values = valid_string_values(raw_param)?
+                    }
+                    EncoderParameterType::Bool => {}
+                }
+                res.insert(name, info);
                 param_pointers = param_pointers.offset(1);
             }
         }
@@ -230,16 +466,63 @@ fn parameters_types(c_encoder: &mut lh::heif_encoder) -> Result<EncoderParameter
     Ok(res)
 }
 
+unsafe fn read_valid_int_values(
+    raw_param: &lh::heif_encoder_parameter,
+    info: &mut EncoderParameterInfo,
+) -> Result<()> {
+    let mut have_minimum = 0;
+    let mut have_maximum = 0;
+    let mut minimum = 0;
+    let mut maximum = 0;
+    let mut num_valid_values = 0;
+    let mut valid_values: *const i32 = ptr::null();
+    let err = lh::heif_encoder_parameter_get_valid_integer_values(
+        raw_param,
+        &mut have_minimum,
+        &mut have_maximum,
+        &mut minimum,
+        &mut maximum,
+        &mut num_valid_values,
+        &mut valid_values,
+    );
+    HeifError::from_heif_error(err)?;
+    info.minimum = (have_minimum != 0).then_some(minimum);
+    info.maximum = (have_maximum != 0).then_some(maximum);
+    if num_valid_values > 0 && !valid_values.is_null() {
+        info.valid_int_values =
+            std::slice::from_raw_parts(valid_values, num_valid_values as usize).to_vec();
+    }
+    Ok(())
+}
+
+unsafe fn valid_string_values(raw_param: &lh::heif_encoder_parameter) -> Result<Vec<String>> {
+    let mut c_values: *const *const c_char = ptr::null();
+    let err = lh::heif_encoder_parameter_get_valid_string_values(raw_param, &mut c_values);
+    HeifError::from_heif_error(err)?;
+    let mut values = Vec::new();
+    if !c_values.is_null() {
+        while !(*c_values).is_null() {
+            values.push(cstr_to_str(*c_values).unwrap_or("").to_string());
+            c_values = c_values.offset(1);
+        }
+    }
+    Ok(values)
+}
+
 #[derive(Debug)]
 pub struct EncodingOptions {
     inner: ptr::NonNull<lh::heif_encoding_options>,
+    callbacks: ProgressCallbacks,
 }
 
 impl EncodingOptions {
     pub fn new() -> Result<Self> {
         let inner_ptr = unsafe { lh::heif_encoding_options_alloc() };
         match ptr::NonNull::new(inner_ptr) {
-            Some(inner) => Ok(Self { inner }),
+            Some(inner) => Ok(Self {
+                inner,
+                callbacks: ProgressCallbacks::default(),
+            }),
             None => Err(HeifError {
                 code: HeifErrorCode::MemoryAllocationError,
                 sub_code: HeifErrorSubCode::Unspecified,
@@ -345,6 +628,55 @@ impl EncodingOptions {
         let lh_options = &mut self.inner_mut().color_conversion_options;
         options.fill_cc_options(lh_options);
     }
+
+    /// Set a handler that receives progress of encoding.
+    ///
+    /// The handler is called with arguments `(step, current, max)`.
+    /// `libheif` doesn't report progress of encoding by itself,
+    /// so encoding of a single image is reported as one unit of work
+    /// of [`ProgressStep::Total`] step.
+    /// [`HeifContext::encode_grid()`](crate::HeifContext::encode_grid)
+    /// reports progress for each encoded tile only with `libheif` 1.23
+    /// or newer (`v1_19`), with older versions the whole grid is
+    /// reported as a single unit of work.
+    ///
+    /// If the handler panics, the panic is caught and the handler
+    /// isn't called anymore.
+    pub fn set_progress_handler<F>(&mut self, handler: F)
+    where
+        F: FnMut(ProgressStep, u32, u32) + Send + 'static,
+    {
+        self.callbacks.set_progress_handler(Some(Box::new(handler)));
+    }
+
+    /// Remove the handler set by
+    /// [`EncodingOptions::set_progress_handler()`].
+    pub fn remove_progress_handler(&mut self) {
+        self.callbacks.set_progress_handler(None);
+    }
+
+    /// Returns the token set by
+    /// [`EncodingOptions::set_cancellation_token()`].
+    #[cfg(feature = "v1_19")]
+    pub fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.callbacks.cancellation_token()
+    }
+
+    /// Set a token that is checked before encoding of an image
+    /// and between encoding of tiles of a grid image.
+    ///
+    /// If cancellation was requested, encoding methods of
+    /// [`HeifContext`](crate::HeifContext) return an error with
+    /// code [`HeifErrorCode::Canceled`].
+    /// Note: a codec can't be interrupted while it encodes an image or a tile.
+    /// Tiles of [`HeifContext::encode_grid()`](crate::HeifContext::encode_grid)
+    /// are checked separately only with `libheif` 1.23 or newer,
+    /// with older versions the token is checked once before encoding
+    /// of the whole grid.
+    #[cfg(feature = "v1_19")]
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.callbacks.set_cancellation_token(token);
+    }
 }
 
 /// This function makes sure the encoding options
@@ -358,6 +690,12 @@ pub(crate) fn get_encoding_options_ptr(
         .unwrap_or_else(ptr::null_mut)
 }
 
+pub(crate) fn get_encoding_callbacks(
+    options: &Option<EncodingOptions>,
+) -> Option<&ProgressCallbacks> {
+    options.as_ref().map(|o| &o.callbacks)
+}
+
 #[derive(Copy, Clone)]
 pub struct EncoderDescriptor<'a> {
     pub(crate) inner: &'a lh::heif_encoder_descriptor,
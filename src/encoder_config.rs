@@ -0,0 +1,326 @@
+use crate::EncoderParameterValue;
+
+/// Typed configuration of an encoder plugin.
+///
+/// Use [`Encoder::apply_config()`](crate::Encoder::apply_config) to set
+/// values of parameters into an encoder.
+///
+/// `kvazaar` encoder has only parameters of quality and lossless mode,
+/// use [`Encoder::set_quality()`](crate::Encoder::set_quality) to set them.
+pub trait EncoderConfig {
+    /// ID of encoder plugin (see [`EncoderDescriptor::id()`](crate::EncoderDescriptor::id))
+    /// which can be configured.
+    fn encoder_id(&self) -> &'static str;
+
+    /// Names and values of parameters that must be set.
+    fn parameters(&self) -> Vec<(&'static str, EncoderParameterValue)>;
+}
+
+/// Chroma subsampling used by an encoder.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EncoderChroma {
+    C420,
+    C422,
+    C444,
+}
+
+impl EncoderChroma {
+    fn value(&self) -> EncoderParameterValue {
+        let value = match self {
+            Self::C420 => "420",
+            Self::C422 => "422",
+            Self::C444 => "444",
+        };
+        EncoderParameterValue::String(value.to_string())
+    }
+}
+
+fn int(value: Option<i32>) -> Option<EncoderParameterValue> {
+    value.map(EncoderParameterValue::Int)
+}
+
+fn int_u8(value: Option<u8>) -> Option<EncoderParameterValue> {
+    value.map(|v| EncoderParameterValue::Int(v as i32))
+}
+
+fn boolean(value: Option<bool>) -> Option<EncoderParameterValue> {
+    value.map(EncoderParameterValue::Bool)
+}
+
+fn string(value: Option<&str>) -> Option<EncoderParameterValue> {
+    value.map(|v| EncoderParameterValue::String(v.to_string()))
+}
+
+fn collect(
+    params: impl IntoIterator<Item = (&'static str, Option<EncoderParameterValue>)>,
+) -> Vec<(&'static str, EncoderParameterValue)> {
+    params
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|v| (name, v)))
+        .collect()
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AomTune {
+    Auto,
+    Psnr,
+    Ssim,
+    /// Requires `libaom` with support of this tune.
+    Iq,
+}
+
+impl AomTune {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Psnr => "psnr",
+            Self::Ssim => "ssim",
+            Self::Iq => "iq",
+        }
+    }
+}
+
+/// Configuration of AV1 encoder based on `libaom` (ID is `aom`).
+///
+/// Parameters with `None` values are not changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AomConfig {
+    /// Speed of encoding (0-9). Higher values are faster.
+    pub speed: Option<u8>,
+    pub chroma: Option<EncoderChroma>,
+    pub tune: Option<AomTune>,
+    pub threads: Option<u8>,
+    /// Minimal quantizer (0-63).
+    pub min_q: Option<u8>,
+    /// Maximal quantizer (0-63).
+    pub max_q: Option<u8>,
+    /// Quality of alpha channel (0-100).
+    pub alpha_quality: Option<u8>,
+    /// Minimal quantizer of alpha channel (0-63).
+    pub alpha_min_q: Option<u8>,
+    /// Maximal quantizer of alpha channel (0-63).
+    pub alpha_max_q: Option<u8>,
+    pub lossless_alpha: Option<bool>,
+    pub realtime: Option<bool>,
+}
+
+impl EncoderConfig for AomConfig {
+    fn encoder_id(&self) -> &'static str {
+        "aom"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, EncoderParameterValue)> {
+        collect([
+            ("speed", int_u8(self.speed)),
+            ("chroma", self.chroma.map(|c| c.value())),
+            ("tune", string(self.tune.map(|t| t.as_str()))),
+            ("threads", int_u8(self.threads)),
+            ("min-q", int_u8(self.min_q)),
+            ("max-q", int_u8(self.max_q)),
+            ("alpha-quality", int_u8(self.alpha_quality)),
+            ("alpha-min-q", int_u8(self.alpha_min_q)),
+            ("alpha-max-q", int_u8(self.alpha_max_q)),
+            ("lossless-alpha", boolean(self.lossless_alpha)),
+            ("realtime", boolean(self.realtime)),
+        ])
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum X265Preset {
+    UltraFast,
+    SuperFast,
+    VeryFast,
+    Faster,
+    Fast,
+    Medium,
+    Slow,
+    Slower,
+    VerySlow,
+    Placebo,
+}
+
+impl X265Preset {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::UltraFast => "ultrafast",
+            Self::SuperFast => "superfast",
+            Self::VeryFast => "veryfast",
+            Self::Faster => "faster",
+            Self::Fast => "fast",
+            Self::Medium => "medium",
+            Self::Slow => "slow",
+            Self::Slower => "slower",
+            Self::VerySlow => "veryslow",
+            Self::Placebo => "placebo",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum X265Tune {
+    Psnr,
+    Ssim,
+    Grain,
+    FastDecode,
+}
+
+impl X265Tune {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Psnr => "psnr",
+            Self::Ssim => "ssim",
+            Self::Grain => "grain",
+            Self::FastDecode => "fastdecode",
+        }
+    }
+}
+
+/// Configuration of HEVC encoder based on `x265` (ID is `x265`).
+///
+/// Parameters with `None` values are not changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct X265Config {
+    pub preset: Option<X265Preset>,
+    pub tune: Option<X265Tune>,
+    pub chroma: Option<EncoderChroma>,
+    /// Complexity of encoding (0-100).
+    pub complexity: Option<u8>,
+    /// Depth of intra transform units (1-4).
+    pub tu_intra_depth: Option<u8>,
+}
+
+impl EncoderConfig for X265Config {
+    fn encoder_id(&self) -> &'static str {
+        "x265"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, EncoderParameterValue)> {
+        collect([
+            ("preset", string(self.preset.map(|p| p.as_str()))),
+            ("tune", string(self.tune.map(|t| t.as_str()))),
+            ("chroma", self.chroma.map(|c| c.value())),
+            ("complexity", int_u8(self.complexity)),
+            ("tu-intra-depth", int_u8(self.tu_intra_depth)),
+        ])
+    }
+}
+
+/// Configuration of AV1 encoder based on `rav1e` (ID is `rav1e`).
+///
+/// Parameters with `None` values are not changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rav1eConfig {
+    /// Speed of encoding (0-10). Higher values are faster.
+    pub speed: Option<u8>,
+    pub threads: Option<u8>,
+    /// Number of tile rows (power of two).
+    pub tile_rows: Option<i32>,
+    /// Number of tile columns (power of two).
+    pub tile_cols: Option<i32>,
+    pub chroma: Option<EncoderChroma>,
+    /// Minimal quantizer (0-255).
+    pub min_q: Option<u8>,
+}
+
+impl EncoderConfig for Rav1eConfig {
+    fn encoder_id(&self) -> &'static str {
+        "rav1e"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, EncoderParameterValue)> {
+        collect([
+            ("speed", int_u8(self.speed)),
+            ("threads", int_u8(self.threads)),
+            ("tile-rows", int(self.tile_rows)),
+            ("tile-cols", int(self.tile_cols)),
+            ("chroma", self.chroma.map(|c| c.value())),
+            ("min-q", int_u8(self.min_q)),
+        ])
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SvtTune {
+    Vq,
+    Psnr,
+    Ssim,
+    /// Requires SVT-AV1 v3 or newer.
+    Iq,
+    /// Requires SVT-AV1 v3 or newer.
+    MsSsim,
+}
+
+impl SvtTune {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Vq => "vq",
+            Self::Psnr => "psnr",
+            Self::Ssim => "ssim",
+            Self::Iq => "iq",
+            Self::MsSsim => "ms-ssim",
+        }
+    }
+}
+
+/// Configuration of AV1 encoder based on `SVT-AV1` (ID is `svt`).
+///
+/// Parameters with `None` values are not changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SvtConfig {
+    /// Speed of encoding (0-13). Higher values are faster.
+    pub speed: Option<u8>,
+    pub threads: Option<u8>,
+    /// Number of tile rows (power of two).
+    pub tile_rows: Option<i32>,
+    /// Number of tile columns (power of two).
+    pub tile_cols: Option<i32>,
+    pub chroma: Option<EncoderChroma>,
+    /// Quantizer (0-63).
+    pub qp: Option<u8>,
+    /// Minimal quantizer (0-63).
+    pub min_q: Option<u8>,
+    /// Maximal quantizer (0-63).
+    pub max_q: Option<u8>,
+    pub tune: Option<SvtTune>,
+}
+
+impl EncoderConfig for SvtConfig {
+    fn encoder_id(&self) -> &'static str {
+        "svt"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, EncoderParameterValue)> {
+        collect([
+            ("speed", int_u8(self.speed)),
+            ("threads", int_u8(self.threads)),
+            ("tile-rows", int(self.tile_rows)),
+            ("tile-cols", int(self.tile_cols)),
+            ("chroma", self.chroma.map(|c| c.value())),
+            ("qp", int_u8(self.qp)),
+            ("min-q", int_u8(self.min_q)),
+            ("max-q", int_u8(self.max_q)),
+            ("tune", string(self.tune.map(|t| t.as_str()))),
+        ])
+    }
+}
+
+/// Configuration of JPEG 2000 encoder based on `OpenJPEG` (ID is `openjpeg`).
+///
+/// Parameters with `None` values are not changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpenJpegConfig {
+    pub chroma: Option<EncoderChroma>,
+}
+
+impl EncoderConfig for OpenJpegConfig {
+    fn encoder_id(&self) -> &'static str {
+        "openjpeg"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, EncoderParameterValue)> {
+        collect([("chroma", self.chroma.map(|c| c.value()))])
+    }
+}
@@ -0,0 +1,595 @@
+use std::any::TypeId;
+use std::ffi::{CStr, CString};
+use std::mem::ManuallyDrop;
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Mutex;
+
+use libheif_sys as lh;
+
+use crate::plugin_api::{
+    call_plugin, HeifEncoderBooleanValues, HeifEncoderIntegerValues, HeifEncoderParameter,
+    HeifEncoderParameterValues, HeifEncoderPlugin, HeifEncoderStringValues, StaticData,
+    ENCODER_PLUGIN_API_VERSION,
+};
+use crate::utils::{cstr_to_str, str_to_cstring};
+use crate::{
+    ColorSpace, CompressionFormat, EncoderParameterInfo, EncoderParameterType,
+    EncoderParameterValue, HeifError, HeifErrorCode, HeifErrorSubCode, Image, Result,
+};
+
+/// Encoder of images implemented in Rust.
+///
+/// A plugin is registered with help of
+/// [`LibHeif::register_encoder_plugin()`](crate::LibHeif::register_encoder_plugin).
+/// After that it is returned by
+/// [`LibHeif::encoder_descriptors()`](crate::LibHeif::encoder_descriptors)
+/// and `libheif` creates a new instance of the type for every
+/// [`Encoder`](crate::Encoder) of the plugin.
+///
+/// Instances may be created and used in different threads.
+pub trait EncoderPlugin: Default + Send + 'static {
+    /// Human-readable name of the plugin.
+    const NAME: &'static CStr;
+    /// Unique ID of the plugin returned by
+    /// [`EncoderDescriptor::id()`](crate::EncoderDescriptor::id).
+    const ID: &'static CStr;
+    /// Compression format of coded images.
+    const FORMAT: CompressionFormat;
+    /// Priority of the plugin. The encoder with the largest priority
+    /// is used by default to encode images of the format.
+    ///
+    /// Built-in encoders of `libheif` have priorities up to `100`.
+    const PRIORITY: u32;
+    const SUPPORTS_LOSSY_COMPRESSION: bool;
+    const SUPPORTS_LOSSLESS_COMPRESSION: bool;
+
+    /// Descriptions of parameters of the encoder, except quality
+    /// and lossless mode. It is called once on registration of the plugin.
+    ///
+    /// Default value of a parameter must be equal to the value
+    /// of the parameter in a new instance of the encoder.
+    fn parameters() -> Vec<EncoderParameterInfo> {
+        Vec::new()
+    }
+
+    /// Quality factor (0-100) of lossy compression.
+    fn quality(&self) -> u8;
+
+    fn set_quality(&mut self, quality: u8) -> Result<()>;
+
+    fn lossless(&self) -> bool;
+
+    fn set_lossless(&mut self, lossless: bool) -> Result<()>;
+
+    /// Returns value of one of parameters described by
+    /// [`EncoderPlugin::parameters()`].
+    fn parameter(&self, name: &str) -> Result<EncoderParameterValue> {
+        Err(unsupported_parameter(name))
+    }
+
+    /// Sets value of one of parameters described by
+    /// [`EncoderPlugin::parameters()`].
+    ///
+    /// The value is already checked with help of
+    /// [`EncoderParameterInfo::validate()`].
+    fn set_parameter(&mut self, name: &str, _value: EncoderParameterValue) -> Result<()> {
+        Err(unsupported_parameter(name))
+    }
+
+    /// Returns color space of images accepted by the encoder.
+    /// `libheif` converts images into this color space before encoding.
+    ///
+    /// By default, images are encoded in their own color space.
+    fn input_color_space(&self, color_space: ColorSpace) -> ColorSpace {
+        color_space
+    }
+
+    /// Encodes the image. Coded data are returned by the following
+    /// calls of [`EncoderPlugin::compressed_data()`].
+    fn encode_image(&mut self, image: &Image) -> Result<()>;
+
+    /// Returns the next chunk of coded data of the last encoded image
+    /// or `None` if all data were returned.
+    ///
+    /// Every chunk is a single unit of the coded bitstream: a NAL unit
+    /// without start code or length prefix for HEVC and VVC, an OBU
+    /// for AV1. Empty chunks are skipped.
+    fn compressed_data(&mut self) -> Result<Option<Vec<u8>>>;
+}
+
+fn unsupported_parameter(name: &str) -> HeifError {
+    HeifError {
+        code: HeifErrorCode::UsageError,
+        sub_code: HeifErrorSubCode::UnsupportedParameter,
+        message: format!("Unsupported encoder parameter '{}'", name),
+    }
+}
+
+/// Plugin structures registered in `libheif`, by types of plugins.
+///
+/// Structures are leaked because `libheif` keeps pointers to them
+/// until deinitialization and allows registering them again after that.
+static REGISTERED_PLUGINS: Mutex<Vec<(TypeId, &'static StaticData<RegisteredEncoder>)>> =
+    Mutex::new(Vec::new());
+
+pub(crate) fn register_encoder_plugin<P: EncoderPlugin>() -> Result<()> {
+    let mut registered = REGISTERED_PLUGINS.lock().unwrap_or_else(|e| e.into_inner());
+    let type_id = TypeId::of::<P>();
+    let encoder = match registered.iter().find(|(id, _)| *id == type_id) {
+        Some(&(_, encoder)) => encoder,
+        None => {
+            let encoder = RegisteredEncoder::new::<P>()?;
+            let encoder: &'static _ = Box::leak(Box::new(StaticData(encoder)));
+            registered.push((type_id, encoder));
+            encoder
+        }
+    };
+
+    // `libheif` keeps every registered copy of an encoder plugin,
+    // so the plugin is registered only if there is no plugin with its ID.
+    // Descriptors refer to plugin structures, so the plugin is already
+    // registered if the returned ID is the string of its own structure.
+    let id_ptr = encoder.0.plugin.id_name;
+    let count =
+        unsafe { lh::heif_get_encoder_descriptors(P::FORMAT as _, id_ptr, ptr::null_mut(), 0) };
+    let mut descriptors = vec![ptr::null(); count.max(0) as usize];
+    let count = unsafe {
+        lh::heif_get_encoder_descriptors(P::FORMAT as _, id_ptr, descriptors.as_mut_ptr(), count)
+    };
+    if count > 0 {
+        let descriptors = &descriptors[..count as usize];
+        let is_registered = descriptors
+            .iter()
+            .any(|&d| unsafe { lh::heif_encoder_descriptor_get_id_name(d) } == id_ptr);
+        if is_registered {
+            return Ok(());
+        }
+        return Err(HeifError {
+            code: HeifErrorCode::UsageError,
+            sub_code: HeifErrorSubCode::PluginLoadingError,
+            message: format!(
+                "Another encoder with ID '{}' is already registered",
+                P::ID.to_string_lossy()
+            ),
+        });
+    }
+    let plugin_ptr =
+        &encoder.0.plugin as *const HeifEncoderPlugin as *const lh::heif_encoder_plugin;
+    let err = unsafe { lh::heif_register_encoder_plugin(plugin_ptr) };
+    HeifError::from_heif_error(err)
+}
+
+fn registered_encoder<P: EncoderPlugin>() -> Option<&'static RegisteredEncoder> {
+    let registered = REGISTERED_PLUGINS.lock().unwrap_or_else(|e| e.into_inner());
+    let type_id = TypeId::of::<P>();
+    registered
+        .iter()
+        .find(|(id, _)| *id == type_id)
+        .map(|(_, encoder)| &encoder.0)
+}
+
+/// Plugin structure with descriptions of parameters of the encoder.
+struct RegisteredEncoder {
+    plugin: HeifEncoderPlugin,
+    infos: Vec<EncoderParameterInfo>,
+    /// Null-terminated array of pointers to items of `parameters`.
+    parameter_ptrs: Vec<*const HeifEncoderParameter>,
+    /// Descriptions of parameters passed into `libheif`. They refer
+    /// to names and values stored in `infos`, `strings` and `string_lists`.
+    _parameters: Vec<HeifEncoderParameter>,
+    _strings: Vec<CString>,
+    _string_lists: Vec<Vec<*const c_char>>,
+}
+
+impl RegisteredEncoder {
+    fn new<P: EncoderPlugin>() -> Result<Self> {
+        let infos = P::parameters();
+        let mut strings = Vec::new();
+        let mut string_lists = Vec::new();
+        let mut c_str = |s: &str| -> Result<*const c_char> {
+            let c_string = str_to_cstring(s, "encoder parameter")?;
+            let c_ptr = c_string.as_ptr();
+            strings.push(c_string);
+            Ok(c_ptr)
+        };
+
+        let mut parameters = Vec::with_capacity(infos.len());
+        for info in infos.iter() {
+            if let Some(default_value) = &info.default_value {
+                info.validate(default_value)?;
+            }
+            let values = match info.parameter_type {
+                EncoderParameterType::Int => HeifEncoderParameterValues {
+                    integer: HeifEncoderIntegerValues {
+                        default_value: match info.default_value {
+                            Some(EncoderParameterValue::Int(v)) => v,
+                            _ => 0,
+                        },
+                        have_minimum_maximum: (info.minimum.is_some() || info.maximum.is_some())
+                            as u8,
+                        minimum: info.minimum.unwrap_or(i32::MIN),
+                        maximum: info.maximum.unwrap_or(i32::MAX),
+                        valid_values: if info.valid_int_values.is_empty() {
+                            ptr::null()
+                        } else {
+                            info.valid_int_values.as_ptr()
+                        },
+                        num_valid_values: info.valid_int_values.len() as c_int,
+                    },
+                },
+                EncoderParameterType::Bool => HeifEncoderParameterValues {
+                    boolean: HeifEncoderBooleanValues {
+                        default_value: matches!(
+                            info.default_value,
+                            Some(EncoderParameterValue::Bool(true))
+                        ) as c_int,
+                    },
+                },
+                EncoderParameterType::String => {
+                    let default_value = match &info.default_value {
+                        Some(EncoderParameterValue::String(v)) => c_str(v)?,
+                        _ => ptr::null(),
+                    };
+                    let valid_values = if info.valid_string_values.is_empty() {
+                        ptr::null()
+                    } else {
+                        let mut list = info
+                            .valid_string_values
+                            .iter()
+                            .map(|v| c_str(v))
+                            .collect::<Result<Vec<_>>>()?;
+                        list.push(ptr::null());
+                        let list_ptr = list.as_ptr();
+                        string_lists.push(list);
+                        list_ptr
+                    };
+                    HeifEncoderParameterValues {
+                        string: HeifEncoderStringValues {
+                            default_value,
+                            valid_values,
+                        },
+                    }
+                }
+            };
+            let parameter = HeifEncoderParameter {
+                version: 2,
+                name: c_str(&info.name)?,
+                parameter_type: info.parameter_type as _,
+                values,
+                has_default: info.default_value.is_some() as c_int,
+            };
+            parameters.push(parameter);
+        }
+
+        let parameter_ptrs = parameters
+            .iter()
+            .map(|p| p as *const HeifEncoderParameter)
+            .chain([ptr::null()])
+            .collect();
+        Ok(Self {
+            plugin: encoder_plugin::<P>(),
+            infos,
+            parameter_ptrs,
+            _parameters: parameters,
+            _strings: strings,
+            _string_lists: string_lists,
+        })
+    }
+
+    fn parameter_info(&self, name: *const c_char) -> Result<&EncoderParameterInfo> {
+        let name = cstr_to_str(name).unwrap_or("");
+        self.infos
+            .iter()
+            .find(|info| info.name == name)
+            .ok_or_else(|| unsupported_parameter(name))
+    }
+}
+
+fn encoder_plugin<P: EncoderPlugin>() -> HeifEncoderPlugin {
+    HeifEncoderPlugin {
+        plugin_api_version: ENCODER_PLUGIN_API_VERSION,
+        compression_format: P::FORMAT as _,
+        id_name: P::ID.as_ptr(),
+        priority: P::PRIORITY.min(c_int::MAX as u32) as c_int,
+        supports_lossy_compression: P::SUPPORTS_LOSSY_COMPRESSION as c_int,
+        supports_lossless_compression: P::SUPPORTS_LOSSLESS_COMPRESSION as c_int,
+        get_plugin_name: Some(plugin_name::<P>),
+        init_plugin: None,
+        cleanup_plugin: None,
+        new_encoder: Some(new_encoder::<P>),
+        free_encoder: Some(free_encoder::<P>),
+        set_parameter_quality: Some(set_parameter_quality::<P>),
+        get_parameter_quality: Some(get_parameter_quality::<P>),
+        set_parameter_lossless: Some(set_parameter_lossless::<P>),
+        get_parameter_lossless: Some(get_parameter_lossless::<P>),
+        set_parameter_logging_level: None,
+        get_parameter_logging_level: None,
+        list_parameters: Some(list_parameters::<P>),
+        set_parameter_integer: Some(set_parameter_integer::<P>),
+        get_parameter_integer: Some(get_parameter_integer::<P>),
+        set_parameter_boolean: Some(set_parameter_boolean::<P>),
+        get_parameter_boolean: Some(get_parameter_boolean::<P>),
+        set_parameter_string: Some(set_parameter_string::<P>),
+        get_parameter_string: Some(get_parameter_string::<P>),
+        query_input_colorspace: Some(query_input_colorspace),
+        encode_image: Some(encode_image::<P>),
+        get_compressed_data: Some(get_compressed_data::<P>),
+        query_input_colorspace2: Some(query_input_colorspace2::<P>),
+        query_encoded_size: None,
+    }
+}
+
+/// State of encoder passed into `libheif`.
+struct PluginEncoder<P> {
+    encoder: P,
+    registered: &'static RegisteredEncoder,
+    /// The last chunk of coded data, it must stay alive
+    /// until the next call of `get_compressed_data()`.
+    current_data: Vec<u8>,
+}
+
+impl<P: EncoderPlugin> PluginEncoder<P> {
+    fn parameter_value(
+        &self,
+        name: *const c_char,
+        parameter_type: EncoderParameterType,
+    ) -> Result<EncoderParameterValue> {
+        let info = self.registered.parameter_info(name)?;
+        if info.parameter_type != parameter_type {
+            return Err(unsupported_parameter(&info.name));
+        }
+        let value = self.encoder.parameter(&info.name)?;
+        info.validate(&value)?;
+        Ok(value)
+    }
+
+    fn set_parameter_value(
+        &mut self,
+        name: *const c_char,
+        value: EncoderParameterValue,
+    ) -> Result<()> {
+        let info = self.registered.parameter_info(name)?;
+        info.validate(&value)?;
+        self.encoder.set_parameter(&info.name, value)
+    }
+}
+
+#[inline]
+unsafe fn plugin_encoder<'a, P>(encoder: *mut c_void) -> &'a mut PluginEncoder<P> {
+    &mut *(encoder as *mut PluginEncoder<P>)
+}
+
+unsafe extern "C" fn plugin_name<P: EncoderPlugin>() -> *const c_char {
+    P::NAME.as_ptr()
+}
+
+unsafe extern "C" fn new_encoder<P: EncoderPlugin>(encoder: *mut *mut c_void) -> lh::heif_error {
+    *encoder = ptr::null_mut();
+    call_plugin(HeifErrorCode::EncoderPluginError, || {
+        let registered = registered_encoder::<P>().ok_or_else(|| HeifError {
+            code: HeifErrorCode::EncoderPluginError,
+            sub_code: HeifErrorSubCode::PluginLoadingError,
+            message: "Encoder plugin is not registered".to_string(),
+        })?;
+        let plugin_encoder = PluginEncoder {
+            encoder: P::default(),
+            registered,
+            current_data: Vec::new(),
+        };
+        *encoder = Box::into_raw(Box::new(plugin_encoder)) as _;
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn free_encoder<P: EncoderPlugin>(encoder: *mut c_void) {
+    let encoder = Box::from_raw(encoder as *mut PluginEncoder<P>);
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(encoder)));
+}
+
+unsafe extern "C" fn set_parameter_quality<P: EncoderPlugin>(
+    encoder: *mut c_void,
+    quality: c_int,
+) -> lh::heif_error {
+    let encoder = plugin_encoder::<P>(encoder);
+    call_plugin(HeifErrorCode::EncoderPluginError, || {
+        encoder.encoder.set_quality(quality.clamp(0, 100) as u8)
+    })
+}
+
+unsafe extern "C" fn get_parameter_quality<P: EncoderPlugin>(
+    encoder: *mut c_void,
+    quality: *mut c_int,
+) -> lh::heif_error {
+    let encoder = plugin_encoder::<P>(encoder);
+    call_plugin(HeifErrorCode::EncoderPluginError, || {
+        *quality = encoder.encoder.quality().into();
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn set_parameter_lossless<P: EncoderPlugin>(
+    encoder: *mut c_void,
+    lossless: c_int,
+) -> lh::heif_error {
+    let encoder = plugin_encoder::<P>(encoder);
+    call_plugin(HeifErrorCode::EncoderPluginError, || {
+        encoder.encoder.set_lossless(lossless != 0)
+    })
+}
+
+unsafe extern "C" fn get_parameter_lossless<P: EncoderPlugin>(
+    encoder: *mut c_void,
+    lossless: *mut c_int,
+) -> lh::heif_error {
+    let encoder = plugin_encoder::<P>(encoder);
+    call_plugin(HeifErrorCode::EncoderPluginError, || {
+        *lossless = encoder.encoder.lossless().into();
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn list_parameters<P: EncoderPlugin>(
+    encoder: *mut c_void,
+) -> *const *const lh::heif_encoder_parameter {
+    let encoder = plugin_encoder::<P>(encoder);
+    encoder.registered.parameter_ptrs.as_ptr() as _
+}
+
+unsafe extern "C" fn set_parameter_integer<P: EncoderPlugin>(
+    encoder: *mut c_void,
+    name: *const c_char,
+    value: c_int,
+) -> lh::heif_error {
+    let encoder = plugin_encoder::<P>(encoder);
+    call_plugin(HeifErrorCode::EncoderPluginError, || {
+        encoder.set_parameter_value(name, EncoderParameterValue::Int(value))
+    })
+}
+
+unsafe extern "C" fn get_parameter_integer<P: EncoderPlugin>(
+    encoder: *mut c_void,
+    name: *const c_char,
+    value: *mut c_int,
+) -> lh::heif_error {
+    let encoder = plugin_encoder::<P>(encoder);
+    call_plugin(HeifErrorCode::EncoderPluginError, || {
+        let param_value = encoder.parameter_value(name, EncoderParameterType::Int)?;
+        if let EncoderParameterValue::Int(v) = param_value {
+            *value = v;
+        }
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn set_parameter_boolean<P: EncoderPlugin>(
+    encoder: *mut c_void,
+    name: *const c_char,
+    value: c_int,
+) -> lh::heif_error {
+    let encoder = plugin_encoder::<P>(encoder);
+    call_plugin(HeifErrorCode::EncoderPluginError, || {
+        encoder.set_parameter_value(name, EncoderParameterValue::Bool(value != 0))
+    })
+}
+
+unsafe extern "C" fn get_parameter_boolean<P: EncoderPlugin>(
+    encoder: *mut c_void,
+    name: *const c_char,
+    value: *mut c_int,
+) -> lh::heif_error {
+    let encoder = plugin_encoder::<P>(encoder);
+    call_plugin(HeifErrorCode::EncoderPluginError, || {
+        let param_value = encoder.parameter_value(name, EncoderParameterType::Bool)?;
+        if let EncoderParameterValue::Bool(v) = param_value {
+            *value = v.into();
+        }
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn set_parameter_string<P: EncoderPlugin>(
+    encoder: *mut c_void,
+    name: *const c_char,
+    value: *const c_char,
+) -> lh::heif_error {
+    let encoder = plugin_encoder::<P>(encoder);
+    call_plugin(HeifErrorCode::EncoderPluginError, || {
+        let value = cstr_to_str(value).ok_or_else(|| HeifError {
+            code: HeifErrorCode::UsageError,
+            sub_code: HeifErrorSubCode::InvalidParameterValue,
+            message: "Value of encoder parameter is not valid UTF-8 string".to_string(),
+        })?;
+        encoder.set_parameter_value(name, EncoderParameterValue::String(value.to_string()))
+    })
+}
+
+unsafe extern "C" fn get_parameter_string<P: EncoderPlugin>(
+    encoder: *mut c_void,
+    name: *const c_char,
+    value: *mut c_char,
+    value_size: c_int,
+) -> lh::heif_error {
+    let encoder = plugin_encoder::<P>(encoder);
+    call_plugin(HeifErrorCode::EncoderPluginError, || {
+        let param_value = encoder.parameter_value(name, EncoderParameterType::String)?;
+        if let EncoderParameterValue::String(v) = param_value {
+            if !value.is_null() && value_size > 0 {
+                // The value is truncated to the size of the buffer.
+                let len = v.len().min(value_size as usize - 1);
+                ptr::copy_nonoverlapping(v.as_ptr(), value as *mut u8, len);
+                *value.add(len) = 0;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// `libheif` calls [`query_input_colorspace2()`] for plugins
+/// of version 2 and newer.
+unsafe extern "C" fn query_input_colorspace(
+    _colorspace: *mut lh::heif_colorspace,
+    _chroma: *mut lh::heif_chroma,
+) {
+}
+
+unsafe extern "C" fn query_input_colorspace2<P: EncoderPlugin>(
+    encoder: *mut c_void,
+    colorspace: *mut lh::heif_colorspace,
+    chroma: *mut lh::heif_chroma,
+) {
+    let encoder = plugin_encoder::<P>(encoder);
+    let Some(color_space) = ColorSpace::from_libheif(*colorspace, *chroma) else {
+        return;
+    };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        encoder.encoder.input_color_space(color_space)
+    }));
+    if let Ok(color_space) = result {
+        *colorspace = color_space.heif_color_space();
+        *chroma = color_space.heif_chroma();
+    }
+}
+
+unsafe extern "C" fn encode_image<P: EncoderPlugin>(
+    encoder: *mut c_void,
+    image: *const lh::heif_image,
+    _image_class: c_uint,
+) -> lh::heif_error {
+    let encoder = plugin_encoder::<P>(encoder);
+    call_plugin(HeifErrorCode::EncoderPluginError, || {
+        // The image is owned by `libheif`.
+        let image = ManuallyDrop::new(Image::from_heif_image(image as *mut _));
+        encoder.encoder.encode_image(&image)
+    })
+}
+
+unsafe extern "C" fn get_compressed_data<P: EncoderPlugin>(
+    encoder: *mut c_void,
+    data: *mut *mut u8,
+    size: *mut c_int,
+    _data_type: *mut c_uint,
+) -> lh::heif_error {
+    let encoder = plugin_encoder::<P>(encoder);
+    *data = ptr::null_mut();
+    *size = 0;
+    call_plugin(HeifErrorCode::EncoderPluginError, || {
+        // `libheif` reads the first byte of every returned chunk.
+        while let Some(chunk) = encoder.encoder.compressed_data()? {
+            if chunk.is_empty() {
+                continue;
+            }
+            let chunk_size = c_int::try_from(chunk.len()).map_err(|_| HeifError {
+                code: HeifErrorCode::EncoderPluginError,
+                sub_code: HeifErrorSubCode::Unspecified,
+                message: "Chunk of coded data is too large".to_string(),
+            })?;
+            encoder.current_data = chunk;
+            *data = encoder.current_data.as_mut_ptr();
+            *size = chunk_size;
+            break;
+        }
+        Ok(())
+    })
+}
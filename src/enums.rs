@@ -164,6 +164,23 @@ pub enum ImageOrientation {
     Unknown,
 }
 
+/// Angle of rotation of an image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    Rotate90Cw,
+    Rotate180,
+    Rotate270Cw,
+}
+
+/// Direction of mirroring of an image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MirrorDirection {
+    /// Flip image vertically (top row becomes bottom one).
+    Vertical,
+    /// Flip image horizontally (left column becomes right one).
+    Horizontal,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, enumn::N)]
 #[non_exhaustive]
 #[repr(C)]
@@ -278,3 +295,15 @@ impl AlphaCompositionMode {
         }
     }
 }
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, enumn::N)]
+#[non_exhaustive]
+#[repr(C)]
+pub enum ProgressStep {
+    /// Overall progress of an operation.
+    /// For example, the number of decoded tiles of a grid image.
+    Total = lh::heif_progress_step_heif_progress_step_total as _,
+    LoadTile = lh::heif_progress_step_heif_progress_step_load_tile as _,
+    /// This value is used when library `libheif` returns unknown value of progress step.
+    Unknown,
+}
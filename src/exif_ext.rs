@@ -0,0 +1,141 @@
+use exif::{DateTime, Exif, In, Tag, Value};
+
+use crate::{HeifError, HeifErrorCode, HeifErrorSubCode, Result};
+
+/// Geographic position from GPS tags of Exif.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsPosition {
+    /// Latitude in degrees. Positive values are north of the equator.
+    pub latitude: f64,
+    /// Longitude in degrees. Positive values are east of the prime meridian.
+    pub longitude: f64,
+    /// Altitude in meters relative to the sea level.
+    pub altitude: Option<f64>,
+}
+
+/// Accessors of commonly used fields of Exif.
+pub trait ExifExt {
+    /// Value of `Orientation` tag (1-8).
+    ///
+    /// Note: `libheif` rotates and mirrors decoded images according to
+    /// transformation properties of the image. So this orientation
+    /// must not be applied to decoded images once more.
+    fn orientation(&self) -> Option<u16>;
+
+    /// Time of capture from `DateTimeOriginal` tag (or `DateTime` tag
+    /// if the former is absent) with sub-seconds and offset from UTC
+    /// if they are present.
+    fn capture_time(&self) -> Option<DateTime>;
+
+    /// Position from `GPSLatitude`, `GPSLongitude` and `GPSAltitude` tags.
+    fn gps_position(&self) -> Option<GpsPosition>;
+}
+
+impl ExifExt for Exif {
+    fn orientation(&self) -> Option<u16> {
+        let field = self.get_field(Tag::Orientation, In::PRIMARY)?;
+        match field.value.get_uint(0)? {
+            value @ 1..=8 => Some(value as u16),
+            _ => None,
+        }
+    }
+
+    fn capture_time(&self) -> Option<DateTime> {
+        [
+            (
+                Tag::DateTimeOriginal,
+                Tag::SubSecTimeOriginal,
+                Tag::OffsetTimeOriginal,
+            ),
+            (Tag::DateTime, Tag::SubSecTime, Tag::OffsetTime),
+        ]
+        .into_iter()
+        .find_map(|(time_tag, subsec_tag, offset_tag)| {
+            let mut date_time = DateTime::from_ascii(ascii(self, time_tag)?).ok()?;
+            if let Some(subsec) = ascii(self, subsec_tag) {
+                date_time.parse_subsec(subsec).ok();
+            }
+            if let Some(offset) = ascii(self, offset_tag) {
+                date_time.parse_offset(offset).ok();
+            }
+            Some(date_time)
+        })
+    }
+
+    fn gps_position(&self) -> Option<GpsPosition> {
+        let latitude = coordinate(self, Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S')?;
+        let longitude = coordinate(self, Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W')?;
+        let altitude = self
+            .get_field(Tag::GPSAltitude, In::PRIMARY)
+            .and_then(|field| match &field.value {
+                Value::Rational(values) => values.first().map(|v| v.to_f64()),
+                _ => None,
+            })
+            .filter(|altitude| altitude.is_finite())
+            .map(|altitude| {
+                let below_sea_level = self
+                    .get_field(Tag::GPSAltitudeRef, In::PRIMARY)
+                    .and_then(|field| field.value.get_uint(0))
+                    == Some(1);
+                if below_sea_level {
+                    -altitude
+                } else {
+                    altitude
+                }
+            });
+        Some(GpsPosition {
+            latitude,
+            longitude,
+            altitude,
+        })
+    }
+}
+
+fn ascii(exif: &Exif, tag: Tag) -> Option<&[u8]> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values.first().map(|v| v.as_slice()),
+        _ => None,
+    }
+}
+
+/// Returns coordinate in degrees. It is negative if value of
+/// the reference tag starts with `negative_ref`.
+fn coordinate(exif: &Exif, tag: Tag, ref_tag: Tag, negative_ref: u8) -> Option<f64> {
+    let degrees = match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Rational(values) if values.len() >= 3 => {
+            values[0].to_f64() + values[1].to_f64() / 60. + values[2].to_f64() / 3600.
+        }
+        _ => return None,
+    };
+    if !degrees.is_finite() {
+        return None;
+    }
+    if ascii(exif, ref_tag).and_then(|r| r.first()) == Some(&negative_ref) {
+        Some(-degrees)
+    } else {
+        Some(degrees)
+    }
+}
+
+/// Parses data of Exif metadata block which starts
+/// with 4-byte offset of the TIFF header.
+pub(crate) fn parse_exif_block(data: &[u8]) -> Result<Exif> {
+    let tiff = data
+        .get(..4)
+        .and_then(|offset| {
+            let offset = u32::from_be_bytes(offset.try_into().ok()?) as usize;
+            data.get(4usize.checked_add(offset)?..)
+        })
+        .ok_or_else(|| HeifError {
+            code: HeifErrorCode::InvalidInput,
+            sub_code: HeifErrorSubCode::EndOfData,
+            message: "Invalid offset of TIFF header in Exif metadata".to_string(),
+        })?;
+    exif::Reader::new()
+        .read_raw(tiff.to_vec())
+        .map_err(|err| HeifError {
+            code: HeifErrorCode::InvalidInput,
+            sub_code: HeifErrorSubCode::Unspecified,
+            message: format!("Invalid Exif metadata: {}", err),
+        })
+}
@@ -0,0 +1,140 @@
+use crate::image::CHANNELS;
+use crate::{
+    Channel, Chroma, ColorSpace, HeifError, HeifErrorCode, HeifErrorSubCode, Image,
+    MirrorDirection, Result, Rotation,
+};
+
+/// Rectangle geometry.
+///
+/// The region is represented by a left top corner position,
+/// and a size defined by width and height.
+/// All the interior points and the edge are part of the region.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Rectangle {
+    pub left: i32,
+    pub top: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum Transformation {
+    Crop { left: u32, top: u32 },
+    Rotate(Rotation),
+    Mirror(MirrorDirection),
+}
+
+/// Returns horizontal and vertical subsampling factors of the channel.
+fn subsampling(color_space: ColorSpace, channel: Channel) -> (u32, u32) {
+    match (color_space, channel) {
+        (ColorSpace::YCbCr(Chroma::C420), Channel::Cb | Channel::Cr) => (2, 2),
+        (ColorSpace::YCbCr(Chroma::C422), Channel::Cb | Channel::Cr) => (2, 1),
+        _ => (1, 1),
+    }
+}
+
+pub(crate) fn crop_image(src: &Image, rect: Rectangle) -> Result<Image> {
+    let invalid_rect = |message: &str| HeifError {
+        code: HeifErrorCode::UsageError,
+        sub_code: HeifErrorSubCode::InvalidParameterValue,
+        message: message.to_string(),
+    };
+    if rect.left < 0 || rect.top < 0 || rect.width == 0 || rect.height == 0 {
+        return Err(invalid_rect(
+            "Crop rectangle must have non-negative position and non-zero size",
+        ));
+    }
+    let left = rect.left as u32;
+    let top = rect.top as u32;
+    let fits = |pos: u32, size: u32, max: u32| pos.checked_add(size).is_some_and(|end| end <= max);
+    if !fits(left, rect.width, src.width()) || !fits(top, rect.height, src.height()) {
+        return Err(invalid_rect("Crop rectangle is out of the image"));
+    }
+    if let Some(color_space) = src.color_space() {
+        let (sx, sy) = subsampling(color_space, Channel::Cb);
+        if left % sx != 0 || top % sy != 0 {
+            return Err(invalid_rect(
+                "Position of crop rectangle must be aligned to chroma subsampling",
+            ));
+        }
+    }
+    transform_image(
+        src,
+        rect.width,
+        rect.height,
+        Transformation::Crop { left, top },
+    )
+}
+
+pub(crate) fn transform_image(
+    src: &Image,
+    dst_width: u32,
+    dst_height: u32,
+    transformation: Transformation,
+) -> Result<Image> {
+    let color_space = src.color_space().ok_or(HeifError {
+        code: HeifErrorCode::UsageError,
+        sub_code: HeifErrorSubCode::UnsupportedColorConversion,
+        message: "Image has unknown color space".to_string(),
+    })?;
+    let swaps_axes = matches!(
+        transformation,
+        Transformation::Rotate(Rotation::Rotate90Cw | Rotation::Rotate270Cw)
+    );
+    if swaps_axes && color_space == ColorSpace::YCbCr(Chroma::C422) {
+        return Err(HeifError {
+            code: HeifErrorCode::UsageError,
+            sub_code: HeifErrorSubCode::UnsupportedColorConversion,
+            message: "Image with 4:2:2 chroma can't be rotated by 90 or 270 degrees".to_string(),
+        });
+    }
+
+    let mut dst = Image::new(dst_width, dst_height, color_space)?;
+    for channel in CHANNELS {
+        let Some(src_plane) = src.plane(channel) else {
+            continue;
+        };
+        let (sx, sy) = subsampling(color_space, channel);
+        let (plane_width, plane_height) = match transformation {
+            Transformation::Crop { .. } => (dst_width.div_ceil(sx), dst_height.div_ceil(sy)),
+            _ if swaps_axes => (src_plane.height, src_plane.width),
+            _ => (src_plane.width, src_plane.height),
+        };
+        let bit_depth = src.bits_per_pixel(channel).unwrap_or(8);
+        dst.create_plane(channel, plane_width, plane_height, bit_depth)?;
+        let Some(mut dst_plane) = dst.plane_mut(channel) else {
+            continue;
+        };
+
+        let src_width = src_plane.width;
+        let src_height = src_plane.height;
+        let src_pixel = |x: u32, y: u32| -> (u32, u32) {
+            match transformation {
+                Transformation::Crop { left, top } => (x + left / sx, y + top / sy),
+                Transformation::Rotate(Rotation::Rotate90Cw) => (y, src_height - 1 - x),
+                Transformation::Rotate(Rotation::Rotate180) => {
+                    (src_width - 1 - x, src_height - 1 - y)
+                }
+                Transformation::Rotate(Rotation::Rotate270Cw) => (src_width - 1 - y, x),
+                Transformation::Mirror(MirrorDirection::Horizontal) => (src_width - 1 - x, y),
+                Transformation::Mirror(MirrorDirection::Vertical) => (x, src_height - 1 - y),
+            }
+        };
+        let bytes_per_pixel = dst_plane.bytes_per_pixel();
+        for (y, dst_row) in dst_plane.rows_mut().enumerate() {
+            for (x, dst_pixel) in dst_row.chunks_exact_mut(bytes_per_pixel).enumerate() {
+                let (src_x, src_y) = src_pixel(x as u32, y as u32);
+                if let Some(pixel) = src_plane.pixel(src_x, src_y) {
+                    dst_pixel.copy_from_slice(pixel);
+                }
+            }
+        }
+    }
+
+    src.copy_properties_to(&mut dst)?;
+    if swaps_axes {
+        let (aspect_h, aspect_v) = src.pixel_aspect_ratio();
+        dst.set_pixel_aspect_ratio(aspect_v, aspect_h);
+    }
+    Ok(dst)
+}
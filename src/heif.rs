@@ -5,10 +5,14 @@ use std::ptr;
 use libheif_sys as lh;
 
 use crate::decoder::get_decoding_options_ptr;
+use crate::decoder_plugin::register_decoder_plugin;
+use crate::encoder_plugin::register_encoder_plugin;
+use crate::precompressed::is_passthrough_encoder;
 use crate::utils::path_to_cstring;
 use crate::{
-    ColorSpace, CompressionFormat, DecoderDescriptor, DecodingOptions, Encoder, EncoderDescriptor,
-    HeifError, Image, ImageHandle, Result,
+    Channel, ColorSpace, CompressionFormat, DecoderDescriptor, DecoderPlugin, DecodingOptions,
+    Encoder, EncoderDescriptor, EncoderPlugin, HeifError, HeifErrorCode, HeifErrorSubCode, Image,
+    ImageHandle, Result, RgbChroma,
 };
 
 /// Guard structure used for `libheif` initialization, working with plugins,
@@ -93,6 +97,26 @@ impl LibHeif {
         Ok(plugins_loaded as usize)
     }
 
+    /// Register a decoder plugin implemented in Rust.
+    ///
+    /// Registration of the same plugin again does nothing.
+    /// `libheif` unregisters all plugins when it is deinitialized,
+    /// i.e. after all instances of [`LibHeif`] are dropped.
+    pub fn register_decoder_plugin<P: DecoderPlugin>(&self) -> Result<()> {
+        register_decoder_plugin::<P>()
+    }
+
+    /// Register an encoder plugin implemented in Rust.
+    ///
+    /// Registration of the same plugin again does nothing.
+    /// It is an error if another encoder with the same ID
+    /// is already registered.
+    /// `libheif` unregisters all plugins when it is deinitialized,
+    /// i.e. after all instances of [`LibHeif`] are dropped.
+    pub fn register_encoder_plugin<P: EncoderPlugin>(&self) -> Result<()> {
+        register_encoder_plugin::<P>()
+    }
+
     /// Decode an image handle into the actual pixel image and also carry out
     /// all geometric transformations specified in the HEIF file (rotation, cropping, mirroring).
     ///
@@ -103,19 +127,122 @@ impl LibHeif {
         image_handle: &ImageHandle,
         color_space: ColorSpace,
         decoding_options: Option<DecodingOptions>,
+    ) -> Result<Image> {
+        self.decode_image(image_handle, color_space, decoding_options.as_ref())
+    }
+
+    /// Decode an image handle into the caller-provided buffer.
+    ///
+    /// It works like [`LibHeif::decode()`], but pixels of the decoded image
+    /// are written into `buf` row by row, each row starts at a multiple
+    /// of `stride` bytes. Padding at the end of rows is left untouched.
+    /// It allows decoding into pooled or pre-allocated frame buffers.
+    ///
+    /// Only color spaces that store all pixel data in one plane are
+    /// supported: interleaved variants of [`ColorSpace::Rgb`] and
+    /// [`ColorSpace::Monochrome`] (only the `Y` channel is written).
+    ///
+    /// Note: `libheif` can't decode into external memory, so the image
+    /// is decoded into memory allocated by `libheif`, and then pixels
+    /// are copied once into `buf`. The method saves allocation of a buffer
+    /// on the caller's side, but not the copying of pixels.
+    pub fn decode_into(
+        &self,
+        image_handle: &ImageHandle,
+        color_space: ColorSpace,
+        decoding_options: Option<DecodingOptions>,
+        buf: &mut [u8],
+        stride: usize,
+    ) -> Result<()> {
+        let channel = match color_space {
+            ColorSpace::Rgb(chroma) if chroma != RgbChroma::C444 => Channel::Interleaved,
+            ColorSpace::Monochrome => Channel::Y,
+            _ => {
+                return Err(HeifError {
+                    code: HeifErrorCode::UsageError,
+                    sub_code: HeifErrorSubCode::InvalidParameterValue,
+                    message: format!(
+                        "Decoding into a buffer is not supported for {:?} color space",
+                        color_space
+                    ),
+                });
+            }
+        };
+        let image = self.decode_image(image_handle, color_space, decoding_options.as_ref())?;
+        if image.color_space() != Some(color_space) {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::UnsupportedColorConversion,
+                message: "Color space of decoded image is not equal to requested one".to_string(),
+            });
+        }
+        let plane = image.plane(channel).ok_or_else(|| HeifError {
+            code: HeifErrorCode::UsageError,
+            sub_code: HeifErrorSubCode::NonExistingImageChannelReferenced,
+            message: format!("Decoded image has no {:?} channel", channel),
+        })?;
+
+        let row_size = plane.row_size();
+        if row_size > stride {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::InvalidParameterValue,
+                message: format!(
+                    "Stride of buffer ({}) is less than size of row ({})",
+                    stride, row_size
+                ),
+            });
+        }
+        let height = plane.height as usize;
+        let required_size = match height {
+            0 => 0,
+            _ => stride * (height - 1) + row_size,
+        };
+        if buf.len() < required_size {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::InvalidParameterValue,
+                message: format!(
+                    "Size of buffer ({}) is less than required ({})",
+                    buf.len(),
+                    required_size
+                ),
+            });
+        }
+        for (dst_row, src_row) in buf.chunks_mut(stride).zip(plane.rows()) {
+            dst_row[..row_size].copy_from_slice(src_row);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn decode_image(
+        &self,
+        image_handle: &ImageHandle,
+        color_space: ColorSpace,
+        decoding_options: Option<&DecodingOptions>,
     ) -> Result<Image> {
         let mut c_image: *mut lh::heif_image = ptr::null_mut();
+        let lock = image_handle.shared.read();
         let err = unsafe {
             lh::heif_decode_image(
                 image_handle.inner,
                 &mut c_image,
                 color_space.heif_color_space(),
                 color_space.heif_chroma(),
-                get_decoding_options_ptr(&decoding_options),
+                get_decoding_options_ptr(decoding_options),
             )
         };
+        drop(lock);
         HeifError::from_heif_error(err)?;
-        Ok(Image::from_heif_image(c_image))
+        let image = Image::from_heif_image(c_image);
+
+        #[cfg(feature = "lcms2")]
+        if let Some(options) = decoding_options {
+            if let Some(profile) = options.output_color_profile() {
+                return transform_decoded_image(&image, color_space, profile, options);
+            }
+        }
+        Ok(image)
     }
 
     /// Get a list of available decoders.
@@ -155,6 +282,10 @@ impl LibHeif {
     ///
     /// Note: to get the actual encoder from the descriptors returned here,
     /// use [`LibHeif::encoder`] method.
+    ///
+    /// Internal encoders used by
+    /// [`HeifContext::add_precompressed_image()`](crate::HeifContext::add_precompressed_image)
+    /// are not returned.
     pub fn encoder_descriptors(
         &self,
         max_count: usize,
@@ -162,26 +293,36 @@ impl LibHeif {
         name_filter: Option<&str>,
     ) -> Vec<EncoderDescriptor<'_>> {
         let format_filter = format_filter.unwrap_or(CompressionFormat::Undefined);
-        let max_count = max_count.min(libc::c_int::MAX as usize);
         let name_filter = name_filter
             .map(|s| CString::new(s).ok())
             .unwrap_or_default();
         let name_filter_ptr = name_filter.map(|s| s.as_ptr()).unwrap_or(ptr::null());
 
-        let mut descriptors_ptr = Vec::with_capacity(max_count);
+        // All descriptors are requested because some of them are skipped.
+        let count = unsafe {
+            lh::heif_get_encoder_descriptors(
+                format_filter as _,
+                name_filter_ptr,
+                ptr::null_mut(),
+                0,
+            )
+        };
+        let mut descriptors_ptr = Vec::with_capacity(count.max(0) as usize);
         unsafe {
             let count = lh::heif_get_encoder_descriptors(
                 format_filter as _,
                 name_filter_ptr,
                 descriptors_ptr.as_mut_ptr(),
-                max_count as _,
+                count,
             );
-            descriptors_ptr.set_len(count as usize);
+            descriptors_ptr.set_len(count.max(0) as usize);
         }
 
         descriptors_ptr
             .into_iter()
             .filter_map(|d_ptr| unsafe { d_ptr.as_ref().map(EncoderDescriptor::new) })
+            .filter(|descriptor| !is_passthrough_encoder(descriptor))
+            .take(max_count)
             .collect()
     }
 
@@ -201,16 +342,41 @@ impl LibHeif {
     /// If there are several encoder plugins for this format,
     /// the encoder with the highest plugin priority will be returned.
     pub fn encoder_for_format(&self, format: CompressionFormat) -> Result<Encoder<'_>> {
-        let mut c_encoder: *mut lh::heif_encoder = ptr::null_mut();
-        let err = unsafe {
-            lh::heif_context_get_encoder_for_format(
-                ptr::null_mut() as _,
-                format as _,
-                &mut c_encoder,
-            )
-        };
-        HeifError::from_heif_error(err)?;
-        let encoder = Encoder::new(unsafe { &mut *c_encoder })?;
-        Ok(encoder)
+        // `heif_context_get_encoder_for_format()` may return an internal
+        // encoder of pre-compressed data, so the encoder is selected
+        // from the filtered list of descriptors.
+        match self.encoder_descriptors(1, Some(format), None).pop() {
+            Some(descriptor) => self.encoder(descriptor),
+            None => Err(HeifError {
+                code: HeifErrorCode::UnsupportedFileType,
+                sub_code: HeifErrorSubCode::Unspecified,
+                message: format!("No encoder for {:?} compression format", format),
+            }),
+        }
+    }
+}
+
+/// Transform pixels of decoded image into the given color profile
+/// and convert it back into requested color space.
+#[cfg(feature = "lcms2")]
+pub(crate) fn transform_decoded_image(
+    image: &Image,
+    color_space: ColorSpace,
+    profile: &crate::TargetColorProfile,
+    options: &DecodingOptions,
+) -> Result<Image> {
+    let transformed = image.transform_to_profile(profile)?;
+    if color_space == ColorSpace::Undefined || transformed.color_space() == Some(color_space) {
+        return Ok(transformed);
     }
+    let bit_depth = [Channel::Y, Channel::R, Channel::Interleaved]
+        .into_iter()
+        .find_map(|channel| image.bits_per_pixel(channel))
+        .unwrap_or(8);
+    let bit_depth = match color_space {
+        ColorSpace::Rgb(RgbChroma::Rgb | RgbChroma::Rgba) => 8,
+        ColorSpace::Rgb(_) if bit_depth <= 8 => 10,
+        _ => bit_depth,
+    };
+    transformed.convert(color_space, bit_depth, &options.color_conversion_options())
 }
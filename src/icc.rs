@@ -0,0 +1,324 @@
+use four_cc::FourCC;
+
+use crate::{HeifError, HeifErrorCode, HeifErrorSubCode, Result};
+
+/// Signature of ICC profile/device class.
+pub type IccDeviceClass = FourCC;
+
+pub mod icc_device_classes {
+    use super::{FourCC, IccDeviceClass};
+
+    pub const INPUT: IccDeviceClass = FourCC(*b"scnr");
+    pub const DISPLAY: IccDeviceClass = FourCC(*b"mntr");
+    pub const OUTPUT: IccDeviceClass = FourCC(*b"prtr");
+    pub const DEVICE_LINK: IccDeviceClass = FourCC(*b"link");
+    pub const COLOR_SPACE: IccDeviceClass = FourCC(*b"spac");
+    pub const ABSTRACT: IccDeviceClass = FourCC(*b"abst");
+    pub const NAMED_COLOR: IccDeviceClass = FourCC(*b"nmcl");
+}
+
+/// Signature of color space of data or profile connection space.
+pub type IccColorSpace = FourCC;
+
+pub mod icc_color_spaces {
+    use super::{FourCC, IccColorSpace};
+
+    pub const XYZ: IccColorSpace = FourCC(*b"XYZ ");
+    pub const LAB: IccColorSpace = FourCC(*b"Lab ");
+    pub const LUV: IccColorSpace = FourCC(*b"Luv ");
+    pub const YCBCR: IccColorSpace = FourCC(*b"YCbr");
+    pub const YXY: IccColorSpace = FourCC(*b"Yxy ");
+    pub const RGB: IccColorSpace = FourCC(*b"RGB ");
+    pub const GRAY: IccColorSpace = FourCC(*b"GRAY");
+    pub const HSV: IccColorSpace = FourCC(*b"HSV ");
+    pub const HLS: IccColorSpace = FourCC(*b"HLS ");
+    pub const CMYK: IccColorSpace = FourCC(*b"CMYK");
+    pub const CMY: IccColorSpace = FourCC(*b"CMY ");
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, enumn::N)]
+#[repr(C)]
+pub enum IccRenderingIntent {
+    Perceptual = 0,
+    RelativeColorimetric = 1,
+    Saturation = 2,
+    AbsoluteColorimetric = 3,
+    /// This value is used when ICC profile contains unknown value of rendering intent.
+    Unknown,
+}
+
+/// Well-known RGB color profiles.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum WellKnownIccProfile {
+    /// sRGB (IEC 61966-2-1).
+    Srgb,
+    /// Display P3 (DCI-P3 primaries with D65 white point).
+    DisplayP3,
+    /// ITU-R BT.2020.
+    Rec2020,
+}
+
+/// Version of ICC specification used by a profile.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct IccVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub bugfix: u8,
+}
+
+/// Summary of ICC profile parsed from its header and tags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IccProfileInfo {
+    /// Text from `desc` tag.
+    pub description: Option<String>,
+    pub device_class: IccDeviceClass,
+    /// Color space of data.
+    pub color_space: IccColorSpace,
+    /// Profile connection space.
+    pub pcs: IccColorSpace,
+    pub version: IccVersion,
+    pub rendering_intent: IccRenderingIntent,
+    /// Well-known profile detected by RGB colorants or by description.
+    pub well_known: Option<WellKnownIccProfile>,
+}
+
+const HEADER_SIZE: usize = 128;
+
+/// Colorants of well-known profiles (`rXYZ`, `gXYZ` and `bXYZ` tags)
+/// adapted to D50 white point with help of Bradford transform.
+const KNOWN_COLORANTS: [(WellKnownIccProfile, [[f64; 3]; 3]); 3] = [
+    (
+        WellKnownIccProfile::Srgb,
+        [
+            [0.4360, 0.2225, 0.0139],
+            [0.3851, 0.7169, 0.0971],
+            [0.1431, 0.0606, 0.7139],
+        ],
+    ),
+    (
+        WellKnownIccProfile::DisplayP3,
+        [
+            [0.5151, 0.2412, -0.0011],
+            [0.2920, 0.6922, 0.0419],
+            [0.1571, 0.0666, 0.7841],
+        ],
+    ),
+    (
+        WellKnownIccProfile::Rec2020,
+        [
+            [0.6735, 0.2790, -0.0019],
+            [0.1657, 0.6753, 0.0300],
+            [0.1250, 0.0456, 0.7969],
+        ],
+    ),
+];
+const COLORANTS_TOLERANCE: f64 = 0.003;
+
+fn invalid_profile(message: &str) -> HeifError {
+    HeifError {
+        code: HeifErrorCode::InvalidInput,
+        sub_code: HeifErrorSubCode::Unspecified,
+        message: format!("Invalid ICC profile: {}", message),
+    }
+}
+
+fn end_of_data() -> HeifError {
+    HeifError {
+        code: HeifErrorCode::InvalidInput,
+        sub_code: HeifErrorSubCode::EndOfData,
+        message: "Invalid ICC profile: unexpected end of data".to_string(),
+    }
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N]> {
+    data.get(offset..offset.saturating_add(N))
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(end_of_data)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    read_bytes(data, offset).map(u32::from_be_bytes)
+}
+
+fn read_s15_fixed16(data: &[u8], offset: usize) -> Result<f64> {
+    read_bytes(data, offset).map(|bytes| i32::from_be_bytes(bytes) as f64 / 65536.0)
+}
+
+/// Parse header and required tags of ICC profile.
+pub(crate) fn parse_icc_profile(data: &[u8]) -> Result<IccProfileInfo> {
+    if data.len() < HEADER_SIZE {
+        return Err(end_of_data());
+    }
+    if &data[36..40] != b"acsp" {
+        return Err(invalid_profile("missing 'acsp' signature"));
+    }
+    let size = read_u32(data, 0)? as usize;
+    if size < HEADER_SIZE || size > data.len() {
+        return Err(invalid_profile("wrong size of profile"));
+    }
+    let data = &data[..size];
+
+    let version_bytes: [u8; 2] = read_bytes(data, 8)?;
+    let version = IccVersion {
+        major: version_bytes[0],
+        minor: version_bytes[1] >> 4,
+        bugfix: version_bytes[1] & 0x0f,
+    };
+    let rendering_intent = read_u32(data, 64)? & 0xffff;
+    let rendering_intent =
+        IccRenderingIntent::n(rendering_intent).unwrap_or(IccRenderingIntent::Unknown);
+
+    let tags = Tags::new(data)?;
+    let description = tags.description()?;
+    let color_space = FourCC(read_bytes(data, 16)?);
+    let well_known = if color_space == icc_color_spaces::RGB {
+        match tags.colorants()? {
+            Some(colorants) => detect_by_colorants(&colorants),
+            None => description.as_deref().and_then(detect_by_description),
+        }
+    } else {
+        None
+    };
+
+    Ok(IccProfileInfo {
+        description,
+        device_class: FourCC(read_bytes(data, 12)?),
+        color_space,
+        pcs: FourCC(read_bytes(data, 20)?),
+        version,
+        rendering_intent,
+        well_known,
+    })
+}
+
+fn detect_by_colorants(colorants: &[[f64; 3]; 3]) -> Option<WellKnownIccProfile> {
+    KNOWN_COLORANTS
+        .iter()
+        .find(|(_, known)| {
+            known
+                .iter()
+                .flatten()
+                .zip(colorants.iter().flatten())
+                .all(|(a, b)| (a - b).abs() <= COLORANTS_TOLERANCE)
+        })
+        .map(|&(profile, _)| profile)
+}
+
+fn detect_by_description(description: &str) -> Option<WellKnownIccProfile> {
+    let description = description.to_ascii_lowercase();
+    if description.contains("display p3") {
+        Some(WellKnownIccProfile::DisplayP3)
+    } else if description.contains("2020") {
+        Some(WellKnownIccProfile::Rec2020)
+    } else if description.starts_with("srgb") {
+        Some(WellKnownIccProfile::Srgb)
+    } else {
+        None
+    }
+}
+
+/// Table of tags of ICC profile.
+struct Tags<'a> {
+    entries: Vec<(FourCC, &'a [u8])>,
+}
+
+impl<'a> Tags<'a> {
+    fn new(data: &'a [u8]) -> Result<Self> {
+        let count = read_u32(data, HEADER_SIZE)? as usize;
+        // Every entry of the table takes 12 bytes.
+        if count > (data.len() - HEADER_SIZE - 4) / 12 {
+            return Err(end_of_data());
+        }
+        let mut entries = Vec::with_capacity(count);
+        for i in 0..count {
+            let entry_offset = HEADER_SIZE + 4 + i * 12;
+            let signature = FourCC(read_bytes(data, entry_offset)?);
+            let offset = read_u32(data, entry_offset + 4)? as usize;
+            let size = read_u32(data, entry_offset + 8)? as usize;
+            let tag_data = data
+                .get(offset..offset.saturating_add(size))
+                .ok_or_else(end_of_data)?;
+            entries.push((signature, tag_data));
+        }
+        Ok(Self { entries })
+    }
+
+    fn get(&self, signature: &[u8; 4]) -> Option<&'a [u8]> {
+        self.entries
+            .iter()
+            .find(|(sig, _)| sig.0 == *signature)
+            .map(|&(_, tag_data)| tag_data)
+    }
+
+    fn description(&self) -> Result<Option<String>> {
+        let Some(tag_data) = self.get(b"desc") else {
+            return Ok(None);
+        };
+        match &read_bytes::<4>(tag_data, 0)? {
+            b"desc" => {
+                // textDescriptionType of ICC v2
+                let len = read_u32(tag_data, 8)? as usize;
+                let text = tag_data
+                    .get(12..12usize.saturating_add(len))
+                    .ok_or_else(end_of_data)?;
+                let text = text.split(|&c| c == 0).next().unwrap_or_default();
+                Ok(Some(String::from_utf8_lossy(text).into_owned()))
+            }
+            b"mluc" => read_mluc(tag_data),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns XYZ values of red, green and blue colorants.
+    fn colorants(&self) -> Result<Option<[[f64; 3]; 3]>> {
+        let mut colorants = [[0.; 3]; 3];
+        for (colorant, signature) in colorants.iter_mut().zip([b"rXYZ", b"gXYZ", b"bXYZ"]) {
+            let Some(tag_data) = self.get(signature) else {
+                return Ok(None);
+            };
+            if &read_bytes::<4>(tag_data, 0)? != b"XYZ " {
+                return Ok(None);
+            }
+            for (i, value) in colorant.iter_mut().enumerate() {
+                *value = read_s15_fixed16(tag_data, 8 + i * 4)?;
+            }
+        }
+        Ok(Some(colorants))
+    }
+}
+
+/// Read string from multiLocalizedUnicodeType. English string
+/// is preferred, otherwise the first one is used.
+fn read_mluc(tag_data: &[u8]) -> Result<Option<String>> {
+    let count = read_u32(tag_data, 8)? as usize;
+    let record_size = read_u32(tag_data, 12)? as usize;
+    if record_size < 12 {
+        return Err(invalid_profile("wrong size of record in 'mluc' tag"));
+    }
+    let mut selected = None;
+    for i in 0..count {
+        let record_offset = 16usize.saturating_add(i.saturating_mul(record_size));
+        let language: [u8; 2] = read_bytes(tag_data, record_offset)?;
+        let len = read_u32(tag_data, record_offset + 4)? as usize;
+        let offset = read_u32(tag_data, record_offset + 8)? as usize;
+        if selected.is_none() || &language == b"en" {
+            selected = Some((offset, len));
+        }
+        if &language == b"en" {
+            break;
+        }
+    }
+    let Some((offset, len)) = selected else {
+        return Ok(None);
+    };
+    let text = tag_data
+        .get(offset..offset.saturating_add(len))
+        .ok_or_else(end_of_data)?;
+    let utf16: Vec<u16> = text
+        .chunks_exact(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+        .take_while(|&c| c != 0)
+        .collect();
+    Ok(Some(String::from_utf16_lossy(&utf16)))
+}
@@ -1,16 +1,35 @@
 use std::mem::MaybeUninit;
+use std::ops::Range;
 use std::os::raw::c_int;
 use std::{ptr, slice};
 
 use libheif_sys as lh;
 
+#[cfg(feature = "lcms2")]
+use crate::color_management::{transform_image_to_profile, TargetColorProfile};
+use crate::conversion::convert_image;
+use crate::geometry::{crop_image, transform_image, Transformation};
+use crate::scaling::scale_image;
 use crate::{
-    Channel, ColorProfileNCLX, ColorProfileRaw, ColorProfileType, ColorSpace, HeifError,
-    HeifErrorCode, HeifErrorSubCode, Result,
+    Channel, Chroma, ColorConversionOptions, ColorProfileNCLX, ColorProfileRaw, ColorProfileType,
+    ColorProfiles, ColorSpace, HeifError, HeifErrorCode, HeifErrorSubCode, MirrorDirection,
+    Rectangle, Result, RgbChroma, Rotation, ScalingFilter, ScalingOptions,
 };
 
 const MAX_IMAGE_SIZE: u32 = i32::MAX as _;
 
+/// All channels which an image may have.
+pub(crate) const CHANNELS: [Channel; 8] = [
+    Channel::Y,
+    Channel::Cb,
+    Channel::Cr,
+    Channel::R,
+    Channel::G,
+    Channel::B,
+    Channel::Alpha,
+    Channel::Interleaved,
+];
+
 pub struct Plane<T> {
     pub data: T,
     pub width: u32,
@@ -18,6 +37,288 @@ pub struct Plane<T> {
     pub stride: usize,
     pub bits_per_pixel: u8,
     pub storage_bits_per_pixel: u8,
+    /// Byte order of 16-bit components of pixels.
+    pub big_endian: bool,
+}
+
+impl<T: AsRef<[u8]>> Plane<T> {
+    /// Size of a row of pixels in bytes, without padding at the end of the row.
+    pub fn row_size(&self) -> usize {
+        (self.width as usize * self.storage_bits_per_pixel as usize).div_ceil(8)
+    }
+
+    /// Number of bytes used to store one pixel.
+    pub fn bytes_per_pixel(&self) -> usize {
+        (self.storage_bits_per_pixel as usize).div_ceil(8)
+    }
+
+    /// Returns an iterator over rows of pixels.
+    /// Every row has size [`Plane::row_size()`], padding is excluded.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        let row_size = self.row_size();
+        self.data
+            .as_ref()
+            .chunks(self.stride.max(1))
+            .take(self.height as usize)
+            .map(move |row| &row[..row_size.min(row.len())])
+    }
+
+    /// Returns an iterator over rows of pixels with 16-bit components.
+    ///
+    /// Components are read in byte order of the plane,
+    /// e.g. [`RgbChroma::HdrRgbBe`](crate::RgbChroma::HdrRgbBe) planes have
+    /// big-endian components, planes of non-interleaved images have
+    /// components in native byte order.
+    ///
+    /// Returns an error if the plane doesn't store components as 16-bit values.
+    pub fn as_u16_rows(&self) -> Result<impl Iterator<Item = U16Row<'_>>> {
+        self.check_u16_components()?;
+        let big_endian = self.big_endian;
+        Ok(self.rows().map(move |data| U16Row { data, big_endian }))
+    }
+
+    /// Returns bytes of the pixel with given coordinates
+    /// or `None` if coordinates are out of the plane.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<&[u8]> {
+        let range = self.pixel_range(x, y)?;
+        self.data.as_ref().get(range)
+    }
+
+    fn pixel_range(&self, x: u32, y: u32) -> Option<Range<usize>> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let bytes_per_pixel = self.bytes_per_pixel();
+        let start = y as usize * self.stride + x as usize * bytes_per_pixel;
+        Some(start..start + bytes_per_pixel)
+    }
+
+    fn check_u16_components(&self) -> Result<()> {
+        if self.bits_per_pixel <= 8 || self.storage_bits_per_pixel % 16 != 0 {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::UnsupportedBitDepth,
+                message: format!(
+                    "Plane with {} bits per pixel doesn't have 16-bit components",
+                    self.bits_per_pixel
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Plane<T> {
+    /// Returns an iterator over mutable rows of pixels.
+    /// Every row has size [`Plane::row_size()`], padding is excluded.
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u8]> {
+        let row_size = self.row_size();
+        self.data
+            .as_mut()
+            .chunks_mut(self.stride.max(1))
+            .take(self.height as usize)
+            .map(move |row| {
+                let size = row_size.min(row.len());
+                &mut row[..size]
+            })
+    }
+
+    /// Returns an iterator over mutable rows of pixels with 16-bit components.
+    ///
+    /// See [`Plane::as_u16_rows()`] for details.
+    pub fn as_u16_rows_mut(&mut self) -> Result<impl Iterator<Item = U16RowMut<'_>>> {
+        self.check_u16_components()?;
+        let big_endian = self.big_endian;
+        Ok(self
+            .rows_mut()
+            .map(move |data| U16RowMut { data, big_endian }))
+    }
+
+    /// Returns mutable bytes of the pixel with given coordinates
+    /// or `None` if coordinates are out of the plane.
+    pub fn pixel_mut(&mut self, x: u32, y: u32) -> Option<&mut [u8]> {
+        let range = self.pixel_range(x, y)?;
+        self.data.as_mut().get_mut(range)
+    }
+}
+
+/// Row of pixels with 16-bit components.
+#[derive(Debug, Clone, Copy)]
+pub struct U16Row<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl U16Row<'_> {
+    /// Number of components in the row.
+    pub fn len(&self) -> usize {
+        self.data.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the component with given index
+    /// or `None` if the index is out of bounds.
+    pub fn get(&self, index: usize) -> Option<u16> {
+        read_u16(self.data, index, self.big_endian)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        let big_endian = self.big_endian;
+        self.data
+            .chunks_exact(2)
+            .map(move |bytes| u16_from_bytes([bytes[0], bytes[1]], big_endian))
+    }
+
+    pub fn to_vec(&self) -> Vec<u16> {
+        self.iter().collect()
+    }
+}
+
+/// Mutable row of pixels with 16-bit components.
+#[derive(Debug)]
+pub struct U16RowMut<'a> {
+    data: &'a mut [u8],
+    big_endian: bool,
+}
+
+impl U16RowMut<'_> {
+    /// Number of components in the row.
+    pub fn len(&self) -> usize {
+        self.data.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the component with given index
+    /// or `None` if the index is out of bounds.
+    pub fn get(&self, index: usize) -> Option<u16> {
+        read_u16(self.data, index, self.big_endian)
+    }
+
+    /// Set value of the component with given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn set(&mut self, index: usize, value: u16) {
+        let len = self.len();
+        assert!(
+            index < len,
+            "index out of bounds: the len is {} but the index is {}",
+            len,
+            index
+        );
+        let bytes = if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        self.data[index * 2..index * 2 + 2].copy_from_slice(&bytes);
+    }
+
+    /// Copy values of components from the given slice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `src` is not equal to the length of the row.
+    pub fn copy_from_slice(&mut self, src: &[u16]) {
+        assert_eq!(
+            src.len(),
+            self.len(),
+            "source slice length does not match length of the row"
+        );
+        for (i, &value) in src.iter().enumerate() {
+            self.set(i, value);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        let big_endian = self.big_endian;
+        self.data
+            .chunks_exact(2)
+            .map(move |bytes| u16_from_bytes([bytes[0], bytes[1]], big_endian))
+    }
+
+    pub fn to_vec(&self) -> Vec<u16> {
+        self.iter().collect()
+    }
+}
+
+#[inline]
+fn u16_from_bytes(bytes: [u8; 2], big_endian: bool) -> u16 {
+    if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    }
+}
+
+#[inline]
+fn read_u16(data: &[u8], index: usize, big_endian: bool) -> Option<u16> {
+    let bytes = data.get(index * 2..index * 2 + 2)?;
+    Some(u16_from_bytes([bytes[0], bytes[1]], big_endian))
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+}
+
+/// Type of values of components of pixels
+/// which is used to create images from buffers.
+///
+/// It is implemented for `u8` (images with bit depth up to 8)
+/// and `u16` (images with bit depth from 9 to 16).
+pub trait PlaneSample: sealed::Sealed + Copy {
+    #[doc(hidden)]
+    fn supports_bit_depth(bit_depth: u8) -> bool;
+
+    #[doc(hidden)]
+    fn copy_rows<'a>(
+        plane: &mut Plane<&mut [u8]>,
+        rows: impl Iterator<Item = &'a [Self]>,
+    ) -> Result<()>
+    where
+        Self: 'a;
+}
+
+impl PlaneSample for u8 {
+    fn supports_bit_depth(bit_depth: u8) -> bool {
+        (1..=8).contains(&bit_depth)
+    }
+
+    fn copy_rows<'a>(
+        plane: &mut Plane<&mut [u8]>,
+        rows: impl Iterator<Item = &'a [u8]>,
+    ) -> Result<()> {
+        for (dst_row, src_row) in plane.rows_mut().zip(rows) {
+            dst_row.copy_from_slice(src_row);
+        }
+        Ok(())
+    }
+}
+
+impl PlaneSample for u16 {
+    fn supports_bit_depth(bit_depth: u8) -> bool {
+        (9..=16).contains(&bit_depth)
+    }
+
+    fn copy_rows<'a>(
+        plane: &mut Plane<&mut [u8]>,
+        rows: impl Iterator<Item = &'a [u16]>,
+    ) -> Result<()> {
+        for (mut dst_row, src_row) in plane.as_u16_rows_mut()?.zip(rows) {
+            dst_row.copy_from_slice(src_row);
+        }
+        Ok(())
+    }
 }
 
 pub struct Planes<T> {
@@ -35,8 +336,6 @@ pub struct Image {
     pub(crate) inner: *mut lh::heif_image,
 }
 
-pub struct ScalingOptions {}
-
 impl Image {
     /// Create a new image of the specified resolution and colorspace.
     /// Note: no memory for the actual image data is reserved yet. You have to use
@@ -66,11 +365,151 @@ impl Image {
         })
     }
 
+    /// Create a new image from interleaved 8-bit RGB pixels.
+    ///
+    /// `data` must contain `width * height * 3` values without padding
+    /// at the end of rows.
+    pub fn from_rgb8(width: u32, height: u32, data: &[u8]) -> Result<Image> {
+        let mut image = Image::new(width, height, ColorSpace::Rgb(RgbChroma::Rgb))?;
+        image.fill_new_plane(Channel::Interleaved, width, height, 8, 3, data)?;
+        Ok(image)
+    }
+
+    /// Create a new image from interleaved 8-bit RGBA pixels.
+    ///
+    /// `data` must contain `width * height * 4` values without padding
+    /// at the end of rows.
+    pub fn from_rgba8(width: u32, height: u32, data: &[u8]) -> Result<Image> {
+        let mut image = Image::new(width, height, ColorSpace::Rgb(RgbChroma::Rgba))?;
+        image.fill_new_plane(Channel::Interleaved, width, height, 8, 4, data)?;
+        Ok(image)
+    }
+
+    /// Create a new image from interleaved RGB pixels with
+    /// 16-bit components which have `bit_depth` significant bits.
+    ///
+    /// `data` must contain `width * height * 3` values without padding
+    /// at the end of rows.
+    pub fn from_rgb16(width: u32, height: u32, data: &[u16], bit_depth: u8) -> Result<Image> {
+        let mut image = Image::new(width, height, ColorSpace::Rgb(RgbChroma::HdrRgbLe))?;
+        image.fill_new_plane(Channel::Interleaved, width, height, bit_depth, 3, data)?;
+        Ok(image)
+    }
+
+    /// Create a new image from interleaved RGBA pixels with
+    /// 16-bit components which have `bit_depth` significant bits.
+    ///
+    /// `data` must contain `width * height * 4` values without padding
+    /// at the end of rows.
+    pub fn from_rgba16(width: u32, height: u32, data: &[u16], bit_depth: u8) -> Result<Image> {
+        let mut image = Image::new(width, height, ColorSpace::Rgb(RgbChroma::HdrRgbaLe))?;
+        image.fill_new_plane(Channel::Interleaved, width, height, bit_depth, 4, data)?;
+        Ok(image)
+    }
+
+    /// Create a new monochrome image from 8-bit luma values.
+    ///
+    /// `data` must contain `width * height` values without padding
+    /// at the end of rows.
+    pub fn from_luma8(width: u32, height: u32, data: &[u8]) -> Result<Image> {
+        let mut image = Image::new(width, height, ColorSpace::Monochrome)?;
+        image.fill_new_plane(Channel::Y, width, height, 8, 1, data)?;
+        Ok(image)
+    }
+
+    /// Create a new monochrome image from 16-bit luma values
+    /// which have `bit_depth` significant bits.
+    ///
+    /// `data` must contain `width * height` values without padding
+    /// at the end of rows.
+    pub fn from_luma16(width: u32, height: u32, data: &[u16], bit_depth: u8) -> Result<Image> {
+        let mut image = Image::new(width, height, ColorSpace::Monochrome)?;
+        image.fill_new_plane(Channel::Y, width, height, bit_depth, 1, data)?;
+        Ok(image)
+    }
+
+    /// Create a new YCbCr 4:2:0 image from separate planes.
+    ///
+    /// `y` must contain `width * height` values, `u` and `v` must contain
+    /// `((width + 1) / 2) * ((height + 1) / 2)` values.
+    /// Type of values must correspond to `bit_depth`:
+    /// `u8` for 8-bit images and `u16` for images with greater bit depth.
+    pub fn from_yuv420_planes<T: PlaneSample>(
+        width: u32,
+        height: u32,
+        y: &[T],
+        u: &[T],
+        v: &[T],
+        bit_depth: u8,
+    ) -> Result<Image> {
+        let mut image = Image::new(width, height, ColorSpace::YCbCr(Chroma::C420))?;
+        let chroma_width = width.div_ceil(2);
+        let chroma_height = height.div_ceil(2);
+        image.fill_new_plane(Channel::Y, width, height, bit_depth, 1, y)?;
+        image.fill_new_plane(Channel::Cb, chroma_width, chroma_height, bit_depth, 1, u)?;
+        image.fill_new_plane(Channel::Cr, chroma_width, chroma_height, bit_depth, 1, v)?;
+        Ok(image)
+    }
+
+    /// Create a new plane and fill it by values from `data`
+    /// which doesn't have padding at the end of rows.
+    fn fill_new_plane<T: PlaneSample>(
+        &mut self,
+        channel: Channel,
+        width: u32,
+        height: u32,
+        bit_depth: u8,
+        components: usize,
+        data: &[T],
+    ) -> Result<()> {
+        if !T::supports_bit_depth(bit_depth) {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::UnsupportedBitDepth,
+                message: format!(
+                    "Bit depth {} is not supported by {}-bit values",
+                    bit_depth,
+                    size_of::<T>() * 8,
+                ),
+            });
+        }
+        let row_len = width as usize * components;
+        let expected_len = row_len * height as usize;
+        if data.len() != expected_len {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::InvalidParameterValue,
+                message: format!(
+                    "Length of data for {:?} channel ({}) is not equal to expected ({})",
+                    channel,
+                    data.len(),
+                    expected_len
+                ),
+            });
+        }
+        self.create_plane(channel, width, height, bit_depth)?;
+        let mut plane = self.plane_mut(channel).ok_or(HeifError {
+            code: HeifErrorCode::UsageError,
+            sub_code: HeifErrorSubCode::NonExistingImageChannelReferenced,
+            message: format!("Image has no {:?} channel", channel),
+        })?;
+        if row_len > 0 {
+            T::copy_rows(&mut plane, data.chunks_exact(row_len))?;
+        }
+        Ok(())
+    }
+
     #[inline]
     pub(crate) fn from_heif_image(image: *mut lh::heif_image) -> Image {
         Image { inner: image }
     }
 
+    /// Passes ownership of the underlying `heif_image` to the caller.
+    pub(crate) fn into_heif_image(self) -> *mut lh::heif_image {
+        let image = std::mem::ManuallyDrop::new(self);
+        image.inner
+    }
+
     /// Get the width of the main channel (Y in YCbCr, or any in RGB).
     pub fn width(&self) -> u32 {
         unsafe { lh::heif_image_get_primary_width(self.inner).max(0) as u32 }
@@ -135,7 +574,7 @@ impl Image {
         (data, stride as _)
     }
 
-    fn plane(&self, channel: Channel) -> Option<Plane<&[u8]>> {
+    pub(crate) fn plane(&self, channel: Channel) -> Option<Plane<&[u8]>> {
         let (data, stride) = self.plane_inner(channel);
         if data.is_null() {
             return None;
@@ -154,6 +593,7 @@ impl Image {
             width,
             height,
             stride,
+            big_endian: self.is_big_endian(),
         })
     }
 
@@ -172,7 +612,7 @@ impl Image {
     }
 
     #[allow(clippy::mut_from_ref)]
-    fn plane_mut(&self, channel: Channel) -> Option<Plane<&mut [u8]>> {
+    pub(crate) fn plane_mut(&self, channel: Channel) -> Option<Plane<&mut [u8]>> {
         let (data, stride): (*mut u8, usize) = self.plane_mut_inner(channel);
         if data.is_null() {
             return None;
@@ -191,9 +631,19 @@ impl Image {
             width,
             height,
             stride,
+            big_endian: self.is_big_endian(),
         })
     }
 
+    /// Byte order of 16-bit components of pixels in planes.
+    fn is_big_endian(&self) -> bool {
+        match self.color_space() {
+            Some(ColorSpace::Rgb(RgbChroma::HdrRgbBe | RgbChroma::HdrRgbaBe)) => true,
+            Some(ColorSpace::Rgb(RgbChroma::HdrRgbLe | RgbChroma::HdrRgbaLe)) => false,
+            _ => cfg!(target_endian = "big"),
+        }
+    }
+
     pub fn planes(&self) -> Planes<&[u8]> {
         Planes {
             y: self.plane(Channel::Y),
@@ -243,15 +693,24 @@ impl Image {
         }
     }
 
-    /// Scale image by "nearest neighbor" method.
+    /// Scale image to the given size.
     ///
-    /// Note: Currently, `_scaling_options` is not used. Pass a `None`.
+    /// By default, it uses "nearest neighbor" method implemented
+    /// in `libheif`. Other filters may be selected with help of
+    /// [`ScalingOptions::set_filter()`]. These filters resample
+    /// every plane of the image, subsampled chroma planes are scaled
+    /// proportionally. Color channels of interleaved RGBA images without
+    /// premultiplied alpha are premultiplied by alpha while resampling.
     pub fn scale(
         &self,
         width: u32,
         height: u32,
-        _scaling_options: Option<ScalingOptions>,
+        scaling_options: Option<ScalingOptions>,
     ) -> Result<Image> {
+        let filter = scaling_options.map(|o| o.filter()).unwrap_or_default();
+        if filter != ScalingFilter::NearestNeighbor {
+            return scale_image(self, width, height, filter);
+        }
         let mut c_image = MaybeUninit::<_>::uninit();
         let err = unsafe {
             lh::heif_image_scale_image(
@@ -268,6 +727,101 @@ impl Image {
         })
     }
 
+    /// Returns a new image which contains pixels from the given
+    /// rectangle of this image.
+    ///
+    /// Position of the rectangle must be aligned to chroma subsampling,
+    /// e.g. `left` and `top` must be even for YCbCr 4:2:0 images.
+    /// Color profiles, premultiplied alpha flag and pixel aspect ratio
+    /// are copied into the new image.
+    pub fn crop(&self, rect: Rectangle) -> Result<Image> {
+        crop_image(self, rect)
+    }
+
+    /// Returns a new image rotated by the given angle.
+    ///
+    /// Color profiles and premultiplied alpha flag are copied into
+    /// the new image. Pixel aspect ratio is also copied, its
+    /// horizontal and vertical spacing are swapped in case of
+    /// rotation by 90 or 270 degrees.
+    ///
+    /// YCbCr 4:2:2 images can be rotated by 180 degrees only.
+    pub fn rotate(&self, rotation: Rotation) -> Result<Image> {
+        let (width, height) = match rotation {
+            Rotation::Rotate180 => (self.width(), self.height()),
+            Rotation::Rotate90Cw | Rotation::Rotate270Cw => (self.height(), self.width()),
+        };
+        transform_image(self, width, height, Transformation::Rotate(rotation))
+    }
+
+    /// Returns a new image mirrored in the given direction.
+    ///
+    /// Color profiles, premultiplied alpha flag and pixel aspect ratio
+    /// are copied into the new image.
+    pub fn mirror(&self, direction: MirrorDirection) -> Result<Image> {
+        transform_image(
+            self,
+            self.width(),
+            self.height(),
+            Transformation::Mirror(direction),
+        )
+    }
+
+    /// Returns a new image converted into the given color space
+    /// with the given number of bits per component.
+    ///
+    /// Matrix coefficients and range of YCbCr values are taken from
+    /// NCLX color profile of the image (BT.601 with full range is used
+    /// if the image doesn't have NCLX profile). Chroma planes are resampled
+    /// with help of algorithms specified in `options`.
+    /// [`ChromaDownsamplingAlgorithm::SharpYuv`](crate::ChromaDownsamplingAlgorithm::SharpYuv)
+    /// is not supported, [`ChromaDownsamplingAlgorithm::Average`](crate::ChromaDownsamplingAlgorithm::Average)
+    /// is used instead of it.
+    ///
+    /// Interleaved [`RgbChroma::Rgb`] and [`RgbChroma::Rgba`] color spaces
+    /// require `bit_depth` equal to 8, "HDR" interleaved color spaces
+    /// require `bit_depth` from 9 to 16.
+    ///
+    /// Color profiles, premultiplied alpha flag and pixel aspect ratio
+    /// are copied into the new image.
+    pub fn convert(
+        &self,
+        color_space: ColorSpace,
+        bit_depth: u8,
+        options: &ColorConversionOptions,
+    ) -> Result<Image> {
+        convert_image(self, color_space, bit_depth, options)
+    }
+
+    /// Returns a new image with pixels transformed from the color profile
+    /// of this image into the `target` color profile.
+    ///
+    /// Source profile is taken from ICC profile (`prof` or `rICC`) of the image
+    /// or built from NCLX profile. Images without color profiles are
+    /// considered as sRGB images.
+    ///
+    /// The new image has interleaved RGB(A) color space with the same
+    /// bit depth and ICC profile (`prof`) with data of the target profile.
+    #[cfg(feature = "lcms2")]
+    pub fn transform_to_profile(&self, target: &TargetColorProfile) -> Result<Image> {
+        transform_image_to_profile(self, target)
+    }
+
+    /// Copy color profiles, premultiplied alpha flag and pixel aspect ratio
+    /// into another image.
+    pub(crate) fn copy_properties_to(&self, dst: &mut Image) -> Result<()> {
+        if let Some(profile) = self.color_profile_raw() {
+            dst.set_color_profile_raw(&profile)?;
+        }
+        if let Some(profile) = self.color_profile_nclx() {
+            dst.set_color_profile_nclx(&profile)?;
+        }
+        dst.set_premultiplied_alpha(self.is_premultiplied_alpha());
+        let (aspect_h, aspect_v) = self.pixel_aspect_ratio();
+        dst.set_pixel_aspect_ratio(aspect_h, aspect_v);
+        Ok(())
+    }
+
     /// The indicated bit_depth corresponds to the bit depth per channel.
     /// I.e. for interleaved formats like RRGGBB, the bit_depth would be,
     /// e.g., 10 bit instead of 30 bits or 3*16=48 bits.
@@ -355,6 +909,35 @@ impl Image {
         HeifError::from_heif_error(err)
     }
 
+    /// Returns ICC and NCLX color profiles of the image.
+    pub fn color_profiles(&self) -> ColorProfiles {
+        ColorProfiles {
+            icc: self.color_profile_raw(),
+            nclx: self.color_profile_nclx(),
+        }
+    }
+
+    /// Set ICC and NCLX color profiles of the image.
+    ///
+    /// Profiles which are `None` are left unchanged, because `libheif`
+    /// can't remove a color profile from an image. So the result of
+    /// [`Image::color_profiles()`] with a removed profile, passed back
+    /// into this method, doesn't remove the profile from the image.
+    /// To get an image without a profile, copy pixels into a new image.
+    ///
+    /// If the image has both profiles, [`HeifContext::encode_image()`](crate::HeifContext::encode_image)
+    /// writes two `colr` boxes only if it was enabled by
+    /// [`EncodingOptions::set_save_two_colr_boxes_when_icc_and_nclx_available()`](crate::EncodingOptions::set_save_two_colr_boxes_when_icc_and_nclx_available).
+    pub fn set_color_profiles(&mut self, profiles: &ColorProfiles) -> Result<()> {
+        if let Some(icc) = profiles.icc.as_ref() {
+            self.set_color_profile_raw(icc)?;
+        }
+        if let Some(nclx) = profiles.nclx.as_ref() {
+            self.set_color_profile_nclx(nclx)?;
+        }
+        Ok(())
+    }
+
     pub fn pixel_aspect_ratio(&self) -> (u32, u32) {
         let mut aspect_h = 0;
         let mut aspect_v = 0;
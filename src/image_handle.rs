@@ -6,12 +6,16 @@ use std::ptr;
 use four_cc::FourCC;
 use libheif_sys as lh;
 
+use crate::context::ContextShared;
 use crate::utils::cstr_to_str;
 use crate::{
-    ColorProfileNCLX, ColorProfileRaw, ColorProfileType, ColorSpace, HeifError, HeifErrorCode,
-    HeifErrorSubCode, ImageMetadata, Result,
+    ColorProfileNCLX, ColorProfileRaw, ColorProfileType, ColorProfiles, ColorSpace, HeifError,
+    HeifErrorCode, HeifErrorSubCode, ImageMetadata, Result,
 };
 
+#[cfg(feature = "exif")]
+use crate::exif_ext::parse_exif_block;
+
 cfg_if::cfg_if! {
     if #[cfg(feature = "v1_18")] {
         use crate::regions::RegionItem;
@@ -19,21 +23,41 @@ cfg_if::cfg_if! {
     }
 }
 
+cfg_if::cfg_if! {
+    if #[cfg(feature = "v1_19")] {
+        use crate::{CompressedData, CompressionFormat};
+    }
+}
+
 /// Encoded image.
-pub struct ImageHandle {
+///
+/// Handles may be sent to other threads and used there concurrently,
+/// for example, to decode images of a [`HeifContext`](crate::HeifContext)
+/// in parallel. Changes of the context made by its methods are
+/// synchronized with operations of handles.
+pub struct ImageHandle<'a> {
     pub(crate) inner: *mut lh::heif_image_handle,
+    pub(crate) shared: ContextShared<'a>,
 }
 
 pub type ItemId = lh::heif_item_id;
 
-impl ImageHandle {
-    pub(crate) fn new(handle: *mut lh::heif_image_handle) -> Self {
-        ImageHandle { inner: handle }
+impl<'a> ImageHandle<'a> {
+    pub(crate) fn new(handle: *mut lh::heif_image_handle, shared: ContextShared<'a>) -> Self {
+        ImageHandle {
+            inner: handle,
+            shared,
+        }
     }
 
     #[cfg(feature = "v1_18")]
-    fn context(&self) -> HeifContext<'_> {
-        unsafe { HeifContext::from_ptr(lh::heif_image_handle_get_context(self.inner)) }
+    fn context(&self) -> HeifContext<'a> {
+        unsafe {
+            HeifContext::from_ptr(
+                lh::heif_image_handle_get_context(self.inner),
+                self.shared.clone(),
+            )
+        }
     }
 
     pub fn item_id(&self) -> ItemId {
@@ -57,6 +81,7 @@ impl ImageHandle {
     }
 
     pub fn is_primary(&self) -> bool {
+        let _lock = self.shared.read();
         unsafe { lh::heif_image_handle_is_primary_image(self.inner) != 0 }
     }
 
@@ -106,6 +131,17 @@ impl ImageHandle {
         }
     }
 
+    /// Returns an iterator over handles of depth images.
+    ///
+    /// IDs of depth images are read when the iterator is created,
+    /// handles are created lazily. The iterator doesn't allocate memory
+    /// unless the image has more than 16 depth images.
+    pub fn depth_images(&self) -> impl Iterator<Item = Result<Self>> + '_ {
+        let count = self.number_of_depth_images().max(0) as usize;
+        ItemIds::new(count, |ids| self.depth_image_ids(ids))
+            .map(move |item_id| self.depth_image_handle(item_id))
+    }
+
     pub fn depth_image_handle(&self, depth_image_id: ItemId) -> Result<Self> {
         let mut out_depth_handler = MaybeUninit::<_>::uninit();
         let err = unsafe {
@@ -117,14 +153,13 @@ impl ImageHandle {
         };
         HeifError::from_heif_error(err)?;
         let out_depth_handler = unsafe { out_depth_handler.assume_init() };
-        Ok(ImageHandle {
-            inner: out_depth_handler,
-        })
+        Ok(ImageHandle::new(out_depth_handler, self.shared.clone()))
     }
 
     // Thumbnails
 
     pub fn number_of_thumbnails(&self) -> usize {
+        let _lock = self.shared.read();
         unsafe { lh::heif_image_handle_get_number_of_thumbnails(self.inner) as _ }
     }
 
@@ -132,6 +167,7 @@ impl ImageHandle {
         if item_ids.is_empty() {
             0
         } else {
+            let _lock = self.shared.read();
             unsafe {
                 lh::heif_image_handle_get_list_of_thumbnail_IDs(
                     self.inner,
@@ -142,8 +178,19 @@ impl ImageHandle {
         }
     }
 
+    /// Returns an iterator over handles of thumbnails.
+    ///
+    /// IDs of thumbnails are read when the iterator is created,
+    /// handles are created lazily. The iterator doesn't allocate memory
+    /// unless the image has more than 16 thumbnails.
+    pub fn thumbnails(&self) -> impl Iterator<Item = Result<Self>> + '_ {
+        ItemIds::new(self.number_of_thumbnails(), |ids| self.thumbnail_ids(ids))
+            .map(move |item_id| self.thumbnail(item_id))
+    }
+
     pub fn thumbnail(&self, thumbnail_id: ItemId) -> Result<Self> {
         let mut out_thumbnail_handler = MaybeUninit::<_>::uninit();
+        let _lock = self.shared.read();
         let err = unsafe {
             lh::heif_image_handle_get_thumbnail(
                 self.inner,
@@ -153,9 +200,7 @@ impl ImageHandle {
         };
         HeifError::from_heif_error(err)?;
         let out_thumbnail_handler = unsafe { out_thumbnail_handler.assume_init() };
-        Ok(ImageHandle {
-            inner: out_thumbnail_handler,
-        })
+        Ok(ImageHandle::new(out_thumbnail_handler, self.shared.clone()))
     }
 
     // Metadata
@@ -182,6 +227,7 @@ impl ImageHandle {
             Some(s) => s.as_ptr(),
             None => ptr::null(),
         };
+        let _lock = self.shared.read();
         unsafe { lh::heif_image_handle_get_number_of_metadata_blocks(self.inner, filter_ptr) }
     }
 
@@ -197,6 +243,7 @@ impl ImageHandle {
                 Some(s) => s.as_ptr(),
                 None => ptr::null(),
             };
+            let _lock = self.shared.read();
             unsafe {
                 lh::heif_image_handle_get_list_of_metadata_block_IDs(
                     self.inner,
@@ -212,6 +259,7 @@ impl ImageHandle {
     /// Exif data will have the type string "Exif".
     /// This string will be valid until the next call to a libheif function.
     pub fn metadata_type(&self, metadata_id: ItemId) -> Option<&str> {
+        let _lock = self.shared.read();
         let c_type: *const c_char =
             unsafe { lh::heif_image_handle_get_metadata_type(self.inner, metadata_id) };
         cstr_to_str(c_type)
@@ -221,6 +269,7 @@ impl ImageHandle {
     ///
     /// For XMP, the content type is `Some("application/rdf+xml")`.
     pub fn metadata_content_type(&self, metadata_id: ItemId) -> Option<&str> {
+        let _lock = self.shared.read();
         let c_type =
             unsafe { lh::heif_image_handle_get_metadata_content_type(self.inner, metadata_id) };
         cstr_to_str(c_type)
@@ -228,18 +277,21 @@ impl ImageHandle {
 
     /// Get the size of the raw metadata, as stored in the HEIF file.
     pub fn metadata_size(&self, metadata_id: ItemId) -> usize {
+        let _lock = self.shared.read();
         unsafe { lh::heif_image_handle_get_metadata_size(self.inner, metadata_id) }
     }
 
     /// Only valid for item type == "uri ", an absolute URI.
     pub fn metadata_item_uri_type(&self, metadata_id: ItemId) -> Option<&str> {
+        let _lock = self.shared.read();
         let c_type =
             unsafe { lh::heif_image_handle_get_metadata_item_uri_type(self.inner, metadata_id) };
         cstr_to_str(c_type)
     }
 
     pub fn metadata(&self, metadata_id: ItemId) -> Result<Vec<u8>> {
-        let size = self.metadata_size(metadata_id);
+        let _lock = self.shared.read();
+        let size = unsafe { lh::heif_image_handle_get_metadata_size(self.inner, metadata_id) };
         if size == 0 {
             return Err(HeifError {
                 code: HeifErrorCode::UsageError,
@@ -257,6 +309,80 @@ impl ImageHandle {
         Ok(result)
     }
 
+    /// Returns parsed Exif from the first Exif metadata block of the image.
+    ///
+    /// The offset of TIFF header stored before Exif data is handled.
+    /// Use [`ExifExt`](crate::ExifExt) to get commonly used fields.
+    #[cfg(feature = "exif")]
+    pub fn exif(&self) -> Result<Option<exif::Exif>> {
+        let mut item_ids = [0];
+        if self.metadata_block_ids(&mut item_ids, b"Exif") == 0 {
+            return Ok(None);
+        }
+        let data = self.metadata(item_ids[0])?;
+        parse_exif_block(&data).map(Some)
+    }
+
+    /// Returns parsed XMP from the first XMP metadata block of the image.
+    #[cfg(feature = "xmp")]
+    pub fn xmp(&self) -> Result<Option<crate::Xmp>> {
+        let count = self.number_of_metadata_blocks(b"mime").max(0) as usize;
+        let xmp_id = ItemIds::new(count, |ids| self.metadata_block_ids(ids, b"mime"))
+            .find(|&id| self.metadata_content_type(id) == Some("application/rdf+xml"));
+        match xmp_id {
+            Some(id) => crate::Xmp::parse(&self.metadata(id)?).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns an iterator over metadata blocks of the image
+    /// with the given type (`0` means all types).
+    ///
+    /// IDs of metadata blocks are read when the iterator is created,
+    /// data of blocks is read lazily. The iterator doesn't allocate memory
+    /// for IDs unless the image has more than 16 such blocks.
+    pub fn metadata_blocks<T>(
+        &self,
+        type_filter: T,
+    ) -> impl Iterator<Item = Result<(ItemId, ImageMetadata)>> + '_
+    where
+        T: Into<FourCC>,
+    {
+        let type_filter = type_filter.into();
+        let count = self.number_of_metadata_blocks(type_filter).max(0) as usize;
+        ItemIds::new(count, |ids| self.metadata_block_ids(ids, type_filter))
+            .map(move |item_id| Ok((item_id, self.metadata_block(item_id)?)))
+    }
+
+    fn metadata_block(&self, item_id: ItemId) -> Result<ImageMetadata> {
+        let item_type = self
+            .metadata_type(item_id)
+            .filter(|t| t.len() == 4)
+            .map(|t| FourCC::from(t.as_bytes()))
+            .ok_or_else(|| HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::NonExistingItemReferenced,
+                message: format!("Metadata block with ID {} not found", item_id),
+            })?;
+        let raw_data = if self.metadata_size(item_id) == 0 {
+            Vec::new()
+        } else {
+            self.metadata(item_id)?
+        };
+        Ok(ImageMetadata {
+            item_type,
+            content_type: self
+                .metadata_content_type(item_id)
+                .unwrap_or_default()
+                .to_string(),
+            uri_type: self
+                .metadata_item_uri_type(item_id)
+                .unwrap_or_default()
+                .to_string(),
+            raw_data,
+        })
+    }
+
     /// Return vector with all image's metadata items.
     pub fn all_metadata(&self) -> Vec<ImageMetadata> {
         let count = self.number_of_metadata_blocks(0).max(0) as usize;
@@ -357,6 +483,113 @@ impl ImageHandle {
         Some(ColorProfileNCLX { inner: profile_ptr })
     }
 
+    /// Returns ICC and NCLX color profiles stored in `colr` boxes of the image.
+    pub fn color_profiles(&self) -> ColorProfiles {
+        ColorProfiles {
+            icc: self.color_profile_raw(),
+            nclx: self.color_profile_nclx(),
+        }
+    }
+
+    /// Returns the codec configuration and coded bytes of the image
+    /// without decoding it.
+    ///
+    /// Only coded image items are supported, derived images (like grids
+    /// or overlays) return an error. It is also an error if the item
+    /// has no codec configuration box required by its format
+    /// (all supported formats except JPEG).
+    ///
+    /// `libheif` doesn't give access to the codec configuration box,
+    /// so the whole context is serialized into memory to find it.
+    /// It is done once, the parsed properties of items are reused
+    /// by the following calls until the context is modified.
+    #[cfg(feature = "v1_19")]
+    pub fn compressed_data(&self) -> Result<CompressedData> {
+        let context = self.context();
+        let item_id = self.item_id();
+        let item_type = unsafe { lh::heif_item_get_item_type(context.inner, item_id) };
+        // Formats, types of configuration boxes and sub-codes
+        // of errors for items without required configuration box.
+        let (format, config_type, missing_config) = match &item_type.to_be_bytes() {
+            b"hvc1" => (
+                CompressionFormat::Hevc,
+                b"hvcC",
+                Some(HeifErrorSubCode::NoHvccBox),
+            ),
+            b"av01" => (
+                CompressionFormat::Av1,
+                b"av1C",
+                Some(HeifErrorSubCode::NoAv1cBox),
+            ),
+            b"vvc1" => (
+                CompressionFormat::Vvc,
+                b"vvcC",
+                Some(HeifErrorSubCode::NoVvccBox),
+            ),
+            b"avc1" => (
+                CompressionFormat::Avc,
+                b"avcC",
+                Some(HeifErrorSubCode::NoAvccBox),
+            ),
+            b"j2k1" => (
+                CompressionFormat::Jpeg2000,
+                b"j2kH",
+                Some(HeifErrorSubCode::Unspecified),
+            ),
+            b"jpeg" => (CompressionFormat::Jpeg, b"jpgC", None),
+            _ => {
+                return Err(HeifError {
+                    code: HeifErrorCode::UnsupportedFeature,
+                    sub_code: HeifErrorSubCode::UnsupportedCodec,
+                    message: format!(
+                        "Image item with type '{}' has no coded data",
+                        FourCC(item_type.to_be_bytes())
+                    ),
+                })
+            }
+        };
+
+        let mut data_ptr: *mut u8 = ptr::null_mut();
+        let mut size: usize = 0;
+        let lock = self.shared.read();
+        let err = unsafe {
+            lh::heif_item_get_item_data(
+                context.inner,
+                item_id,
+                ptr::null_mut(),
+                &mut data_ptr,
+                &mut size,
+            )
+        };
+        HeifError::from_heif_error(err)?;
+        let data = if data_ptr.is_null() {
+            Vec::new()
+        } else {
+            let data = unsafe { std::slice::from_raw_parts(data_ptr, size) }.to_vec();
+            unsafe { lh::heif_release_item_data(context.inner, &mut data_ptr) };
+            data
+        };
+        drop(lock);
+
+        let properties = context.item_properties()?;
+        let config = match (properties.find(item_id, config_type), missing_config) {
+            (Some(config), _) => config.to_vec(),
+            (None, None) => Vec::new(),
+            (None, Some(sub_code)) => {
+                return Err(HeifError {
+                    code: HeifErrorCode::InvalidInput,
+                    sub_code,
+                    message: format!("Image item has no '{}' box", FourCC(*config_type)),
+                })
+            }
+        };
+        Ok(CompressedData {
+            format,
+            config,
+            data,
+        })
+    }
+
     /// Add a region item to an image.
     ///
     /// The region item is a collection of regions (point, polyline, polygon,
@@ -370,8 +603,9 @@ impl ImageHandle {
         &mut self,
         reference_width: u32,
         reference_height: u32,
-    ) -> Result<RegionItem> {
+    ) -> Result<RegionItem<'a>> {
         let mut lh_region_item_ptr: *mut lh::heif_region_item = ptr::null_mut();
+        let _lock = self.shared.write();
         let err = unsafe {
             lh::heif_image_handle_add_region_item(
                 self.inner,
@@ -386,16 +620,17 @@ impl ImageHandle {
             sub_code: HeifErrorSubCode::Unspecified,
             message: "".to_string(),
         })?;
-        Ok(RegionItem::new(item_ptr))
+        Ok(RegionItem::new(item_ptr, self.shared.clone()))
     }
 
     /// Get the region items attached to the image.
     #[cfg(feature = "v1_18")]
-    pub fn region_items(&self) -> Vec<RegionItem> {
+    pub fn region_items(&self) -> Vec<RegionItem<'a>> {
+        let _lock = self.shared.read();
         let num_items = unsafe { lh::heif_image_handle_get_number_of_region_items(self.inner) };
         let size = num_items.max(0) as usize;
         let mut item_ids: Vec<ItemId> = Vec::with_capacity(size);
-        let mut items: Vec<RegionItem> = Vec::with_capacity(size);
+        let mut items: Vec<RegionItem<'a>> = Vec::with_capacity(size);
         if size > 0 {
             unsafe {
                 lh::heif_image_handle_get_list_of_region_item_ids(
@@ -412,7 +647,7 @@ impl ImageHandle {
                 };
                 if HeifError::from_heif_error(err).is_ok() {
                     if let Some(region_item_ptr) = ptr::NonNull::new(item_ptr) {
-                        items.push(RegionItem::new(region_item_ptr));
+                        items.push(RegionItem::new(region_item_ptr, self.shared.clone()));
                     }
                 }
             }
@@ -421,10 +656,7 @@ impl ImageHandle {
     }
 
     /// Returns the vector of auxiliary image handles assigned to this image handle.
-    pub fn auxiliary_images<T: Into<Option<AuxiliaryImagesFilter>>>(
-        &self,
-        filter: T,
-    ) -> Vec<ImageHandle> {
+    pub fn auxiliary_images<T: Into<Option<AuxiliaryImagesFilter>>>(&self, filter: T) -> Vec<Self> {
         let filter = filter.into().unwrap_or_default();
         let num_items =
             unsafe { lh::heif_image_handle_get_number_of_auxiliary_images(self.inner, filter.0) };
@@ -451,7 +683,7 @@ impl ImageHandle {
                     )
                 };
                 if HeifError::from_heif_error(err).is_ok() && !handle_ptr.is_null() {
-                    image_handles.push(ImageHandle::new(handle_ptr));
+                    image_handles.push(ImageHandle::new(handle_ptr, self.shared.clone()));
                 }
             }
         }
@@ -509,8 +741,75 @@ impl AuxiliaryImagesFilter {
     }
 }
 
-impl Drop for ImageHandle {
+impl Drop for ImageHandle<'_> {
     fn drop(&mut self) {
         unsafe { lh::heif_image_handle_release(self.inner) };
     }
 }
+
+// Methods of the handle read properties of the item which are not changed
+// after creation of the item or hold the lock of the context.
+unsafe impl Send for ImageHandle<'_> {}
+unsafe impl Sync for ImageHandle<'_> {}
+
+/// Maximal number of IDs stored inside [`ItemIds`] without allocation.
+const INLINE_ITEM_IDS: usize = 16;
+
+/// Iterator over IDs of items.
+///
+/// `libheif` returns IDs of items only as a list filled into a buffer,
+/// so IDs are requested at once. Short lists are stored inline,
+/// longer ones are stored in a `Vec`.
+pub(crate) enum ItemIds {
+    Inline {
+        ids: [ItemId; INLINE_ITEM_IDS],
+        pos: usize,
+        len: usize,
+    },
+    Heap(std::vec::IntoIter<ItemId>),
+}
+
+impl ItemIds {
+    /// Returns IDs filled by the given function
+    /// into a buffer with the given size.
+    pub(crate) fn new<F>(count: usize, fill: F) -> Self
+    where
+        F: FnOnce(&mut [ItemId]) -> usize,
+    {
+        if count <= INLINE_ITEM_IDS {
+            let mut ids = [0; INLINE_ITEM_IDS];
+            let len = fill(&mut ids[..count]).min(count);
+            Self::Inline { ids, pos: 0, len }
+        } else {
+            let mut ids = vec![0; count];
+            let len = fill(&mut ids);
+            ids.truncate(len);
+            Self::Heap(ids.into_iter())
+        }
+    }
+}
+
+impl Iterator for ItemIds {
+    type Item = ItemId;
+
+    fn next(&mut self) -> Option<ItemId> {
+        match self {
+            Self::Inline { ids, pos, len } => {
+                let id = ids[..*len].get(*pos).copied()?;
+                *pos += 1;
+                Some(id)
+            }
+            Self::Heap(ids) => ids.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match self {
+            Self::Inline { pos, len, .. } => len - pos,
+            Self::Heap(ids) => ids.len(),
+        };
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for ItemIds {}
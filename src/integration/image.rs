@@ -121,8 +121,7 @@ impl From<HeifError> for ImageError {
 }
 
 struct HeifDecoder<'a> {
-    _context: HeifContext<'a>,
-    image_handle: ImageHandle,
+    image_handle: ImageHandle<'a>,
     color_type: ColorType,
 }
 
@@ -137,7 +136,6 @@ impl<'a> HeifDecoder<'a> {
         let color_type = get_color_type(&image_handle)?;
 
         Ok(Self {
-            _context: context,
             image_handle,
             color_type,
         })
@@ -223,28 +221,9 @@ impl<'a> image::ImageDecoder for HeifDecoder<'a> {
         Self: Sized,
     {
         let color_space = get_color_space(self.color_type);
-        let img = LibHeif::new().decode(&self.image_handle, color_space, None)?;
-        if !matches!(img.color_space(), Some(c) if c == color_space) {
-            return Err(image_error("Color space mismatch."));
-        }
-        let planes = img.planes();
-        let Some(plane) = planes.interleaved else {
-            return Err(image_error("Image is not interleaved."));
-        };
-
-        let row_size = plane.width as usize * (plane.storage_bits_per_pixel / 8) as usize;
-        if row_size > plane.stride {
-            return Err(image_error("Row size is greater than stride."));
-        }
-        let dst_rows = buf.chunks_exact_mut(row_size);
-        let src_rows = plane
-            .data
-            .chunks_exact(plane.stride)
-            .take(plane.height as usize)
-            .map(|row| &row[..row_size]);
-        for (dst_row, src_row) in dst_rows.zip(src_rows) {
-            dst_row.copy_from_slice(src_row);
-        }
+        let row_size =
+            self.image_handle.width() as usize * self.color_type.bytes_per_pixel() as usize;
+        LibHeif::new().decode_into(&self.image_handle, color_space, None, buf, row_size)?;
         Ok(())
     }
 
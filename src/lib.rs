@@ -4,39 +4,78 @@
 #![doc = include_str!("../README.md")]
 extern crate core;
 
+#[cfg(feature = "lcms2")]
+pub use color_management::TargetColorProfile;
 pub use color_profile::*;
 pub use context::HeifContext;
 pub use decoder::*;
+pub use decoder_plugin::DecoderPlugin;
 pub use encoder::*;
+pub use encoder_config::*;
+pub use encoder_plugin::EncoderPlugin;
 pub use enums::*;
 pub use errors::{HeifError, HeifErrorCode, HeifErrorSubCode, Result};
+#[cfg(feature = "exif")]
+pub use exif;
+#[cfg(feature = "exif")]
+pub use exif_ext::{ExifExt, GpsPosition};
+pub use geometry::Rectangle;
 pub use heif::*;
+pub use icc::*;
 pub use image::*;
 pub use image_handle::{AuxiliaryImagesFilter, ImageHandle, ItemId};
 pub use metadata::ImageMetadata;
+pub use precompressed::CompressedData;
+#[cfg(feature = "v1_19")]
+pub use progress::CancellationToken;
 pub use reader::{Reader, StreamReader};
+pub use scaling::{ScalingFilter, ScalingOptions};
 #[cfg(feature = "v1_19")]
 pub use security_limits::*;
 #[cfg(feature = "v1_20")]
 pub use track::*;
 pub use utils::check_file_type;
+#[cfg(feature = "xmp")]
+pub use xmp::*;
+mod bmff;
+#[cfg(feature = "lcms2")]
+mod color_management;
 mod color_profile;
+#[cfg(doctest)]
+mod compile_fail_tests;
 mod context;
+mod conversion;
 mod decoder;
+mod decoder_plugin;
+mod editing;
 mod encoder;
+mod encoder_config;
+mod encoder_plugin;
 mod enums;
 mod errors;
+#[cfg(feature = "exif")]
+mod exif_ext;
+mod geometry;
 mod heif;
+mod icc;
 mod image;
 mod image_handle;
 #[cfg(feature = "image")]
 pub mod integration;
 mod metadata;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod plugin_api;
+mod precompressed;
+mod progress;
 mod reader;
 #[cfg(feature = "v1_18")]
 pub mod regions;
+mod scaling;
 #[cfg(feature = "v1_19")]
 mod security_limits;
 #[cfg(feature = "v1_20")]
 mod track;
 mod utils;
+#[cfg(feature = "xmp")]
+mod xmp;
@@ -16,6 +16,7 @@ pub struct ImageMetadata {
     ///
     /// For Exif data, you probably have to skip the first four bytes of
     /// the data, since they indicate the offset to the start of
-    /// the TIFF header of the Exif data.    
+    /// the TIFF header of the Exif data. Or use `ImageHandle::exif()`
+    /// with the `exif` feature to get parsed Exif.
     pub raw_data: Vec<u8>,
 }
@@ -0,0 +1,275 @@
+#[cfg(feature = "v1_19")]
+use std::ptr;
+
+#[cfg(feature = "v1_19")]
+use libheif_sys as lh;
+use rayon::prelude::*;
+
+#[cfg(feature = "v1_19")]
+use crate::decoder::get_decoding_options_ptr;
+#[cfg(feature = "v1_19")]
+use crate::image::CHANNELS;
+use crate::progress::ProgressCallbacks;
+#[cfg(feature = "v1_19")]
+use crate::{Channel, Chroma, HeifError, HeifErrorCode, HeifErrorSubCode, Plane};
+use crate::{
+    ColorSpace, DecodingOptions, HeifContext, Image, ImageHandle, LibHeif, ProgressStep, Result,
+};
+
+impl HeifContext<'_> {
+    /// Decode all top level images of the context in parallel.
+    ///
+    /// Images are decoded on the current thread pool of `rayon`,
+    /// so call this method inside [`rayon::ThreadPool::install()`]
+    /// to limit the number of threads used to decode images.
+    /// Results are returned in the order of
+    /// [`HeifContext::top_level_images()`].
+    ///
+    /// The progress handler of `decoding_options` receives progress of
+    /// [`ProgressStep::Total`] step, one unit for every decoded image.
+    pub fn decode_all(
+        &self,
+        color_space: ColorSpace,
+        decoding_options: Option<DecodingOptions>,
+    ) -> Vec<Result<Image>> {
+        let lib_heif = LibHeif::new();
+        let (options, callbacks) = split_progress_handler(decoding_options);
+        let handles: Vec<Result<ImageHandle>> = self.top_level_images().collect();
+
+        callbacks.start(ProgressStep::Total, handles.len() as u32);
+        let images = handles
+            .into_par_iter()
+            .map(|handle| {
+                #[cfg(feature = "v1_19")]
+                callbacks.check_cancellation()?;
+                let image = lib_heif.decode_image(&handle?, color_space, options.as_ref())?;
+                callbacks.advance(ProgressStep::Total);
+                Ok(image)
+            })
+            .collect();
+        callbacks.end(ProgressStep::Total);
+        images
+    }
+}
+
+#[cfg(feature = "v1_19")]
+impl LibHeif {
+    /// Decode an image like [`LibHeif::decode()`], but tiles of grid
+    /// images are decoded in parallel.
+    ///
+    /// Tiles are decoded on the current thread pool of `rayon`,
+    /// so call this method inside [`rayon::ThreadPool::install()`]
+    /// to limit the number of threads used to decode the image.
+    /// Images without tiles are decoded with [`LibHeif::decode()`].
+    ///
+    /// The progress handler of `decoding_options` receives progress of
+    /// [`ProgressStep::Total`] step, one unit for every decoded tile.
+    pub fn decode_tiles_parallel(
+        &self,
+        image_handle: &ImageHandle,
+        color_space: ColorSpace,
+        decoding_options: Option<DecodingOptions>,
+    ) -> Result<Image> {
+        let process_transformations = !decoding_options
+            .as_ref()
+            .is_some_and(|o| o.ignore_transformations());
+        let tiling = image_tiling(image_handle, process_transformations)?;
+        let columns = tiling.num_columns;
+        let num_tiles = columns as usize * tiling.num_rows as usize;
+        if num_tiles <= 1 || tiling.number_of_extra_dimensions > 0 {
+            return self.decode(image_handle, color_space, decoding_options);
+        }
+
+        let (options, callbacks) = split_progress_handler(decoding_options);
+        callbacks.start(ProgressStep::Total, num_tiles as u32);
+        let tiles: Vec<Image> = (0..num_tiles as u32)
+            .into_par_iter()
+            .map(|index| {
+                callbacks.check_cancellation()?;
+                let tile = decode_tile(
+                    image_handle,
+                    color_space,
+                    options.as_ref(),
+                    index % columns,
+                    index / columns,
+                )?;
+                callbacks.advance(ProgressStep::Total);
+                Ok(tile)
+            })
+            .collect::<Result<_>>()?;
+        let image = compose_tiles(image_handle, &tiling, &tiles)?;
+        callbacks.end(ProgressStep::Total);
+
+        #[cfg(feature = "lcms2")]
+        if let Some(options) = options.as_ref() {
+            if let Some(profile) = options.output_color_profile() {
+                return crate::heif::transform_decoded_image(&image, color_space, profile, options);
+            }
+        }
+        Ok(image)
+    }
+}
+
+/// Moves the progress handler out of decoding options, so that
+/// `libheif` doesn't report progress of decoding of separate images.
+fn split_progress_handler(
+    decoding_options: Option<DecodingOptions>,
+) -> (Option<DecodingOptions>, ProgressCallbacks) {
+    let mut callbacks = ProgressCallbacks::default();
+    let options = decoding_options.map(|mut options| {
+        callbacks.set_progress_handler(options.take_progress_handler());
+        #[cfg(feature = "v1_19")]
+        callbacks.set_cancellation_token(options.cancellation_token().cloned());
+        options
+    });
+    (options, callbacks)
+}
+
+#[cfg(feature = "v1_19")]
+fn image_tiling(
+    image_handle: &ImageHandle,
+    process_transformations: bool,
+) -> Result<lh::heif_image_tiling> {
+    let mut tiling = lh::heif_image_tiling {
+        version: 1,
+        num_columns: 0,
+        num_rows: 0,
+        tile_width: 0,
+        tile_height: 0,
+        image_width: 0,
+        image_height: 0,
+        top_offset: 0,
+        left_offset: 0,
+        number_of_extra_dimensions: 0,
+        extra_dimension_size: [0; 8],
+    };
+    let err = unsafe {
+        lh::heif_image_handle_get_image_tiling(
+            image_handle.inner,
+            process_transformations as _,
+            &mut tiling,
+        )
+    };
+    HeifError::from_heif_error(err)?;
+    Ok(tiling)
+}
+
+#[cfg(feature = "v1_19")]
+fn decode_tile(
+    image_handle: &ImageHandle,
+    color_space: ColorSpace,
+    decoding_options: Option<&DecodingOptions>,
+    tile_x: u32,
+    tile_y: u32,
+) -> Result<Image> {
+    let mut c_image: *mut lh::heif_image = ptr::null_mut();
+    let lock = image_handle.shared.read();
+    let err = unsafe {
+        lh::heif_image_handle_decode_image_tile(
+            image_handle.inner,
+            &mut c_image,
+            color_space.heif_color_space(),
+            color_space.heif_chroma(),
+            get_decoding_options_ptr(decoding_options),
+            tile_x,
+            tile_y,
+        )
+    };
+    drop(lock);
+    HeifError::from_heif_error(err)?;
+    Ok(Image::from_heif_image(c_image))
+}
+
+/// Creates an image of the whole grid and copies decoded tiles into it.
+#[cfg(feature = "v1_19")]
+fn compose_tiles(
+    image_handle: &ImageHandle,
+    tiling: &lh::heif_image_tiling,
+    tiles: &[Image],
+) -> Result<Image> {
+    let first_tile = &tiles[0];
+    let color_space = first_tile.color_space().ok_or_else(|| HeifError {
+        code: HeifErrorCode::DecoderPluginError,
+        sub_code: HeifErrorSubCode::Unspecified,
+        message: "Decoded tile has unknown color space".to_string(),
+    })?;
+    let (chroma_x, chroma_y) = match color_space {
+        ColorSpace::YCbCr(Chroma::C420) => (2, 2),
+        ColorSpace::YCbCr(Chroma::C422) => (2, 1),
+        _ => (1, 1),
+    };
+
+    let mut image = Image::new(tiling.image_width, tiling.image_height, color_space)?;
+    for channel in CHANNELS {
+        let Some(bit_depth) = first_tile
+            .has_channel(channel)
+            .then(|| first_tile.bits_per_pixel(channel))
+            .flatten()
+        else {
+            continue;
+        };
+        let (sub_x, sub_y) = match channel {
+            Channel::Cb | Channel::Cr => (chroma_x, chroma_y),
+            _ => (1, 1),
+        };
+        image.create_plane(
+            channel,
+            tiling.image_width.div_ceil(sub_x),
+            tiling.image_height.div_ceil(sub_y),
+            bit_depth,
+        )?;
+        let Some(mut dst) = image.plane_mut(channel) else {
+            continue;
+        };
+        for (index, tile) in (0..).zip(tiles) {
+            let src = tile.plane(channel).ok_or_else(|| HeifError {
+                code: HeifErrorCode::DecoderPluginError,
+                sub_code: HeifErrorSubCode::NonExistingImageChannelReferenced,
+                message: format!("Decoded tile has no {:?} channel", channel),
+            })?;
+            let x = i64::from(index % tiling.num_columns) * i64::from(tiling.tile_width)
+                - i64::from(tiling.left_offset);
+            let y = i64::from(index / tiling.num_columns) * i64::from(tiling.tile_height)
+                - i64::from(tiling.top_offset);
+            copy_tile(
+                &src,
+                &mut dst,
+                x.div_euclid(sub_x.into()),
+                y.div_euclid(sub_y.into()),
+            );
+        }
+    }
+
+    first_tile.copy_properties_to(&mut image)?;
+    if let Some(profile) = image_handle.color_profile_raw() {
+        image.set_color_profile_raw(&profile)?;
+    }
+    if let Some(profile) = image_handle.color_profile_nclx() {
+        image.set_color_profile_nclx(&profile)?;
+    }
+    Ok(image)
+}
+
+/// Copies pixels of the tile into the plane of the whole image
+/// at the given position. Pixels outside the image are skipped.
+#[cfg(feature = "v1_19")]
+fn copy_tile(src: &Plane<&[u8]>, dst: &mut Plane<&mut [u8]>, x: i64, y: i64) {
+    let bytes_per_pixel = dst.bytes_per_pixel();
+    let left = x.max(0);
+    let top = y.max(0);
+    let right = (x + i64::from(src.width)).min(i64::from(dst.width));
+    let bottom = (y + i64::from(src.height)).min(i64::from(dst.height));
+    if left >= right || top >= bottom {
+        return;
+    }
+
+    let row_size = (right - left) as usize * bytes_per_pixel;
+    let src_offset = (left - x) as usize * bytes_per_pixel;
+    let dst_offset = left as usize * bytes_per_pixel;
+    for row in top..bottom {
+        let src_start = (row - y) as usize * src.stride + src_offset;
+        let dst_start = row as usize * dst.stride + dst_offset;
+        dst.data[dst_start..dst_start + row_size]
+            .copy_from_slice(&src.data[src_start..src_start + row_size]);
+    }
+}
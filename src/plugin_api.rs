@@ -0,0 +1,343 @@
+//! Layouts of structures from `heif_plugin.h`.
+//!
+//! `libheif-sys` declares these structures as opaque,
+//! so they are defined here to register plugins written in Rust.
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::panic::{self, AssertUnwindSafe};
+
+use libheif_sys as lh;
+
+use crate::{HeifError, HeifErrorCode, HeifErrorSubCode, Result};
+
+/// Version of `heif_encoder_plugin` structure with fields defined below.
+pub(crate) const ENCODER_PLUGIN_API_VERSION: c_int = 3;
+
+/// Value of `heif_image_input_class_normal`.
+pub(crate) const IMAGE_INPUT_CLASS_NORMAL: c_uint = 1;
+
+/// `heif_encoder_plugin` structure with fields up to API version 3.
+#[repr(C)]
+pub(crate) struct HeifEncoderPlugin {
+    pub plugin_api_version: c_int,
+    pub compression_format: lh::heif_compression_format,
+    pub id_name: *const c_char,
+    pub priority: c_int,
+    pub supports_lossy_compression: c_int,
+    pub supports_lossless_compression: c_int,
+    pub get_plugin_name: Option<unsafe extern "C" fn() -> *const c_char>,
+    pub init_plugin: Option<unsafe extern "C" fn()>,
+    pub cleanup_plugin: Option<unsafe extern "C" fn()>,
+    pub new_encoder: Option<unsafe extern "C" fn(encoder: *mut *mut c_void) -> lh::heif_error>,
+    pub free_encoder: Option<unsafe extern "C" fn(encoder: *mut c_void)>,
+    pub set_parameter_quality:
+        Option<unsafe extern "C" fn(encoder: *mut c_void, quality: c_int) -> lh::heif_error>,
+    pub get_parameter_quality:
+        Option<unsafe extern "C" fn(encoder: *mut c_void, quality: *mut c_int) -> lh::heif_error>,
+    pub set_parameter_lossless:
+        Option<unsafe extern "C" fn(encoder: *mut c_void, lossless: c_int) -> lh::heif_error>,
+    pub get_parameter_lossless:
+        Option<unsafe extern "C" fn(encoder: *mut c_void, lossless: *mut c_int) -> lh::heif_error>,
+    pub set_parameter_logging_level:
+        Option<unsafe extern "C" fn(encoder: *mut c_void, logging: c_int) -> lh::heif_error>,
+    pub get_parameter_logging_level:
+        Option<unsafe extern "C" fn(encoder: *mut c_void, logging: *mut c_int) -> lh::heif_error>,
+    pub list_parameters: Option<
+        unsafe extern "C" fn(encoder: *mut c_void) -> *const *const lh::heif_encoder_parameter,
+    >,
+    pub set_parameter_integer: Option<
+        unsafe extern "C" fn(
+            encoder: *mut c_void,
+            name: *const c_char,
+            value: c_int,
+        ) -> lh::heif_error,
+    >,
+    pub get_parameter_integer: Option<
+        unsafe extern "C" fn(
+            encoder: *mut c_void,
+            name: *const c_char,
+            value: *mut c_int,
+        ) -> lh::heif_error,
+    >,
+    pub set_parameter_boolean: Option<
+        unsafe extern "C" fn(
+            encoder: *mut c_void,
+            name: *const c_char,
+            value: c_int,
+        ) -> lh::heif_error,
+    >,
+    pub get_parameter_boolean: Option<
+        unsafe extern "C" fn(
+            encoder: *mut c_void,
+            name: *const c_char,
+            value: *mut c_int,
+        ) -> lh::heif_error,
+    >,
+    pub set_parameter_string: Option<
+        unsafe extern "C" fn(
+            encoder: *mut c_void,
+            name: *const c_char,
+            value: *const c_char,
+        ) -> lh::heif_error,
+    >,
+    pub get_parameter_string: Option<
+        unsafe extern "C" fn(
+            encoder: *mut c_void,
+            name: *const c_char,
+            value: *mut c_char,
+            value_size: c_int,
+        ) -> lh::heif_error,
+    >,
+    pub query_input_colorspace: Option<
+        unsafe extern "C" fn(colorspace: *mut lh::heif_colorspace, chroma: *mut lh::heif_chroma),
+    >,
+    pub encode_image: Option<
+        unsafe extern "C" fn(
+            encoder: *mut c_void,
+            image: *const lh::heif_image,
+            image_class: c_uint,
+        ) -> lh::heif_error,
+    >,
+    pub get_compressed_data: Option<
+        unsafe extern "C" fn(
+            encoder: *mut c_void,
+            data: *mut *mut u8,
+            size: *mut c_int,
+            data_type: *mut c_uint,
+        ) -> lh::heif_error,
+    >,
+    // --- version 2 ---
+    pub query_input_colorspace2: Option<
+        unsafe extern "C" fn(
+            encoder: *mut c_void,
+            colorspace: *mut lh::heif_colorspace,
+            chroma: *mut lh::heif_chroma,
+        ),
+    >,
+    // --- version 3 ---
+    pub query_encoded_size: Option<
+        unsafe extern "C" fn(
+            encoder: *mut c_void,
+            input_width: u32,
+            input_height: u32,
+            encoded_width: *mut u32,
+            encoded_height: *mut u32,
+        ),
+    >,
+}
+
+/// `heif_encoder_parameter` structure of version 2.
+#[repr(C)]
+pub(crate) struct HeifEncoderParameter {
+    pub version: c_int,
+    pub name: *const c_char,
+    pub parameter_type: lh::heif_encoder_parameter_type,
+    pub values: HeifEncoderParameterValues,
+    // --- version 2 ---
+    pub has_default: c_int,
+}
+
+#[repr(C)]
+pub(crate) union HeifEncoderParameterValues {
+    pub integer: HeifEncoderIntegerValues,
+    pub string: HeifEncoderStringValues,
+    pub boolean: HeifEncoderBooleanValues,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct HeifEncoderIntegerValues {
+    pub default_value: c_int,
+    pub have_minimum_maximum: u8,
+    pub minimum: c_int,
+    pub maximum: c_int,
+    pub valid_values: *const c_int,
+    pub num_valid_values: c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct HeifEncoderStringValues {
+    pub default_value: *const c_char,
+    /// Null-terminated array of strings.
+    pub valid_values: *const *const c_char,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct HeifEncoderBooleanValues {
+    pub default_value: c_int,
+}
+
+/// Returns the latest version of `heif_decoder_plugin` structure
+/// supported by the linked `libheif`.
+///
+/// `libheif` rejects plugins with newer version than it supports,
+/// and the latest versions of `libheif` can decode only with help
+/// of plugins of version 5 or newer.
+pub(crate) fn decoder_plugin_api_version() -> c_int {
+    // Numeric version of libheif, encoded as 0xHHMMLL00.
+    let version = unsafe { lh::heif_get_version_number() };
+    match version >> 16 {
+        0x0115.. => 5,
+        0x0114 => 4,
+        _ => 3,
+    }
+}
+
+/// `heif_decoder_plugin_compressed_format_description` structure.
+#[repr(C)]
+pub(crate) struct HeifDecoderPluginFormat {
+    pub format: lh::heif_compression_format,
+}
+
+/// `heif_decoder_plugin_options` structure.
+#[repr(C)]
+pub(crate) struct HeifDecoderPluginOptions {
+    pub format: lh::heif_compression_format,
+    pub strict_decoding: c_int,
+    pub num_threads: c_int,
+    // --- version 6 ---
+    pub limits: *const c_void,
+}
+
+/// `heif_decoder_plugin` structure with fields up to API version 5.
+///
+/// Pointers to `heif_security_limits` are declared as `c_void`,
+/// because plugins written in Rust don't use them.
+#[repr(C)]
+pub(crate) struct HeifDecoderPlugin {
+    pub plugin_api_version: c_int,
+    pub get_plugin_name: Option<unsafe extern "C" fn() -> *const c_char>,
+    pub init_plugin: Option<unsafe extern "C" fn()>,
+    pub deinit_plugin: Option<unsafe extern "C" fn()>,
+    pub does_support_format:
+        Option<unsafe extern "C" fn(format: lh::heif_compression_format) -> c_int>,
+    pub new_decoder: Option<unsafe extern "C" fn(decoder: *mut *mut c_void) -> lh::heif_error>,
+    pub free_decoder: Option<unsafe extern "C" fn(decoder: *mut c_void)>,
+    pub push_data: Option<
+        unsafe extern "C" fn(
+            decoder: *mut c_void,
+            data: *const c_void,
+            size: usize,
+        ) -> lh::heif_error,
+    >,
+    pub decode_image: Option<
+        unsafe extern "C" fn(
+            decoder: *mut c_void,
+            out_img: *mut *mut lh::heif_image,
+        ) -> lh::heif_error,
+    >,
+    // --- version 2 ---
+    pub set_strict_decoding: Option<unsafe extern "C" fn(decoder: *mut c_void, flag: c_int)>,
+    // --- version 3 ---
+    pub id_name: *const c_char,
+    // --- version 4 ---
+    pub decode_next_image: Option<
+        unsafe extern "C" fn(
+            decoder: *mut c_void,
+            out_img: *mut *mut lh::heif_image,
+            limits: *const c_void,
+        ) -> lh::heif_error,
+    >,
+    // --- version 5 ---
+    pub minimum_required_libheif_version: u32,
+    pub does_support_format2:
+        Option<unsafe extern "C" fn(format: *const HeifDecoderPluginFormat) -> c_int>,
+    pub new_decoder2: Option<
+        unsafe extern "C" fn(
+            decoder: *mut *mut c_void,
+            options: *const HeifDecoderPluginOptions,
+        ) -> lh::heif_error,
+    >,
+    pub push_data2: Option<
+        unsafe extern "C" fn(
+            decoder: *mut c_void,
+            data: *const c_void,
+            size: usize,
+            user_data: usize,
+        ) -> lh::heif_error,
+    >,
+    pub flush_data: Option<unsafe extern "C" fn(decoder: *mut c_void) -> lh::heif_error>,
+    pub decode_next_image2: Option<
+        unsafe extern "C" fn(
+            decoder: *mut c_void,
+            out_img: *mut *mut lh::heif_image,
+            out_user_data: *mut usize,
+            limits: *const c_void,
+        ) -> lh::heif_error,
+    >,
+}
+
+/// Wrapper to store plugin structures in static variables.
+pub(crate) struct StaticData<T>(pub T);
+
+// Plugin structures contain only pointers to static data and functions.
+unsafe impl<T> Sync for StaticData<T> {}
+
+pub(crate) const fn heif_error(
+    code: lh::heif_error_code,
+    subcode: lh::heif_suberror_code,
+    message: &'static CStr,
+) -> lh::heif_error {
+    lh::heif_error {
+        code,
+        subcode,
+        message: message.as_ptr(),
+    }
+}
+
+pub(crate) const HEIF_ERROR_OK: lh::heif_error = heif_error(
+    lh::heif_error_code_heif_error_Ok,
+    lh::heif_suberror_code_heif_suberror_Unspecified,
+    c"Success",
+);
+
+pub(crate) const HEIF_ERROR_UNSUPPORTED_PARAMETER: lh::heif_error = heif_error(
+    lh::heif_error_code_heif_error_Usage_error,
+    lh::heif_suberror_code_heif_suberror_Unsupported_parameter,
+    c"Unsupported encoder parameter",
+);
+
+thread_local! {
+    /// Message of the last error returned by a plugin function on this thread.
+    /// `libheif` copies messages of errors right after a call of the function.
+    static ERROR_MESSAGE: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Calls a function implemented by a plugin and converts its result
+/// into `heif_error`. Panics are converted into errors with `default_code`,
+/// because they must not unwind into `libheif`.
+pub(crate) fn call_plugin(
+    default_code: HeifErrorCode,
+    f: impl FnOnce() -> Result<()>,
+) -> lh::heif_error {
+    let err = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return HEIF_ERROR_OK,
+        Ok(Err(err)) => err,
+        Err(_) => HeifError {
+            code: default_code,
+            sub_code: HeifErrorSubCode::Unspecified,
+            message: "Plugin panicked".to_string(),
+        },
+    };
+    let code = match err.code {
+        HeifErrorCode::ContextCreateFailed | HeifErrorCode::Unknown => default_code,
+        code => code,
+    };
+    let sub_code = match err.sub_code {
+        HeifErrorSubCode::Unknown => HeifErrorSubCode::Unspecified,
+        sub_code => sub_code,
+    };
+    let message = CString::new(err.message.replace('\0', "")).unwrap_or_default();
+    ERROR_MESSAGE.with(|last_message| {
+        let mut last_message = last_message.borrow_mut();
+        *last_message = message;
+        lh::heif_error {
+            code: code as _,
+            subcode: sub_code as _,
+            message: last_message.as_ptr(),
+        }
+    })
+}
@@ -0,0 +1,586 @@
+#[cfg(feature = "v1_19")]
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use libheif_sys as lh;
+
+#[cfg(feature = "v1_19")]
+use crate::bmff::Boxes;
+use crate::plugin_api::{
+    heif_error, HeifEncoderPlugin, StaticData, ENCODER_PLUGIN_API_VERSION, HEIF_ERROR_OK,
+    HEIF_ERROR_UNSUPPORTED_PARAMETER, IMAGE_INPUT_CLASS_NORMAL,
+};
+use crate::{
+    Channel, Chroma, ColorSpace, CompressionFormat, Encoder, EncoderDescriptor, HeifContext,
+    HeifError, HeifErrorCode, HeifErrorSubCode, Image, ImageHandle, LibHeif, Result,
+};
+
+/// Coded data of an image item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedData {
+    pub format: CompressionFormat,
+    /// Payload of the codec configuration box (`hvcC`, `av1C`, etc.)
+    /// without the box header. It is empty only for JPEG items
+    /// without `jpgC` box.
+    pub config: Vec<u8>,
+    /// Coded bytes of the item as they are stored in the file.
+    /// For example, NAL units of HEVC are prefixed with their sizes.
+    pub data: Vec<u8>,
+}
+
+/// Packets of coded data that are passed to `libheif` by the encoder plugin.
+struct CodedStream {
+    color_space: ColorSpace,
+    bit_depth: u8,
+    packets: Vec<Vec<u8>>,
+}
+
+fn invalid_input(message: &str) -> HeifError {
+    HeifError {
+        code: HeifErrorCode::InvalidInput,
+        sub_code: HeifErrorSubCode::EndOfData,
+        message: message.to_string(),
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    data.get(offset..offset.saturating_add(2))
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| invalid_input("Unexpected end of 'hvcC' data"))
+}
+
+impl CodedStream {
+    fn new(format: CompressionFormat, config: &[u8], bitstream: &[u8]) -> Result<Self> {
+        let stream = match format {
+            CompressionFormat::Hevc => Self::hevc(config, bitstream)?,
+            CompressionFormat::Av1 => Self::av1(config, bitstream)?,
+            _ => {
+                return Err(HeifError {
+                    code: HeifErrorCode::UnsupportedFeature,
+                    sub_code: HeifErrorSubCode::UnsupportedCodec,
+                    message: format!(
+                        "Pre-compressed images in {:?} format are not supported",
+                        format
+                    ),
+                })
+            }
+        };
+        if stream.packets.iter().any(|p| p.len() > c_int::MAX as usize) {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::InvalidParameterValue,
+                message: "Pre-compressed data is too big".to_string(),
+            });
+        }
+        Ok(stream)
+    }
+
+    /// NAL units from arrays of `hvcC` box followed by NAL units of the bitstream.
+    fn hevc(config: &[u8], bitstream: &[u8]) -> Result<Self> {
+        let header = config
+            .get(..23)
+            .ok_or_else(|| invalid_input("Unexpected end of 'hvcC' data"))?;
+        let color_space = match header[16] & 3 {
+            0 => ColorSpace::Monochrome,
+            1 => ColorSpace::YCbCr(Chroma::C420),
+            2 => ColorSpace::YCbCr(Chroma::C422),
+            _ => ColorSpace::YCbCr(Chroma::C444),
+        };
+        let bit_depth = (header[17] & 7) + 8;
+        let length_size = (header[21] & 3) as usize + 1;
+
+        let mut packets = Vec::new();
+        let mut offset = 23;
+        for _ in 0..header[22] {
+            let num_units = read_u16(config, offset + 1)?;
+            offset += 3;
+            for _ in 0..num_units {
+                let size = read_u16(config, offset)? as usize;
+                offset += 2;
+                let unit = config
+                    .get(offset..offset + size)
+                    .filter(|unit| !unit.is_empty())
+                    .ok_or_else(|| invalid_input("Invalid NAL unit in 'hvcC' data"))?;
+                packets.push(unit.to_vec());
+                offset += size;
+            }
+        }
+
+        let mut offset = 0;
+        while offset < bitstream.len() {
+            let size = bitstream
+                .get(offset..offset + length_size)
+                .ok_or_else(|| invalid_input("Unexpected end of HEVC bitstream"))?
+                .iter()
+                .fold(0usize, |size, &b| (size << 8) | b as usize);
+            offset += length_size;
+            let unit = bitstream
+                .get(offset..offset.saturating_add(size))
+                .filter(|unit| !unit.is_empty())
+                .ok_or_else(|| invalid_input("Invalid NAL unit in HEVC bitstream"))?;
+            packets.push(unit.to_vec());
+            offset += size;
+        }
+
+        Ok(Self {
+            color_space,
+            bit_depth,
+            packets,
+        })
+    }
+
+    /// OBUs of the bitstream. `libheif` builds `av1C` box from
+    /// the sequence header OBU which must be a part of the bitstream.
+    fn av1(config: &[u8], bitstream: &[u8]) -> Result<Self> {
+        let header = config
+            .get(..4)
+            .ok_or_else(|| invalid_input("Unexpected end of 'av1C' data"))?;
+        if header[0] & 0x80 == 0 {
+            return Err(HeifError {
+                code: HeifErrorCode::InvalidInput,
+                sub_code: HeifErrorSubCode::UnsupportedDataVersion,
+                message: "Invalid marker of 'av1C' data".to_string(),
+            });
+        }
+        if bitstream.is_empty() {
+            return Err(invalid_input("AV1 bitstream is empty"));
+        }
+        let flags = header[2];
+        let bit_depth = match (flags & 0x40 != 0, flags & 0x20 != 0) {
+            (true, true) => 12,
+            (true, false) => 10,
+            _ => 8,
+        };
+        let color_space = if flags & 0x10 != 0 {
+            ColorSpace::Monochrome
+        } else {
+            match (flags & 0x08 != 0, flags & 0x04 != 0) {
+                (true, true) => ColorSpace::YCbCr(Chroma::C420),
+                (true, false) => ColorSpace::YCbCr(Chroma::C422),
+                _ => ColorSpace::YCbCr(Chroma::C444),
+            }
+        };
+        Ok(Self {
+            color_space,
+            bit_depth,
+            packets: vec![bitstream.to_vec()],
+        })
+    }
+
+    /// Image that describes size and pixel format of coded data.
+    /// Values of its pixels are not used, but they are zeroed
+    /// because `libheif` may read them before passing the image
+    /// to the encoder plugin.
+    fn placeholder_image(&self, width: u32, height: u32) -> Result<Image> {
+        let mut image = Image::new(width, height, self.color_space)?;
+        image.create_plane(Channel::Y, width, height, self.bit_depth)?;
+        let chroma_size = match self.color_space {
+            ColorSpace::YCbCr(Chroma::C420) => Some((width.div_ceil(2), height.div_ceil(2))),
+            ColorSpace::YCbCr(Chroma::C422) => Some((width.div_ceil(2), height)),
+            ColorSpace::YCbCr(Chroma::C444) => Some((width, height)),
+            _ => None,
+        };
+        if let Some((chroma_width, chroma_height)) = chroma_size {
+            for channel in [Channel::Cb, Channel::Cr] {
+                image.create_plane(channel, chroma_width, chroma_height, self.bit_depth)?;
+            }
+        }
+        let planes = image.planes_mut();
+        for plane in [planes.y, planes.cb, planes.cr].into_iter().flatten() {
+            plane.data.fill(0);
+        }
+        Ok(image)
+    }
+}
+
+pub(crate) fn add_precompressed_image<'a>(
+    context: &mut HeifContext<'a>,
+    format: CompressionFormat,
+    config_record: &[u8],
+    bitstream: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<ImageHandle<'a>> {
+    let stream = CodedStream::new(format, config_record, bitstream)?;
+    let image = stream.placeholder_image(width, height)?;
+
+    let lib_heif = LibHeif::new();
+    let mut encoder = passthrough_encoder(&lib_heif, format)?;
+    let pending = PendingStream::new(stream.packets);
+    let err = unsafe {
+        lh::heif_encoder_set_parameter_integer(encoder.inner, STREAM_PARAMETER.as_ptr(), pending.id)
+    };
+    HeifError::from_heif_error(err)?;
+    context.encode_image(&image, &mut encoder, None)
+}
+
+/// Name of the encoder parameter that receives ID of [`PendingStream`].
+const STREAM_PARAMETER: &CStr = c"stream";
+
+/// Packets of coded data waiting for `encode_image()` call
+/// of the encoder which received their ID as [`STREAM_PARAMETER`].
+static PENDING_STREAMS: Mutex<Vec<(c_int, Vec<Vec<u8>>)>> = Mutex::new(Vec::new());
+static NEXT_STREAM_ID: AtomicI32 = AtomicI32::new(1);
+
+/// Packets added into [`PENDING_STREAMS`] which are removed from it
+/// on drop if the encoder didn't take them.
+struct PendingStream {
+    id: c_int,
+}
+
+impl PendingStream {
+    fn new(packets: Vec<Vec<u8>>) -> Self {
+        let id = NEXT_STREAM_ID.fetch_add(1, Ordering::Relaxed) & c_int::MAX;
+        pending_streams().push((id, packets));
+        Self { id }
+    }
+
+    fn take(id: c_int) -> Option<Vec<Vec<u8>>> {
+        let mut streams = pending_streams();
+        let index = streams.iter().position(|(stream_id, _)| *stream_id == id)?;
+        Some(streams.swap_remove(index).1)
+    }
+}
+
+impl Drop for PendingStream {
+    fn drop(&mut self) {
+        Self::take(self.id);
+    }
+}
+
+fn pending_streams() -> MutexGuard<'static, Vec<(c_int, Vec<Vec<u8>>)>> {
+    PENDING_STREAMS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+static REGISTRATION_MUTEX: Mutex<()> = Mutex::new(());
+
+/// Returns an instance of encoder that passes pre-compressed data to `libheif`.
+///
+/// The encoder plugin is registered on first use and after
+/// deinitialization of `libheif` that unregisters all plugins.
+fn passthrough_encoder(lib_heif: &LibHeif, format: CompressionFormat) -> Result<Encoder<'_>> {
+    let (plugin, id) = match format {
+        CompressionFormat::Av1 => (&AV1_PLUGIN, AV1_PLUGIN_ID),
+        _ => (&HEVC_PLUGIN, HEVC_PLUGIN_ID),
+    };
+    let _lock = REGISTRATION_MUTEX.lock();
+    let mut descriptor = find_descriptor(format, id);
+    if descriptor.is_none() {
+        let plugin_ptr = &plugin.0 as *const HeifEncoderPlugin as *const lh::heif_encoder_plugin;
+        let err = unsafe { lh::heif_register_encoder_plugin(plugin_ptr) };
+        HeifError::from_heif_error(err)?;
+        descriptor = find_descriptor(format, id);
+    }
+    match descriptor {
+        Some(descriptor) => lib_heif.encoder(descriptor),
+        None => Err(HeifError {
+            code: HeifErrorCode::EncoderPluginError,
+            sub_code: HeifErrorSubCode::PluginLoadingError,
+            message: "Failed to register encoder of pre-compressed data".to_string(),
+        }),
+    }
+}
+
+/// [`LibHeif::encoder_descriptors()`] hides encoders of pre-compressed data,
+/// so the descriptor is requested from `libheif` directly.
+fn find_descriptor(format: CompressionFormat, id: &CStr) -> Option<EncoderDescriptor<'static>> {
+    let mut descriptor_ptr = ptr::null();
+    let count = unsafe {
+        lh::heif_get_encoder_descriptors(format as _, id.as_ptr(), &mut descriptor_ptr, 1)
+    };
+    if count < 1 {
+        return None;
+    }
+    unsafe { descriptor_ptr.as_ref() }.map(EncoderDescriptor::new)
+}
+
+/// Returns `true` if the descriptor belongs to the encoder of pre-compressed data.
+///
+/// Such encoders accept only data from [`HeifContext::add_precompressed_image()`],
+/// so they must not be visible as general-purpose encoders.
+pub(crate) fn is_passthrough_encoder(descriptor: &EncoderDescriptor) -> bool {
+    let id = descriptor.id().as_bytes();
+    id == HEVC_PLUGIN_ID.to_bytes() || id == AV1_PLUGIN_ID.to_bytes()
+}
+
+const HEVC_PLUGIN_ID: &CStr = c"libheif-rs-precompressed-hevc";
+const AV1_PLUGIN_ID: &CStr = c"libheif-rs-precompressed-av1";
+
+static HEVC_PLUGIN: StaticData<HeifEncoderPlugin> = StaticData(passthrough_plugin(
+    lh::heif_compression_format_heif_compression_HEVC,
+    HEVC_PLUGIN_ID,
+));
+static AV1_PLUGIN: StaticData<HeifEncoderPlugin> = StaticData(passthrough_plugin(
+    lh::heif_compression_format_heif_compression_AV1,
+    AV1_PLUGIN_ID,
+));
+
+static NO_PARAMETERS: StaticData<[*const lh::heif_encoder_parameter; 1]> =
+    StaticData([ptr::null()]);
+
+const fn passthrough_plugin(
+    format: lh::heif_compression_format,
+    id_name: &'static CStr,
+) -> HeifEncoderPlugin {
+    HeifEncoderPlugin {
+        plugin_api_version: ENCODER_PLUGIN_API_VERSION,
+        compression_format: format,
+        id_name: id_name.as_ptr(),
+        // The lowest priority - `libheif` never selects the encoder as default
+        // one if there is any other encoder for the same format.
+        // `LibHeif` hides it from the list of available encoders.
+        priority: 0,
+        supports_lossy_compression: 0,
+        supports_lossless_compression: 0,
+        get_plugin_name: Some(plugin_name),
+        init_plugin: None,
+        cleanup_plugin: None,
+        new_encoder: Some(new_encoder),
+        free_encoder: Some(free_encoder),
+        set_parameter_quality: Some(set_int_value),
+        get_parameter_quality: Some(get_int_value),
+        set_parameter_lossless: Some(set_int_value),
+        get_parameter_lossless: Some(get_int_value),
+        set_parameter_logging_level: Some(set_int_value),
+        get_parameter_logging_level: Some(get_int_value),
+        list_parameters: Some(list_parameters),
+        set_parameter_integer: Some(set_stream_id),
+        get_parameter_integer: Some(get_named_value),
+        set_parameter_boolean: Some(set_named_value),
+        get_parameter_boolean: Some(get_named_value),
+        set_parameter_string: Some(set_named_string),
+        get_parameter_string: Some(get_named_string),
+        query_input_colorspace: Some(query_input_colorspace),
+        encode_image: Some(encode_image),
+        get_compressed_data: Some(get_compressed_data),
+        query_input_colorspace2: Some(query_input_colorspace2),
+        query_encoded_size: None,
+    }
+}
+
+#[derive(Default)]
+struct PassthroughEncoder {
+    /// ID of [`PendingStream`] with packets of the next image.
+    stream_id: Option<c_int>,
+    packets: VecDeque<Vec<u8>>,
+    current_packet: Vec<u8>,
+}
+
+unsafe extern "C" fn plugin_name() -> *const c_char {
+    c"Pre-compressed data (libheif-rs)".as_ptr()
+}
+
+unsafe extern "C" fn new_encoder(encoder: *mut *mut c_void) -> lh::heif_error {
+    *encoder = Box::into_raw(Box::<PassthroughEncoder>::default()) as _;
+    HEIF_ERROR_OK
+}
+
+unsafe extern "C" fn free_encoder(encoder: *mut c_void) {
+    drop(Box::from_raw(encoder as *mut PassthroughEncoder));
+}
+
+unsafe extern "C" fn set_int_value(_encoder: *mut c_void, _value: c_int) -> lh::heif_error {
+    HEIF_ERROR_OK
+}
+
+unsafe extern "C" fn get_int_value(_encoder: *mut c_void, value: *mut c_int) -> lh::heif_error {
+    *value = 0;
+    HEIF_ERROR_OK
+}
+
+unsafe extern "C" fn list_parameters(
+    _encoder: *mut c_void,
+) -> *const *const lh::heif_encoder_parameter {
+    NO_PARAMETERS.0.as_ptr()
+}
+
+unsafe extern "C" fn set_stream_id(
+    encoder: *mut c_void,
+    name: *const c_char,
+    value: c_int,
+) -> lh::heif_error {
+    if name.is_null() || CStr::from_ptr(name) != STREAM_PARAMETER {
+        return HEIF_ERROR_UNSUPPORTED_PARAMETER;
+    }
+    let encoder = &mut *(encoder as *mut PassthroughEncoder);
+    encoder.stream_id = Some(value);
+    HEIF_ERROR_OK
+}
+
+unsafe extern "C" fn set_named_value(
+    _encoder: *mut c_void,
+    _name: *const c_char,
+    _value: c_int,
+) -> lh::heif_error {
+    HEIF_ERROR_UNSUPPORTED_PARAMETER
+}
+
+unsafe extern "C" fn get_named_value(
+    _encoder: *mut c_void,
+    _name: *const c_char,
+    _value: *mut c_int,
+) -> lh::heif_error {
+    HEIF_ERROR_UNSUPPORTED_PARAMETER
+}
+
+unsafe extern "C" fn set_named_string(
+    _encoder: *mut c_void,
+    _name: *const c_char,
+    _value: *const c_char,
+) -> lh::heif_error {
+    HEIF_ERROR_UNSUPPORTED_PARAMETER
+}
+
+unsafe extern "C" fn get_named_string(
+    _encoder: *mut c_void,
+    _name: *const c_char,
+    _value: *mut c_char,
+    _value_size: c_int,
+) -> lh::heif_error {
+    HEIF_ERROR_UNSUPPORTED_PARAMETER
+}
+
+/// Placeholder image already has the pixel format of coded data.
+unsafe extern "C" fn query_input_colorspace(
+    _colorspace: *mut lh::heif_colorspace,
+    _chroma: *mut lh::heif_chroma,
+) {
+}
+
+unsafe extern "C" fn query_input_colorspace2(
+    _encoder: *mut c_void,
+    _colorspace: *mut lh::heif_colorspace,
+    _chroma: *mut lh::heif_chroma,
+) {
+}
+
+unsafe extern "C" fn encode_image(
+    encoder: *mut c_void,
+    _image: *const lh::heif_image,
+    image_class: c_uint,
+) -> lh::heif_error {
+    let encoder = &mut *(encoder as *mut PassthroughEncoder);
+    let packets = encoder.stream_id.take().and_then(PendingStream::take);
+    match packets {
+        Some(packets) if image_class == IMAGE_INPUT_CLASS_NORMAL => {
+            encoder.packets = packets.into();
+            HEIF_ERROR_OK
+        }
+        _ => heif_error(
+            lh::heif_error_code_heif_error_Encoder_plugin_error,
+            lh::heif_suberror_code_heif_suberror_Unsupported_codec,
+            c"Encoder only accepts pre-compressed data from HeifContext::add_precompressed_image()",
+        ),
+    }
+}
+
+unsafe extern "C" fn get_compressed_data(
+    encoder: *mut c_void,
+    data: *mut *mut u8,
+    size: *mut c_int,
+    _data_type: *mut c_uint,
+) -> lh::heif_error {
+    let encoder = &mut *(encoder as *mut PassthroughEncoder);
+    match encoder.packets.pop_front() {
+        Some(packet) => {
+            // The packet must stay alive until the next call.
+            encoder.current_packet = packet;
+            *data = encoder.current_packet.as_mut_ptr();
+            *size = encoder.current_packet.len() as _;
+        }
+        None => {
+            *data = ptr::null_mut();
+            *size = 0;
+        }
+    }
+    HEIF_ERROR_OK
+}
+
+/// Properties of items parsed from `iprp` box of a file.
+#[cfg(feature = "v1_19")]
+#[derive(Debug, Default)]
+pub(crate) struct ItemProperties {
+    /// Types and payloads of boxes stored in `ipco` box.
+    properties: Vec<([u8; 4], Vec<u8>)>,
+    /// 1-based indexes of properties associated with items by `ipma` boxes.
+    associations: HashMap<u32, Vec<usize>>,
+}
+
+#[cfg(feature = "v1_19")]
+impl ItemProperties {
+    pub(crate) fn parse(file: &[u8]) -> Self {
+        let mut item_properties = Self::default();
+        let Some(iprp) = find_box(file, b"meta")
+            // Skip version and flags of the full box.
+            .and_then(|meta| find_box(meta.get(4..)?, b"iprp"))
+        else {
+            return item_properties;
+        };
+        if let Some(ipco) = find_box(iprp, b"ipco") {
+            item_properties.properties = Boxes(ipco)
+                .map(|b| (b.box_type, b.payload.to_vec()))
+                .collect();
+        }
+        for ipma in Boxes(iprp).filter(|b| &b.box_type == b"ipma") {
+            parse_ipma(ipma.payload, &mut item_properties.associations);
+        }
+        item_properties
+    }
+
+    /// Returns payload of the property box with the given type
+    /// that is associated with the item.
+    pub(crate) fn find(&self, item_id: u32, box_type: &[u8; 4]) -> Option<&[u8]> {
+        self.associations
+            .get(&item_id)?
+            .iter()
+            .filter_map(|&index| self.properties.get(index.checked_sub(1)?))
+            .find(|(property_type, _)| property_type == box_type)
+            .map(|(_, payload)| payload.as_slice())
+    }
+}
+
+#[cfg(feature = "v1_19")]
+fn find_box<'a>(data: &'a [u8], box_type: &[u8; 4]) -> Option<&'a [u8]> {
+    Boxes(data)
+        .find(|b| &b.box_type == box_type)
+        .map(|b| b.payload)
+}
+
+/// Adds 1-based indexes of properties associated with items by `ipma` box.
+#[cfg(feature = "v1_19")]
+fn parse_ipma(ipma: &[u8], associations: &mut HashMap<u32, Vec<usize>>) -> Option<()> {
+    let version = *ipma.first()?;
+    let large_indexes = ipma.get(3)? & 1 != 0;
+    let count = u32::from_be_bytes(ipma.get(4..8)?.try_into().ok()?);
+    let mut offset = 8;
+    for _ in 0..count {
+        let id = if version < 1 {
+            offset += 2;
+            u16::from_be_bytes(ipma.get(offset - 2..offset)?.try_into().ok()?) as u32
+        } else {
+            offset += 4;
+            u32::from_be_bytes(ipma.get(offset - 4..offset)?.try_into().ok()?)
+        };
+        let count = *ipma.get(offset)? as usize;
+        offset += 1;
+        let indexes = associations.entry(id).or_default();
+        for _ in 0..count {
+            let index = if large_indexes {
+                offset += 2;
+                u16::from_be_bytes(ipma.get(offset - 2..offset)?.try_into().ok()?) & 0x7fff
+            } else {
+                offset += 1;
+                (*ipma.get(offset - 1)? & 0x7f) as u16
+            };
+            indexes.push(index as usize);
+        }
+    }
+    Some(())
+}
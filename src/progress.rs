@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::os::raw::{c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(feature = "v1_19")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "v1_19")]
+use std::sync::Arc;
+use std::sync::Mutex;
+#[cfg(feature = "rayon")]
+use std::sync::PoisonError;
+
+use libheif_sys as lh;
+
+#[cfg(feature = "v1_19")]
+use crate::{HeifError, HeifErrorCode, HeifErrorSubCode};
+use crate::{ProgressStep, Result};
+
+/// A token that allows cancelling a long-running operation.
+///
+/// The token is cheap to clone. All clones share the same state, so you may
+/// keep one clone and pass another one into the options of the operation.
+/// For example, to abort decoding of an image from another thread.
+#[cfg(feature = "v1_19")]
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+#[cfg(feature = "v1_19")]
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation of all operations that use this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+pub(crate) type ProgressHandler = Box<dyn FnMut(ProgressStep, u32, u32) + Send>;
+
+struct ProgressState {
+    handler: ProgressHandler,
+    // Maximum and last reported values of progress for every started step.
+    steps: HashMap<ProgressStep, (u32, u32)>,
+    // The handler isn't called anymore after it panicked.
+    panicked: bool,
+}
+
+impl ProgressState {
+    /// Calls the handler. Panics are swallowed, because
+    /// the handler is called by callbacks passed into `libheif`
+    /// and panics must not unwind into it.
+    fn report(&mut self, step: ProgressStep, progress: u32, max_progress: u32) {
+        if self.panicked {
+            return;
+        }
+        let handler = &mut self.handler;
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            handler(step, progress, max_progress);
+        }));
+        self.panicked = res.is_err();
+    }
+
+    fn start(&mut self, step: ProgressStep, max_progress: u32) {
+        self.steps.insert(step, (max_progress, 0));
+        self.report(step, 0, max_progress);
+    }
+
+    fn progress(&mut self, step: ProgressStep, progress: u32) {
+        let max_progress = match self.steps.get_mut(&step) {
+            // Don't report the same value twice
+            Some((_, current)) if *current == progress => return,
+            Some((max_progress, current)) => {
+                *current = progress;
+                *max_progress
+            }
+            None => 0,
+        };
+        self.report(step, progress, max_progress);
+    }
+
+    /// Increases progress of the step by one.
+    #[cfg(feature = "rayon")]
+    fn advance(&mut self, step: ProgressStep) {
+        let progress = self.steps.get(&step).map_or(0, |&(_, current)| current);
+        self.progress(step, progress.saturating_add(1));
+    }
+
+    fn end(&mut self, step: ProgressStep) {
+        if let Some((max_progress, current)) = self.steps.remove(&step) {
+            if current != max_progress {
+                self.report(step, max_progress, max_progress);
+            }
+        }
+    }
+}
+
+/// Rust-side state of progress and cancellation callbacks
+/// which `libheif` receives as `progress_user_data`.
+#[derive(Default)]
+pub(crate) struct ProgressCallbacks {
+    progress: Option<Mutex<ProgressState>>,
+    #[cfg(feature = "v1_19")]
+    cancellation_token: Option<CancellationToken>,
+}
+
+impl ProgressCallbacks {
+    pub(crate) fn has_progress_handler(&self) -> bool {
+        self.progress.is_some()
+    }
+
+    pub(crate) fn set_progress_handler(&mut self, handler: Option<ProgressHandler>) {
+        self.progress = handler.map(|handler| {
+            Mutex::new(ProgressState {
+                handler,
+                steps: HashMap::new(),
+                panicked: false,
+            })
+        });
+    }
+
+    #[cfg(feature = "rayon")]
+    pub(crate) fn take_progress_handler(&mut self) -> Option<ProgressHandler> {
+        self.progress.take().map(|state| {
+            state
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner)
+                .handler
+        })
+    }
+
+    #[cfg(feature = "v1_19")]
+    pub(crate) fn cancellation_token(&self) -> Option<&CancellationToken> {
+        self.cancellation_token.as_ref()
+    }
+
+    #[cfg(feature = "v1_19")]
+    pub(crate) fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.cancellation_token = token;
+    }
+
+    pub(crate) fn start(&self, step: ProgressStep, max_progress: u32) {
+        self.with_state(|state| state.start(step, max_progress));
+    }
+
+    pub(crate) fn progress(&self, step: ProgressStep, progress: u32) {
+        self.with_state(|state| state.progress(step, progress));
+    }
+
+    #[cfg(feature = "rayon")]
+    pub(crate) fn advance(&self, step: ProgressStep) {
+        self.with_state(|state| state.advance(step));
+    }
+
+    pub(crate) fn end(&self, step: ProgressStep) {
+        self.with_state(|state| state.end(step));
+    }
+
+    /// Returns an error with code [`HeifErrorCode::Canceled`]
+    /// if cancellation was requested.
+    #[cfg(feature = "v1_19")]
+    pub(crate) fn check_cancellation(&self) -> Result<()> {
+        if self.cancellation_token().is_some_and(|t| t.is_cancelled()) {
+            Err(HeifError {
+                code: HeifErrorCode::Canceled,
+                sub_code: HeifErrorSubCode::Unspecified,
+                message: "Operation has been canceled".to_string(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Runs an operation that can't report intermediate progress
+    /// as a single unit of work of [`ProgressStep::Total`] step.
+    pub(crate) fn run_single_step<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        #[cfg(feature = "v1_19")]
+        self.check_cancellation()?;
+        self.start(ProgressStep::Total, 1);
+        let res = f()?;
+        self.end(ProgressStep::Total);
+        Ok(res)
+    }
+
+    fn with_state(&self, f: impl FnOnce(&mut ProgressState)) {
+        if let Some(Ok(mut state)) = self.progress.as_ref().map(|m| m.lock()) {
+            f(&mut state);
+        }
+    }
+}
+
+impl Debug for ProgressCallbacks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("ProgressCallbacks");
+        debug.field("has_progress_handler", &self.has_progress_handler());
+        #[cfg(feature = "v1_19")]
+        debug.field("cancellation_token", &self.cancellation_token);
+        debug.finish()
+    }
+}
+
+#[inline]
+unsafe fn callbacks<'a>(user_data: *mut c_void) -> Option<&'a ProgressCallbacks> {
+    (user_data as *const ProgressCallbacks).as_ref()
+}
+
+#[inline]
+fn progress_step(step: lh::heif_progress_step) -> ProgressStep {
+    ProgressStep::n(step).unwrap_or(ProgressStep::Unknown)
+}
+
+pub(crate) unsafe extern "C" fn start_progress(
+    step: lh::heif_progress_step,
+    max_progress: c_int,
+    user_data: *mut c_void,
+) {
+    if let Some(callbacks) = callbacks(user_data) {
+        callbacks.start(progress_step(step), max_progress.max(0) as u32);
+    }
+}
+
+pub(crate) unsafe extern "C" fn on_progress(
+    step: lh::heif_progress_step,
+    progress: c_int,
+    user_data: *mut c_void,
+) {
+    if let Some(callbacks) = callbacks(user_data) {
+        callbacks.progress(progress_step(step), progress.max(0) as u32);
+    }
+}
+
+pub(crate) unsafe extern "C" fn end_progress(step: lh::heif_progress_step, user_data: *mut c_void) {
+    if let Some(callbacks) = callbacks(user_data) {
+        callbacks.end(progress_step(step));
+    }
+}
+
+#[cfg(feature = "v1_19")]
+pub(crate) unsafe extern "C" fn cancel(user_data: *mut c_void) -> c_int {
+    callbacks(user_data)
+        .and_then(|callbacks| callbacks.cancellation_token())
+        .is_some_and(|token| token.is_cancelled()) as _
+}
@@ -1,4 +1,6 @@
 use std::os::raw::{c_int, c_void};
+use std::sync::{Mutex, PoisonError};
+use std::thread::{self, ThreadId};
 use std::{io, slice};
 
 use libheif_sys as lh;
@@ -94,39 +96,99 @@ where
     }
 }
 
+/// User data of [`HEIF_READER`].
+///
+/// `libheif` may call the reader from several threads,
+/// so calls are serialized by the mutex.
+pub(crate) struct ReaderUserData<'a> {
+    reader: Mutex<Box<dyn Reader + 'a>>,
+    /// Thread that the reader is bound to if it isn't `Send`.
+    owner: Option<ThreadId>,
+}
+
+impl<'a> ReaderUserData<'a> {
+    /// Creates user data with the reader that may be called from any thread.
+    pub(crate) fn new(reader: Box<dyn Reader + Send + 'a>) -> Self {
+        Self {
+            reader: Mutex::new(reader),
+            owner: None,
+        }
+    }
+
+    /// Creates user data with the reader that may be called only
+    /// from the current thread.
+    pub(crate) fn new_thread_bound(reader: Box<dyn Reader + 'a>) -> Self {
+        Self {
+            reader: Mutex::new(reader),
+            owner: Some(thread::current().id()),
+        }
+    }
+
+    pub(crate) fn is_thread_bound(&self) -> bool {
+        self.owner.is_some()
+    }
+
+    /// Returns `true` if the reader may be used (and dropped)
+    /// in the current thread.
+    pub(crate) fn is_accessible(&self) -> bool {
+        self.owner
+            .is_none_or(|owner| owner == thread::current().id())
+    }
+}
+
+/// Calls the function with the reader or returns `None` if the reader
+/// can't be used in the current thread.
+unsafe fn with_reader<R>(
+    user_data: *mut c_void,
+    f: impl FnOnce(&mut dyn Reader) -> R,
+) -> Option<R> {
+    let user_data = &*(user_data as *const ReaderUserData);
+    if !user_data.is_accessible() {
+        return None;
+    }
+    let mut reader = user_data
+        .reader
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    Some(f(reader.as_mut()))
+}
+
 unsafe extern "C" fn get_position(user_data: *mut c_void) -> i64 {
-    let reader = &mut *(user_data as *mut Box<dyn Reader>);
-    reader.position() as _
+    with_reader(user_data, |reader| reader.position() as _).unwrap_or(0)
 }
 
 unsafe extern "C" fn read(data: *mut c_void, size: usize, user_data: *mut c_void) -> c_int {
     if data.is_null() || size == 0 {
         return 0;
     }
-    let reader = &mut *(user_data as *mut Box<dyn Reader>);
     let buf = slice::from_raw_parts_mut(data as *mut u8, size);
-    if reader.read_exact(buf).is_ok() {
-        0
-    } else {
-        1
-    }
+    with_reader(user_data, |reader| {
+        if reader.read_exact(buf).is_ok() {
+            0
+        } else {
+            1
+        }
+    })
+    .unwrap_or(1)
 }
 
 unsafe extern "C" fn seek(position: i64, user_data: *mut c_void) -> c_int {
-    let reader = &mut *(user_data as *mut Box<dyn Reader>);
-    match reader.seek(position as _) {
+    with_reader(user_data, |reader| match reader.seek(position as _) {
         Ok(_) => 0,
         Err(_) => 1,
-    }
+    })
+    .unwrap_or(1)
 }
 
 unsafe extern "C" fn wait_for_file_size(
     target_size: i64,
     user_data: *mut c_void,
 ) -> lh::heif_reader_grow_status {
-    let reader = &mut *(user_data as *mut Box<dyn Reader>);
     let target_size = target_size as u64;
-    reader.wait_for_file_size(target_size) as _
+    with_reader(user_data, |reader| {
+        reader.wait_for_file_size(target_size) as _
+    })
+    .unwrap_or(ReaderGrowStatus::Timeout as _)
 }
 
 #[cfg(not(feature = "v1_19"))]
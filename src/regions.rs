@@ -1,7 +1,9 @@
 use libheif_sys as lh;
 use std::ptr;
 
+use crate::context::ContextShared;
 use crate::utils::get_non_null_ptr;
+pub use crate::Rectangle;
 use crate::{HeifError, Image, ItemId, Result};
 
 pub struct PointRegion(ptr::NonNull<lh::heif_region>);
@@ -36,19 +38,6 @@ impl PointRegion {
 
 pub struct RectangleRegion(ptr::NonNull<lh::heif_region>);
 
-/// Rectangle geometry.
-///
-/// The region is represented by a left top corner position,
-/// and a size defined by width and height.
-/// All the interior points and the edge are part of the region.
-#[derive(Default, Copy, Clone, PartialEq, Eq)]
-pub struct Rectangle {
-    pub left: i32,
-    pub top: i32,
-    pub width: u32,
-    pub height: u32,
-}
-
 #[derive(Default, Copy, Clone, PartialEq)]
 pub struct RectangleTransformed {
     pub left: f64,
@@ -438,11 +427,12 @@ impl Drop for Region {
 /// Region item.
 ///
 /// See ISO/IEC 23008-12:2022 Section 6.10 "Region items and region annotations"
-pub struct RegionItem {
+pub struct RegionItem<'a> {
     pub(crate) inner: *mut lh::heif_region_item,
+    shared: ContextShared<'a>,
 }
 
-impl Drop for RegionItem {
+impl Drop for RegionItem<'_> {
     fn drop(&mut self) {
         unsafe {
             lh::heif_region_item_release(self.inner);
@@ -450,10 +440,19 @@ impl Drop for RegionItem {
     }
 }
 
-impl RegionItem {
-    pub(crate) fn new(inner: ptr::NonNull<lh::heif_region_item>) -> Self {
+// Regions are added to the region item with holding the write lock
+// of the context, and the list of regions is read with holding the read lock.
+unsafe impl Send for RegionItem<'_> {}
+unsafe impl Sync for RegionItem<'_> {}
+
+impl<'a> RegionItem<'a> {
+    pub(crate) fn new(
+        inner: ptr::NonNull<lh::heif_region_item>,
+        shared: ContextShared<'a>,
+    ) -> Self {
         Self {
             inner: inner.as_ptr(),
+            shared,
         }
     }
 
@@ -478,6 +477,7 @@ impl RegionItem {
 
     /// Get the regions that are part of a region item.
     pub fn regions(&self) -> Vec<Region> {
+        let _lock = self.shared.read();
         let num_regions = unsafe { lh::heif_region_item_get_number_of_regions(self.inner) };
         let size = num_regions.max(0) as usize;
         let mut region_ptrs: Vec<*mut lh::heif_region> = Vec::with_capacity(size);
@@ -503,6 +503,7 @@ impl RegionItem {
     /// Add a point region to the region item.
     pub fn add_point(&mut self, x: i32, y: i32) -> Result<Region> {
         let mut region_ptr: *mut lh::heif_region = ptr::null_mut();
+        let _lock = self.shared.write();
         let err =
             unsafe { lh::heif_region_item_add_region_point(self.inner, x, y, &mut region_ptr) };
         HeifError::from_heif_error(err)?;
@@ -512,6 +513,7 @@ impl RegionItem {
     /// Add a rectangle region to the region item.
     pub fn add_rectangle(&mut self, rectangle: Rectangle) -> Result<Region> {
         let mut region_ptr: *mut lh::heif_region = ptr::null_mut();
+        let _lock = self.shared.write();
         let err = unsafe {
             lh::heif_region_item_add_region_rectangle(
                 self.inner,
@@ -537,6 +539,7 @@ impl RegionItem {
         radius_y: u32,
     ) -> Result<Region> {
         let mut region_ptr: *mut lh::heif_region = ptr::null_mut();
+        let _lock = self.shared.write();
         let err = unsafe {
             lh::heif_region_item_add_region_ellipse(
                 self.inner,
@@ -560,6 +563,7 @@ impl RegionItem {
     /// The points are provided as pairs of (X, Y) coordinates.
     pub fn add_polygon(&mut self, points: &[(i32, i32)]) -> Result<Region> {
         let mut region_ptr: *mut lh::heif_region = ptr::null_mut();
+        let _lock = self.shared.write();
         let err = unsafe {
             lh::heif_region_item_add_region_polygon(
                 self.inner,
@@ -583,6 +587,7 @@ impl RegionItem {
     /// The points are provided as pairs of (X, Y) coordinates.
     pub fn add_polyline(&mut self, points: &[(i32, i32)]) -> Result<Region> {
         let mut region_ptr: *mut lh::heif_region = ptr::null_mut();
+        let _lock = self.shared.write();
         let err = unsafe {
             lh::heif_region_item_add_region_polyline(
                 self.inner,
@@ -604,6 +609,7 @@ impl RegionItem {
         mask_item_id: ItemId,
     ) -> Result<Region> {
         let mut region_ptr: *mut lh::heif_region = ptr::null_mut();
+        let _lock = self.shared.write();
         let err = unsafe {
             lh::heif_region_item_add_region_referenced_mask(
                 self.inner,
@@ -636,6 +642,7 @@ impl RegionItem {
         mask_data: &[u8],
     ) -> Result<Region> {
         let mut region_ptr: *mut lh::heif_region = ptr::null_mut();
+        let _lock = self.shared.write();
         let err = unsafe {
             lh::heif_region_item_add_region_inline_mask_data(
                 self.inner,
@@ -671,6 +678,7 @@ impl RegionItem {
     /// of the region, the image is cropped.
     pub fn add_inline_mask(&mut self, rectangle: Rectangle, image: &Image) -> Result<Region> {
         let mut region_ptr: *mut lh::heif_region = ptr::null_mut();
+        let _lock = self.shared.write();
         let err = unsafe {
             lh::heif_region_item_add_region_inline_mask(
                 self.inner,
@@ -0,0 +1,346 @@
+use std::f64::consts::PI;
+
+use crate::image::CHANNELS;
+use crate::{
+    Channel, Chroma, ColorSpace, HeifError, HeifErrorCode, HeifErrorSubCode, Image, Plane, Result,
+    RgbChroma,
+};
+
+/// Filter used to resample pixels of an image.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ScalingFilter {
+    /// Scaling is performed by `libheif`.
+    #[default]
+    NearestNeighbor,
+    /// Linear interpolation between two nearest pixels in each direction.
+    Bilinear,
+    /// Catmull-Rom cubic interpolation.
+    Bicubic,
+    /// Lanczos filter with window of size 3.
+    Lanczos3,
+}
+
+impl ScalingFilter {
+    fn support(&self) -> f64 {
+        match self {
+            Self::NearestNeighbor => 0.5,
+            Self::Bilinear => 1.0,
+            Self::Bicubic => 2.0,
+            Self::Lanczos3 => 3.0,
+        }
+    }
+
+    fn kernel(&self, x: f64) -> f64 {
+        let x = x.abs();
+        match self {
+            Self::NearestNeighbor => {
+                if x < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Self::Bilinear => (1.0 - x).max(0.0),
+            Self::Bicubic => {
+                // Catmull-Rom spline (a = -0.5)
+                const A: f64 = -0.5;
+                if x < 1.0 {
+                    ((A + 2.0) * x - (A + 3.0)) * x * x + 1.0
+                } else if x < 2.0 {
+                    (((x - 5.0) * x + 8.0) * x - 4.0) * A
+                } else {
+                    0.0
+                }
+            }
+            Self::Lanczos3 => {
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = x * PI;
+        x.sin() / x
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScalingOptions {
+    filter: ScalingFilter,
+}
+
+impl ScalingOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn filter(&self) -> ScalingFilter {
+        self.filter
+    }
+
+    pub fn set_filter(&mut self, filter: ScalingFilter) {
+        self.filter = filter;
+    }
+}
+
+/// Scale the image with help of the given filter.
+pub(crate) fn scale_image(
+    src: &Image,
+    width: u32,
+    height: u32,
+    filter: ScalingFilter,
+) -> Result<Image> {
+    if width == 0 || height == 0 {
+        return Err(HeifError {
+            code: HeifErrorCode::UsageError,
+            sub_code: HeifErrorSubCode::InvalidParameterValue,
+            message: "Width and height of scaled image must be greater than zero".to_string(),
+        });
+    }
+    let color_space = src.color_space().ok_or(HeifError {
+        code: HeifErrorCode::UsageError,
+        sub_code: HeifErrorSubCode::UnsupportedColorConversion,
+        message: "Image has unknown color space".to_string(),
+    })?;
+    let mut dst = Image::new(width, height, color_space)?;
+    let premultiply = matches!(
+        color_space,
+        ColorSpace::Rgb(RgbChroma::Rgba | RgbChroma::HdrRgbaBe | RgbChroma::HdrRgbaLe)
+    ) && !src.is_premultiplied_alpha();
+
+    for channel in CHANNELS {
+        let Some(src_plane) = src.plane(channel) else {
+            continue;
+        };
+        let (dst_width, dst_height) = channel_size(color_space, channel, width, height);
+        let bit_depth = src.bits_per_pixel(channel).unwrap_or(8);
+        dst.create_plane(channel, dst_width, dst_height, bit_depth)?;
+        let Some(mut dst_plane) = dst.plane_mut(channel) else {
+            continue;
+        };
+
+        let mut pixels = PlaneBuffer::read(&src_plane)?;
+        if premultiply && channel == Channel::Interleaved {
+            pixels.premultiply(bit_depth);
+        }
+        let mut pixels = pixels
+            .resample_horizontal(dst_width as usize, filter)
+            .resample_vertical(dst_height as usize, filter);
+        if premultiply && channel == Channel::Interleaved {
+            pixels.unpremultiply(bit_depth);
+        }
+        pixels.write(&mut dst_plane, bit_depth)?;
+    }
+    src.copy_properties_to(&mut dst)?;
+    Ok(dst)
+}
+
+/// Size of the plane of the channel for image with given size.
+fn channel_size(color_space: ColorSpace, channel: Channel, width: u32, height: u32) -> (u32, u32) {
+    match (color_space, channel) {
+        (ColorSpace::YCbCr(Chroma::C420), Channel::Cb | Channel::Cr) => {
+            (width.div_ceil(2), height.div_ceil(2))
+        }
+        (ColorSpace::YCbCr(Chroma::C422), Channel::Cb | Channel::Cr) => (width.div_ceil(2), height),
+        _ => (width, height),
+    }
+}
+
+/// Pixels of one plane as floating point values.
+pub(crate) struct PlaneBuffer {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) components: usize,
+    pub(crate) values: Vec<f32>,
+}
+
+impl PlaneBuffer {
+    pub(crate) fn read<T: AsRef<[u8]>>(plane: &Plane<T>) -> Result<Self> {
+        let is_16bit = plane.bits_per_pixel > 8;
+        let component_bits = if is_16bit { 16 } else { 8 };
+        let components = (plane.storage_bits_per_pixel as usize / component_bits).max(1);
+        let width = plane.width as usize;
+        let height = plane.height as usize;
+        let mut values = Vec::with_capacity(width * height * components);
+        if is_16bit {
+            for row in plane.as_u16_rows()? {
+                values.extend(row.iter().map(|v| v as f32));
+            }
+        } else {
+            for row in plane.rows() {
+                values.extend(row.iter().map(|&v| v as f32));
+            }
+        }
+        Ok(Self {
+            width,
+            height,
+            components,
+            values,
+        })
+    }
+
+    pub(crate) fn write(&self, plane: &mut Plane<&mut [u8]>, bit_depth: u8) -> Result<()> {
+        let max_value = ((1u32 << bit_depth) - 1) as f32;
+        let row_len = self.width * self.components;
+        let to_int = |v: &f32| v.round().clamp(0.0, max_value) as u16;
+        if bit_depth > 8 {
+            for (mut dst_row, src_row) in plane.as_u16_rows_mut()?.zip(self.rows(row_len)) {
+                let values: Vec<u16> = src_row.iter().map(to_int).collect();
+                dst_row.copy_from_slice(&values);
+            }
+        } else {
+            for (dst_row, src_row) in plane.rows_mut().zip(self.rows(row_len)) {
+                for (dst, src) in dst_row.iter_mut().zip(src_row) {
+                    *dst = to_int(src) as u8;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn rows(&self, row_len: usize) -> impl Iterator<Item = &[f32]> {
+        self.values.chunks_exact(row_len.max(1))
+    }
+
+    fn premultiply(&mut self, bit_depth: u8) {
+        let max_value = ((1u32 << bit_depth) - 1) as f32;
+        for pixel in self.values.chunks_exact_mut(self.components) {
+            let (alpha, colors) = pixel.split_last_mut().unwrap();
+            let k = *alpha / max_value;
+            colors.iter_mut().for_each(|c| *c *= k);
+        }
+    }
+
+    fn unpremultiply(&mut self, bit_depth: u8) {
+        let max_value = ((1u32 << bit_depth) - 1) as f32;
+        for pixel in self.values.chunks_exact_mut(self.components) {
+            let (alpha, colors) = pixel.split_last_mut().unwrap();
+            let alpha = alpha.clamp(0.0, max_value);
+            if alpha > 0.0 {
+                let k = max_value / alpha;
+                colors.iter_mut().for_each(|c| *c *= k);
+            } else {
+                colors.iter_mut().for_each(|c| *c = 0.0);
+            }
+        }
+    }
+
+    fn resample_horizontal(self, dst_width: usize, filter: ScalingFilter) -> Self {
+        if dst_width == self.width {
+            return self;
+        }
+        let weights = Weights::new(self.width, dst_width, filter);
+        let components = self.components;
+        let mut values = Vec::with_capacity(dst_width * self.height * components);
+        for src_row in self.values.chunks_exact(self.width * components) {
+            for (start, coeffs) in weights.iter() {
+                for c in 0..components {
+                    let sum: f32 = coeffs
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &w)| w * src_row[(start + i) * components + c])
+                        .sum();
+                    values.push(sum);
+                }
+            }
+        }
+        Self {
+            width: dst_width,
+            height: self.height,
+            components,
+            values,
+        }
+    }
+
+    fn resample_vertical(self, dst_height: usize, filter: ScalingFilter) -> Self {
+        if dst_height == self.height {
+            return self;
+        }
+        let weights = Weights::new(self.height, dst_height, filter);
+        let row_len = self.width * self.components;
+        let mut values = Vec::with_capacity(row_len * dst_height);
+        for (start, coeffs) in weights.iter() {
+            let row_start = values.len();
+            values.resize(row_start + row_len, 0.0);
+            let dst_row = &mut values[row_start..];
+            for (i, &w) in coeffs.iter().enumerate() {
+                let src_offset = (start + i) * row_len;
+                let src_row = &self.values[src_offset..src_offset + row_len];
+                for (dst, &src) in dst_row.iter_mut().zip(src_row) {
+                    *dst += w * src;
+                }
+            }
+        }
+        Self {
+            width: self.width,
+            height: dst_height,
+            components: self.components,
+            values,
+        }
+    }
+}
+
+/// Normalized coefficients of the filter for every destination pixel.
+struct Weights {
+    /// Index of first source pixel and number of coefficients.
+    bounds: Vec<(usize, usize)>,
+    /// Coefficients of all pixels, every pixel has `window_size` values.
+    coeffs: Vec<f32>,
+    window_size: usize,
+}
+
+impl Weights {
+    fn new(src_size: usize, dst_size: usize, filter: ScalingFilter) -> Self {
+        let scale = src_size as f64 / dst_size as f64;
+        let filter_scale = scale.max(1.0);
+        let support = filter.support() * filter_scale;
+        let window_size = (support.ceil() as usize * 2 + 1).min(src_size);
+        let mut bounds = Vec::with_capacity(dst_size);
+        let mut coeffs = vec![0f32; dst_size * window_size];
+
+        for (x, dst_coeffs) in coeffs.chunks_exact_mut(window_size).enumerate() {
+            let center = (x as f64 + 0.5) * scale;
+            let left = ((center - support).floor().max(0.0) as usize).min(src_size - 1);
+            let right = ((center + support).ceil() as usize).min(src_size);
+            let count = (right - left).min(window_size);
+
+            let mut sum = 0.0;
+            for (i, coeff) in dst_coeffs.iter_mut().take(count).enumerate() {
+                let w = filter.kernel((left as f64 + i as f64 + 0.5 - center) / filter_scale);
+                *coeff = w as f32;
+                sum += w;
+            }
+            if sum != 0.0 {
+                dst_coeffs
+                    .iter_mut()
+                    .for_each(|c| *c = (*c as f64 / sum) as f32);
+            } else {
+                // Use the nearest pixel if the filter has no non-zero coefficients.
+                dst_coeffs[0] = 1.0;
+            }
+            bounds.push((left, count.max(1)));
+        }
+        Self {
+            bounds,
+            coeffs,
+            window_size,
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (usize, &[f32])> {
+        self.bounds
+            .iter()
+            .zip(self.coeffs.chunks_exact(self.window_size))
+            .map(|(&(start, count), coeffs)| (start, &coeffs[..count]))
+    }
+}
@@ -3,6 +3,7 @@ use std::ptr;
 use four_cc::FourCC;
 use libheif_sys as lh;
 
+use crate::context::ContextShared;
 use crate::decoder::get_decoding_options_ptr;
 use crate::{ColorSpace, DecodingOptions, HeifError, Image, Result};
 
@@ -23,14 +24,24 @@ pub struct ImageResolution {
     pub height: u16,
 }
 
-pub struct Track {
+pub struct Track<'a> {
     pub(crate) inner: *mut lh::heif_track,
+    shared: ContextShared<'a>,
 }
 
-impl Track {
+// Decoding of images changes the state of the track and reads data
+// of samples without synchronization in `libheif`, so it holds the write
+// lock of the context. Other methods read immutable properties of the track.
+unsafe impl Send for Track<'_> {}
+unsafe impl Sync for Track<'_> {}
+
+impl<'a> Track<'a> {
     #[inline]
-    pub(crate) fn from_heif_track(track: *mut lh::heif_track) -> Track {
-        Track { inner: track }
+    pub(crate) fn from_heif_track(track: *mut lh::heif_track, shared: ContextShared<'a>) -> Self {
+        Track {
+            inner: track,
+            shared,
+        }
     }
 
     /// Get the ID of the track.
@@ -95,13 +106,14 @@ impl Track {
         decoding_options: Option<DecodingOptions>,
     ) -> Result<Image> {
         let mut c_image: *mut lh::heif_image = ptr::null_mut();
+        let _lock = self.shared.write();
         let err = unsafe {
             lh::heif_track_decode_next_image(
                 self.inner,
                 &mut c_image,
                 color_space.heif_color_space(),
                 color_space.heif_chroma(),
-                get_decoding_options_ptr(&decoding_options),
+                get_decoding_options_ptr(decoding_options.as_ref()),
             )
         };
         HeifError::from_heif_error(err)?;
@@ -109,7 +121,7 @@ impl Track {
     }
 }
 
-impl Drop for Track {
+impl Drop for Track<'_> {
     fn drop(&mut self) {
         unsafe { lh::heif_track_release(self.inner) };
     }
@@ -0,0 +1,495 @@
+use std::fmt::Write;
+
+use crate::{HeifError, HeifErrorCode, HeifErrorSubCode, Result};
+
+/// URIs of commonly used XMP namespaces.
+pub mod xmp_namespaces {
+    pub const XMP: &str = "http://ns.adobe.com/xap/1.0/";
+    /// Dublin Core.
+    pub const DC: &str = "http://purl.org/dc/elements/1.1/";
+    pub const XMP_MM: &str = "http://ns.adobe.com/xap/1.0/mm/";
+    pub const PHOTOSHOP: &str = "http://ns.adobe.com/photoshop/1.0/";
+    pub const TIFF: &str = "http://ns.adobe.com/tiff/1.0/";
+    pub const EXIF: &str = "http://ns.adobe.com/exif/1.0/";
+    /// Gain map of HDR images created by Apple devices.
+    pub const APPLE_HDR_GAIN_MAP: &str = "http://ns.apple.com/HDRGainMap/1.0/";
+    /// Adobe gain map (ISO 21496-1 draft).
+    pub const HDR_GAIN_MAP: &str = "http://ns.adobe.com/hdr-gain-map/1.0/";
+}
+
+const RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const XML: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Prefixes used for well-known namespaces.
+const KNOWN_PREFIXES: [(&str, &str); 8] = [
+    ("xmp", xmp_namespaces::XMP),
+    ("dc", xmp_namespaces::DC),
+    ("xmpMM", xmp_namespaces::XMP_MM),
+    ("photoshop", xmp_namespaces::PHOTOSHOP),
+    ("tiff", xmp_namespaces::TIFF),
+    ("exif", xmp_namespaces::EXIF),
+    ("HDRGainMap", xmp_namespaces::APPLE_HDR_GAIN_MAP),
+    ("hdrgm", xmp_namespaces::HDR_GAIN_MAP),
+];
+
+/// Value of XMP property.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XmpValue {
+    Simple(String),
+    /// Unordered array (`rdf:Bag`).
+    Bag(Vec<String>),
+    /// Ordered array (`rdf:Seq`).
+    Seq(Vec<String>),
+    /// Alternative texts in different languages (`rdf:Alt`).
+    /// Each item is a pair of the language (value of `xml:lang`) and the text.
+    LangAlt(Vec<(String, String)>),
+    /// Value that isn't supported by the model, like a structure
+    /// or a resource reference. It is written back as is.
+    Raw {
+        /// XML of the whole property element.
+        xml: String,
+        /// Prefixes and URIs of namespaces used by `xml`
+        /// and declared outside of it.
+        namespaces: Vec<(String, String)>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmpProperty {
+    /// URI of the namespace.
+    pub namespace: String,
+    pub name: String,
+    pub value: XmpValue,
+}
+
+/// Minimal model of XMP packet.
+///
+/// Only simple properties, arrays of simple values and
+/// language alternatives are supported. Other properties, like
+/// structures and resource references, are kept as [`XmpValue::Raw`]
+/// values and written back unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Xmp {
+    /// Prefixes and URIs of namespaces.
+    namespaces: Vec<(String, String)>,
+    properties: Vec<XmpProperty>,
+}
+
+impl Xmp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses XMP packet.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let text = std::str::from_utf8(data).map_err(|e| invalid_xmp(&e.to_string()))?;
+        let text = text.trim_start_matches('\u{feff}').trim_end_matches('\0');
+        let document = roxmltree::Document::parse(text).map_err(|e| invalid_xmp(&e.to_string()))?;
+        let mut xmp = Self::new();
+        // Descriptions nested into values of properties are parts of these values.
+        let descriptions = document.descendants().filter(|node| {
+            node.is_element()
+                && is_rdf(node, "Description")
+                && node
+                    .parent_element()
+                    .is_some_and(|parent| is_rdf(&parent, "RDF"))
+        });
+        for description in descriptions {
+            for attribute in description.attributes() {
+                let Some(namespace) = attribute.namespace() else {
+                    continue;
+                };
+                if namespace == RDF || namespace == XML {
+                    continue;
+                }
+                xmp.add_parsed_namespace(description.lookup_prefix(namespace), namespace);
+                xmp.push_property(
+                    namespace,
+                    attribute.name(),
+                    XmpValue::Simple(attribute.value().to_string()),
+                );
+            }
+            for element in description.children().filter(|node| node.is_element()) {
+                let tag_name = element.tag_name();
+                let Some(namespace) = tag_name.namespace() else {
+                    continue;
+                };
+                let value = parse_value(&element).unwrap_or_else(|| raw_value(text, &element));
+                xmp.add_parsed_namespace(element.lookup_prefix(namespace), namespace);
+                xmp.push_property(namespace, tag_name.name(), value);
+            }
+        }
+        Ok(xmp)
+    }
+
+    fn add_parsed_namespace(&mut self, prefix: Option<&str>, namespace: &str) {
+        if self.namespaces.iter().any(|(_, uri)| uri == namespace) {
+            return;
+        }
+        match prefix {
+            Some(prefix) if !self.namespaces.iter().any(|(p, _)| p == prefix) => self
+                .namespaces
+                .push((prefix.to_string(), namespace.to_string())),
+            _ => {
+                self.prefix(namespace);
+            }
+        }
+    }
+
+    fn push_property(&mut self, namespace: &str, name: &str, value: XmpValue) {
+        let existing = self
+            .properties
+            .iter_mut()
+            .find(|p| p.namespace == namespace && p.name == name);
+        match existing {
+            Some(property) => property.value = value,
+            None => self.properties.push(XmpProperty {
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+                value,
+            }),
+        }
+    }
+
+    /// Returns prefix of the namespace. A new prefix is registered
+    /// if the namespace is unknown.
+    fn prefix(&mut self, namespace: &str) -> &str {
+        let index = match self.namespaces.iter().position(|(_, uri)| uri == namespace) {
+            Some(index) => index,
+            None => {
+                let is_free = |prefix: &str| !self.namespaces.iter().any(|(p, _)| p == prefix);
+                let prefix = KNOWN_PREFIXES
+                    .iter()
+                    .find(|(prefix, uri)| *uri == namespace && is_free(prefix))
+                    .map(|(prefix, _)| prefix.to_string())
+                    .unwrap_or_else(|| {
+                        (1..)
+                            .map(|i| format!("ns{}", i))
+                            .find(|prefix| is_free(prefix))
+                            .unwrap_or_default()
+                    });
+                self.namespaces.push((prefix, namespace.to_string()));
+                self.namespaces.len() - 1
+            }
+        };
+        &self.namespaces[index].0
+    }
+
+    /// Prefixes and URIs of namespaces used in the packet.
+    pub fn namespaces(&self) -> &[(String, String)] {
+        &self.namespaces
+    }
+
+    /// Sets the prefix used for the namespace while serializing.
+    pub fn register_namespace(&mut self, prefix: &str, namespace: &str) -> Result<()> {
+        check_name(prefix)?;
+        if self
+            .namespaces
+            .iter()
+            .any(|(p, uri)| p == prefix && uri != namespace)
+        {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::InvalidParameterValue,
+                message: format!("Prefix '{}' is used by another namespace", prefix),
+            });
+        }
+        self.namespaces.retain(|(_, uri)| uri != namespace);
+        self.namespaces
+            .push((prefix.to_string(), namespace.to_string()));
+        Ok(())
+    }
+
+    pub fn properties(&self) -> &[XmpProperty] {
+        &self.properties
+    }
+
+    pub fn property(&self, namespace: &str, name: &str) -> Option<&XmpValue> {
+        self.properties
+            .iter()
+            .find(|p| p.namespace == namespace && p.name == name)
+            .map(|p| &p.value)
+    }
+
+    /// Adds or replaces the property.
+    ///
+    /// A prefix for unknown namespace is chosen automatically,
+    /// use [`Xmp::register_namespace()`] to set another one.
+    pub fn set_property(&mut self, namespace: &str, name: &str, value: XmpValue) -> Result<()> {
+        check_name(name)?;
+        if namespace.is_empty() {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::InvalidParameterValue,
+                message: "Namespace of XMP property is empty".to_string(),
+            });
+        }
+        self.prefix(namespace);
+        self.push_property(namespace, name, value);
+        Ok(())
+    }
+
+    pub fn remove_property(&mut self, namespace: &str, name: &str) -> Option<XmpValue> {
+        let index = self
+            .properties
+            .iter()
+            .position(|p| p.namespace == namespace && p.name == name)?;
+        Some(self.properties.remove(index).value)
+    }
+
+    fn simple_value(&self, namespace: &str, name: &str) -> Option<&str> {
+        match self.property(namespace, name)? {
+            XmpValue::Simple(value) => Some(value.trim()),
+            _ => None,
+        }
+    }
+
+    /// Rating of the image (`xmp:Rating`). Value -1 means "rejected",
+    /// 0 - "unrated", values from 1 to 5 are ratings.
+    pub fn rating(&self) -> Option<i32> {
+        let value = self.simple_value(xmp_namespaces::XMP, "Rating")?;
+        value
+            .parse::<i32>()
+            .ok()
+            .or_else(|| value.parse::<f64>().ok().map(|v| v as i32))
+    }
+
+    pub fn set_rating(&mut self, rating: i32) {
+        self.prefix(xmp_namespaces::XMP);
+        self.push_property(
+            xmp_namespaces::XMP,
+            "Rating",
+            XmpValue::Simple(rating.to_string()),
+        );
+    }
+
+    /// Keywords of the image (`dc:subject`).
+    pub fn keywords(&self) -> Vec<&str> {
+        match self.property(xmp_namespaces::DC, "subject") {
+            Some(XmpValue::Bag(values) | XmpValue::Seq(values)) => {
+                values.iter().map(|v| v.as_str()).collect()
+            }
+            Some(XmpValue::Simple(value)) => vec![value.as_str()],
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn set_keywords<I, S>(&mut self, keywords: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.prefix(xmp_namespaces::DC);
+        let keywords = keywords.into_iter().map(|k| k.into()).collect();
+        self.push_property(xmp_namespaces::DC, "subject", XmpValue::Bag(keywords));
+    }
+
+    /// Version of Apple's HDR gain map (`HDRGainMap:HDRGainMapVersion`).
+    pub fn apple_hdr_gain_map_version(&self) -> Option<u32> {
+        self.simple_value(xmp_namespaces::APPLE_HDR_GAIN_MAP, "HDRGainMapVersion")?
+            .parse()
+            .ok()
+    }
+
+    /// Headroom of Apple's HDR gain map (`HDRGainMap:HDRGainMapHeadroom`).
+    pub fn apple_hdr_gain_map_headroom(&self) -> Option<f32> {
+        self.simple_value(xmp_namespaces::APPLE_HDR_GAIN_MAP, "HDRGainMapHeadroom")?
+            .parse()
+            .ok()
+    }
+
+    /// Serializes the model into XMP packet that can be stored with help of
+    /// [`HeifContext::add_xmp_metadata()`](crate::HeifContext::add_xmp_metadata).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let prefix = |namespace: &str| {
+            self.namespaces
+                .iter()
+                .find(|(_, uri)| uri == namespace)
+                .map(|(p, _)| p.as_str())
+                .unwrap_or_default()
+        };
+        let mut xml = String::new();
+        xml.push_str("<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n");
+        xml.push_str("<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n");
+        xml.push_str(" <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n");
+        xml.push_str("  <rdf:Description rdf:about=\"\"");
+        let is_raw = |property: &XmpProperty| matches!(property.value, XmpValue::Raw { .. });
+        for (prefix, uri) in &self.namespaces {
+            if self
+                .properties
+                .iter()
+                .any(|p| &p.namespace == uri && !is_raw(p))
+            {
+                let _ = write!(xml, "\n    xmlns:{}=\"{}\"", prefix, escape(uri));
+            }
+        }
+        xml.push_str(">\n");
+        for property in self.properties.iter().filter(|p| !is_raw(p)) {
+            let name = format!("{}:{}", prefix(&property.namespace), property.name);
+            match &property.value {
+                XmpValue::Simple(value) => {
+                    let _ = writeln!(xml, "   <{0}>{1}</{0}>", name, escape(value));
+                }
+                XmpValue::Bag(values) | XmpValue::Seq(values) => {
+                    let array = match property.value {
+                        XmpValue::Bag(_) => "rdf:Bag",
+                        _ => "rdf:Seq",
+                    };
+                    let _ = writeln!(xml, "   <{}>\n    <{}>", name, array);
+                    for value in values {
+                        let _ = writeln!(xml, "     <rdf:li>{}</rdf:li>", escape(value));
+                    }
+                    let _ = writeln!(xml, "    </{}>\n   </{}>", array, name);
+                }
+                XmpValue::LangAlt(values) => {
+                    let _ = writeln!(xml, "   <{}>\n    <rdf:Alt>", name);
+                    for (lang, value) in values {
+                        let _ = writeln!(
+                            xml,
+                            "     <rdf:li xml:lang=\"{}\">{}</rdf:li>",
+                            escape(lang),
+                            escape(value)
+                        );
+                    }
+                    let _ = writeln!(xml, "    </rdf:Alt>\n   </{}>", name);
+                }
+                XmpValue::Raw { .. } => {}
+            }
+        }
+        xml.push_str("  </rdf:Description>\n");
+        // Every raw value is written into a separate description
+        // with the namespaces it was parsed with.
+        for property in &self.properties {
+            if let XmpValue::Raw {
+                xml: raw,
+                namespaces,
+            } = &property.value
+            {
+                xml.push_str("  <rdf:Description rdf:about=\"\"");
+                for (prefix, uri) in namespaces {
+                    if prefix.is_empty() {
+                        let _ = write!(xml, "\n    xmlns=\"{}\"", escape(uri));
+                    } else {
+                        let _ = write!(xml, "\n    xmlns:{}=\"{}\"", prefix, escape(uri));
+                    }
+                }
+                let _ = writeln!(xml, ">\n   {}\n  </rdf:Description>", raw);
+            }
+        }
+        xml.push_str(" </rdf:RDF>\n</x:xmpmeta>\n");
+        xml.push_str("<?xpacket end=\"w\"?>");
+        xml.into_bytes()
+    }
+}
+
+fn is_rdf(node: &roxmltree::Node, name: &str) -> bool {
+    let tag_name = node.tag_name();
+    tag_name.namespace() == Some(RDF) && tag_name.name() == name
+}
+
+/// Returns `None` if the value can't be represented by the model without losses.
+fn parse_value(element: &roxmltree::Node) -> Option<XmpValue> {
+    // Resource references, structures and qualifiers in attributes.
+    if element.attributes().len() > 0 {
+        return None;
+    }
+    let mut children = element.children().filter(|node| node.is_element());
+    let Some(array) = children.next() else {
+        return Some(XmpValue::Simple(element.text().unwrap_or("").to_string()));
+    };
+    if children.next().is_some() {
+        return None;
+    }
+    let is_alt = is_rdf(&array, "Alt");
+    let is_simple_item = |node: roxmltree::Node| {
+        is_rdf(&node, "li")
+            && !node.children().any(|child| child.is_element())
+            && node
+                .attributes()
+                .all(|a| is_alt && a.namespace() == Some(XML) && a.name() == "lang")
+    };
+    let mut elements = array.children().filter(|node| node.is_element());
+    if array.attributes().len() > 0 || !elements.all(is_simple_item) {
+        return None;
+    }
+    let items = array.children().filter(|node| node.is_element());
+    let text = |item: roxmltree::Node| item.text().unwrap_or("").to_string();
+    if is_rdf(&array, "Bag") {
+        Some(XmpValue::Bag(items.map(text).collect()))
+    } else if is_rdf(&array, "Seq") {
+        Some(XmpValue::Seq(items.map(text).collect()))
+    } else if is_alt {
+        let values = items
+            .map(|item| {
+                let lang = item.attribute((XML, "lang")).unwrap_or("x-default");
+                (lang.to_string(), text(item))
+            })
+            .collect();
+        Some(XmpValue::LangAlt(values))
+    } else {
+        None
+    }
+}
+
+/// Keeps XML of the property element together with namespaces
+/// that are declared outside of it.
+fn raw_value(text: &str, element: &roxmltree::Node) -> XmpValue {
+    let mut used_namespaces = Vec::new();
+    for node in element.descendants().filter(|node| node.is_element()) {
+        let attributes = node.attributes().filter_map(|a| a.namespace());
+        for uri in node.tag_name().namespace().into_iter().chain(attributes) {
+            if uri != XML && !used_namespaces.contains(&uri) {
+                used_namespaces.push(uri);
+            }
+        }
+    }
+    let mut namespaces: Vec<(String, String)> = element
+        .namespaces()
+        .filter(|ns| used_namespaces.contains(&ns.uri()))
+        .map(|ns| (ns.name().unwrap_or("").to_string(), ns.uri().to_string()))
+        .collect();
+    namespaces.sort();
+    namespaces.dedup_by(|a, b| a.0 == b.0);
+    XmpValue::Raw {
+        xml: text[element.range()].to_string(),
+        namespaces,
+    }
+}
+
+/// Checks that the string is a valid XML name without colon.
+fn check_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let is_valid = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if is_valid {
+        Ok(())
+    } else {
+        Err(HeifError {
+            code: HeifErrorCode::UsageError,
+            sub_code: HeifErrorSubCode::InvalidParameterValue,
+            message: format!("Invalid XML name: '{}'", name),
+        })
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+fn invalid_xmp(message: &str) -> HeifError {
+    HeifError {
+        code: HeifErrorCode::InvalidInput,
+        sub_code: HeifErrorSubCode::Unspecified,
+        message: format!("Invalid XMP data: {}", message),
+    }
+}
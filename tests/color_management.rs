@@ -0,0 +1,97 @@
+#[cfg(feature = "lcms2")]
+mod color_management_tests {
+    use libheif_rs::{
+        color_profile_types, Channel, ColorProfile, ColorProfileRaw, ColorSpace, DecodingOptions,
+        HeifContext, Image, LibHeif, Result, RgbChroma, TargetColorProfile,
+    };
+
+    #[test]
+    fn transform_image_to_display_p3() -> Result<()> {
+        let pixels = [
+            255, 255, 255, // white
+            255, 0, 0, // red
+            0, 0, 0, // black
+            0, 255, 0, // green
+        ];
+        let image = Image::from_rgb8(2, 2, &pixels)?;
+
+        let p3_image = image.transform_to_profile(&TargetColorProfile::DisplayP3)?;
+        assert_eq!(p3_image.width(), 2);
+        assert_eq!(p3_image.height(), 2);
+        assert_eq!(
+            p3_image.color_space(),
+            Some(ColorSpace::Rgb(RgbChroma::Rgb))
+        );
+        let profile = p3_image.color_profile_raw().unwrap();
+        assert_eq!(profile.profile_type(), color_profile_types::PROF);
+        assert!(!profile.data.is_empty());
+
+        let planes = p3_image.planes();
+        let plane = planes.interleaved.unwrap();
+        let white = plane.pixel(0, 0).unwrap();
+        assert!(white.iter().all(|&v| v >= 254), "{:?}", white);
+        let black = plane.pixel(0, 1).unwrap();
+        assert!(black.iter().all(|&v| v <= 1), "{:?}", black);
+        // sRGB red is less saturated in the wider Display P3 gamut.
+        let red = plane.pixel(1, 0).unwrap();
+        assert!(red[0] < 255 && red[1] > 0, "{:?}", red);
+
+        let target = TargetColorProfile::from(&profile);
+        assert_eq!(target, TargetColorProfile::Icc(profile.data.clone()));
+
+        // Source profile is taken from ICC profile of transformed image.
+        let srgb_image = p3_image.transform_to_profile(&TargetColorProfile::Srgb)?;
+        let planes = srgb_image.planes();
+        let red = planes.interleaved.unwrap().pixel(1, 0).unwrap().to_vec();
+        assert!(red[0] >= 253 && red[1] <= 2 && red[2] <= 2, "{:?}", red);
+
+        Ok(())
+    }
+
+    #[test]
+    fn transform_image_with_alpha() -> Result<()> {
+        let pixels = [255, 0, 0, 17, 0, 0, 255, 200];
+        let image = Image::from_rgba8(2, 1, &pixels)?;
+        let p3_image = image.transform_to_profile(&TargetColorProfile::DisplayP3)?;
+        assert_eq!(
+            p3_image.color_space(),
+            Some(ColorSpace::Rgb(RgbChroma::Rgba))
+        );
+        let planes = p3_image.planes();
+        let plane = planes.interleaved.unwrap();
+        assert_eq!(plane.pixel(0, 0).unwrap()[3], 17);
+        assert_eq!(plane.pixel(1, 0).unwrap()[3], 200);
+        Ok(())
+    }
+
+    #[test]
+    fn transform_to_invalid_icc_profile() -> Result<()> {
+        let image = Image::from_rgb8(1, 1, &[1, 2, 3])?;
+        let profile = ColorProfileRaw::new(color_profile_types::PROF, vec![1, 2, 3]);
+        let result = image.transform_to_profile(&TargetColorProfile::from(&profile));
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_into_color_profile() -> Result<()> {
+        let lib_heif = LibHeif::new();
+        let ctx = HeifContext::read_from_file("./data/test.heif")?;
+        let handle = ctx.primary_image_handle()?;
+
+        let mut options = DecodingOptions::new().unwrap();
+        options.set_output_color_profile(Some(TargetColorProfile::DisplayP3));
+        assert_eq!(
+            options.output_color_profile(),
+            Some(&TargetColorProfile::DisplayP3)
+        );
+        let image = lib_heif.decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), Some(options))?;
+        assert_eq!(image.color_space(), Some(ColorSpace::Rgb(RgbChroma::Rgb)));
+        assert_eq!(image.width(), handle.width());
+        assert_eq!(image.bits_per_pixel(Channel::Interleaved), Some(8));
+        let profile = image.color_profile_raw().unwrap();
+        assert_eq!(profile.profile_type(), color_profile_types::PROF);
+
+        Ok(())
+    }
+}
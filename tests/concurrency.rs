@@ -0,0 +1,81 @@
+use std::thread;
+
+use rayon::prelude::*;
+
+use libheif_rs::{ColorSpace, HeifContext, ImageHandle, LibHeif, Result, RgbChroma};
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn send_and_sync() {
+    assert_send_sync::<HeifContext>();
+    assert_send_sync::<ImageHandle>();
+    assert_send_sync::<libheif_rs::DecodingOptions>();
+    #[cfg(feature = "v1_18")]
+    assert_send_sync::<libheif_rs::regions::RegionItem>();
+    #[cfg(feature = "v1_20")]
+    assert_send_sync::<libheif_rs::Track>();
+
+    fn assert_send<T: Send>() {}
+    assert_send::<libheif_rs::Encoder>();
+}
+
+fn decode(lib_heif: &LibHeif, handle: &ImageHandle) -> Result<(u32, u32)> {
+    let image = lib_heif.decode(handle, ColorSpace::Rgb(RgbChroma::Rgb), None)?;
+    Ok((image.width(), image.height()))
+}
+
+#[test]
+fn decode_handles_in_parallel() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    let ctx = HeifContext::read_from_file("./data/collection.heif")?;
+    let handles = ctx.image_handles()?;
+    assert_eq!(handles.len(), 4);
+
+    let sizes: Vec<(u32, u32)> = handles
+        .into_par_iter()
+        .map(|handle| decode(&lib_heif, &handle))
+        .collect::<Result<_>>()?;
+    assert_eq!(sizes, [(480, 360); 4]);
+    Ok(())
+}
+
+#[test]
+fn decode_from_shared_context() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    let ctx = HeifContext::read_from_file("./data/collection.heif")?;
+    let item_ids = ctx.image_ids();
+
+    let sizes: Vec<(u32, u32)> = item_ids
+        .par_iter()
+        .map(|&item_id| decode(&lib_heif, &ctx.image_handle(item_id)?))
+        .collect::<Result<_>>()?;
+    assert_eq!(sizes, [(480, 360); 4]);
+    Ok(())
+}
+
+#[test]
+fn modify_context_while_decoding() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    let mut ctx = HeifContext::read_from_file("./data/collection.heif")?;
+    let handles = ctx.image_handles()?;
+    let primary = ctx.primary_image_handle()?;
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = handles
+            .iter()
+            .map(|handle| scope.spawn(|| decode(&lib_heif, handle)))
+            .collect();
+        for i in 0..10 {
+            let data = format!("<x:xmpmeta>{}</x:xmpmeta>", i);
+            ctx.add_xmp_metadata(&primary, data.as_bytes())?;
+        }
+        for worker in workers {
+            assert_eq!(worker.join().unwrap()?, (480, 360));
+        }
+        Ok::<_, libheif_rs::HeifError>(())
+    })?;
+
+    assert_eq!(primary.number_of_metadata_blocks(b"mime"), 10);
+    Ok(())
+}
@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+use std::ffi::CStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use libheif_rs::{
+    Channel, Chroma, ColorSpace, CompressionFormat, DecoderPlugin, DecodingOptions, HeifContext,
+    HeifError, HeifErrorCode, HeifErrorSubCode, Image, LibHeif, Result,
+};
+
+const WIDTH: u32 = 1652;
+const HEIGHT: u32 = 1791;
+
+static PUSHED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Decoder that ignores coded data and returns a gray image for every frame.
+#[derive(Default)]
+struct StubDecoder {
+    pushed_bytes: usize,
+    frames: VecDeque<usize>,
+}
+
+impl DecoderPlugin for StubDecoder {
+    const NAME: &'static CStr = c"Stub decoder";
+    const ID: &'static CStr = c"stub";
+
+    fn supports_format(format: CompressionFormat) -> u32 {
+        // Low priority, so the decoder is used only if it is selected by ID.
+        match format {
+            CompressionFormat::Av1 => 1,
+            _ => 0,
+        }
+    }
+
+    fn push_data(&mut self, data: &[u8], user_data: usize) -> Result<()> {
+        self.pushed_bytes += data.len();
+        self.frames.push_back(user_data);
+        Ok(())
+    }
+
+    fn decode_image(&mut self) -> Result<Option<(Image, usize)>> {
+        let Some(user_data) = self.frames.pop_front() else {
+            return Ok(None);
+        };
+        PUSHED_BYTES.fetch_add(self.pushed_bytes, Ordering::SeqCst);
+        Ok(Some((gray_image(WIDTH, HEIGHT)?, user_data)))
+    }
+}
+
+fn gray_image(width: u32, height: u32) -> Result<Image> {
+    let mut image = Image::new(width, height, ColorSpace::YCbCr(Chroma::C420))?;
+    image.create_plane(Channel::Y, width, height, 8)?;
+    image.create_plane(Channel::Cb, width.div_ceil(2), height.div_ceil(2), 8)?;
+    image.create_plane(Channel::Cr, width.div_ceil(2), height.div_ceil(2), 8)?;
+    let planes = image.planes_mut();
+    for mut plane in [planes.y, planes.cb, planes.cr].into_iter().flatten() {
+        plane.rows_mut().for_each(|row| row.fill(42));
+    }
+    Ok(image)
+}
+
+/// Decoder that always fails.
+#[derive(Default)]
+struct FailingDecoder;
+
+impl DecoderPlugin for FailingDecoder {
+    const NAME: &'static CStr = c"Failing decoder";
+    const ID: &'static CStr = c"failing";
+
+    fn supports_format(format: CompressionFormat) -> u32 {
+        match format {
+            CompressionFormat::Av1 => 1,
+            _ => 0,
+        }
+    }
+
+    fn push_data(&mut self, _data: &[u8], _user_data: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn decode_image(&mut self) -> Result<Option<(Image, usize)>> {
+        Err(HeifError {
+            code: HeifErrorCode::DecoderPluginError,
+            sub_code: HeifErrorSubCode::Unspecified,
+            message: "Stub error".to_string(),
+        })
+    }
+}
+
+fn decoding_options(decoder_id: &str) -> Result<DecodingOptions> {
+    let mut options = DecodingOptions::new().unwrap();
+    options.set_decoder_id(Some(decoder_id))?;
+    Ok(options)
+}
+
+#[test]
+fn decode_with_plugin() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    lib_heif.register_decoder_plugin::<StubDecoder>()?;
+    // Second registration does nothing
+    lib_heif.register_decoder_plugin::<StubDecoder>()?;
+
+    let descriptors = lib_heif.decoder_descriptors(100, Some(CompressionFormat::Av1));
+    let stub_descriptors: Vec<_> = descriptors.iter().filter(|d| d.id() == "stub").collect();
+    assert_eq!(stub_descriptors.len(), 1);
+    assert_eq!(stub_descriptors[0].name(), "Stub decoder");
+
+    let ctx = HeifContext::read_from_file("./data/test.heif")?;
+    let handle = ctx.primary_image_handle()?;
+    let image = lib_heif.decode(
+        &handle,
+        ColorSpace::Undefined,
+        Some(decoding_options("stub")?),
+    )?;
+    assert_eq!(image.color_space(), Some(ColorSpace::YCbCr(Chroma::C420)));
+    assert_eq!(image.width(), WIDTH);
+    assert_eq!(image.height(), HEIGHT);
+    let y_plane = image.planes().y.unwrap();
+    assert!(y_plane.rows().all(|row| row.iter().all(|&v| v == 42)));
+    assert!(PUSHED_BYTES.load(Ordering::SeqCst) > 0);
+    Ok(())
+}
+
+#[test]
+fn error_of_plugin() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    lib_heif.register_decoder_plugin::<FailingDecoder>()?;
+
+    let ctx = HeifContext::read_from_file("./data/test.heif")?;
+    let handle = ctx.primary_image_handle()?;
+    let err = lib_heif
+        .decode(
+            &handle,
+            ColorSpace::Undefined,
+            Some(decoding_options("failing")?),
+        )
+        .err()
+        .unwrap();
+    assert!(matches!(err.code, HeifErrorCode::DecoderPluginError));
+    assert!(err.message.contains("Stub error"));
+    Ok(())
+}
+
+#[cfg(feature = "v1_21")]
+mod v1_21 {
+    use super::*;
+
+    /// Decoder of frames of `star-8bpc.avifs` that returns
+    /// images only after receiving of the next frame or flushing.
+    #[derive(Default)]
+    struct SequenceStubDecoder {
+        frames: VecDeque<usize>,
+        flushed: bool,
+    }
+
+    impl DecoderPlugin for SequenceStubDecoder {
+        const NAME: &'static CStr = c"Stub decoder of sequences";
+        const ID: &'static CStr = c"stub-sequence";
+
+        fn supports_format(format: CompressionFormat) -> u32 {
+            match format {
+                CompressionFormat::Av1 => 1,
+                _ => 0,
+            }
+        }
+
+        fn push_data(&mut self, _data: &[u8], user_data: usize) -> Result<()> {
+            self.frames.push_back(user_data);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            self.flushed = true;
+            Ok(())
+        }
+
+        fn decode_image(&mut self) -> Result<Option<(Image, usize)>> {
+            if self.frames.len() < 2 && !self.flushed {
+                return Ok(None);
+            }
+            match self.frames.pop_front() {
+                Some(user_data) => Ok(Some((gray_image(159, 159)?, user_data))),
+                None => Ok(None),
+            }
+        }
+    }
+
+    #[test]
+    fn decode_sequence_with_plugin() -> Result<()> {
+        let lib_heif = LibHeif::new();
+        lib_heif.register_decoder_plugin::<SequenceStubDecoder>()?;
+
+        let ctx = HeifContext::read_from_file("./data/star-8bpc.avifs")?;
+        let track = ctx.track(0).unwrap();
+        let mut durations = Vec::new();
+        loop {
+            let options = decoding_options("stub-sequence")?;
+            match track.decode_next_image(ColorSpace::Undefined, Some(options)) {
+                Ok(image) => durations.push(image.duration()),
+                Err(err) if matches!(err.code, HeifErrorCode::EndOfSequence) => break,
+                Err(err) => return Err(err),
+            }
+        }
+        // Every frame is decoded once and has timing of its own sample.
+        assert_eq!(durations, [1024; 5]);
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use libheif_rs::{
+    Channel, ColorSpace, CompressionFormat, HeifContext, HeifErrorCode, Image, ImageHandle, ItemId,
+    LibHeif, Result, RgbChroma,
+};
+
+fn exif_ids(handle: &ImageHandle) -> Vec<ItemId> {
+    let mut item_ids = vec![0; 2];
+    let count = handle.metadata_block_ids(&mut item_ids, b"Exif");
+    item_ids.truncate(count);
+    item_ids
+}
+
+fn decode_primary(context: &HeifContext) -> Result<(u32, u32)> {
+    let lib_heif = LibHeif::new();
+    let handle = context.primary_image_handle()?;
+    let image = lib_heif.decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)?;
+    Ok((image.width(), image.height()))
+}
+
+fn create_image(width: u32, height: u32) -> Result<Image> {
+    let mut image = Image::new(width, height, ColorSpace::Rgb(RgbChroma::Rgb))?;
+    image.create_plane(Channel::Interleaved, width, height, 24)?;
+    let planes = image.planes_mut();
+    let mut plane = planes.interleaved.unwrap();
+    plane.rows_mut().for_each(|row| row.fill(128));
+    Ok(image)
+}
+
+#[test]
+fn remove_metadata() -> Result<()> {
+    let mut context = HeifContext::edit_from_file("./data/test.heif")?;
+    let handle = context.primary_image_handle()?;
+    let exif_ids = exif_ids(&handle);
+    assert_eq!(exif_ids, [2]);
+
+    context.remove_metadata(&handle, exif_ids[0])?;
+    let buf = context.write_to_bytes()?;
+
+    let context = HeifContext::read_from_bytes(&buf)?;
+    let handle = context.primary_image_handle()?;
+    assert_eq!(handle.number_of_metadata_blocks(0), 0);
+    assert_eq!(handle.number_of_thumbnails(), 1);
+    assert_eq!(decode_primary(&context)?, (1652, 1791));
+    Ok(())
+}
+
+#[test]
+fn replace_metadata() -> Result<()> {
+    let mut context = HeifContext::edit_from_file("./data/test.heif")?;
+    let handle = context.primary_image_handle()?;
+    let new_exif = b"\0\0\0\0II*\0NewExif";
+    context.replace_metadata(&handle, 2, new_exif)?;
+    context.add_exif_metadata(&handle, b"MM\0*FakeExif")?;
+    context.add_xmp_metadata(&handle, b"<x:xmpmeta/>")?;
+    let buf = context.write_to_bytes()?;
+
+    let context = HeifContext::read_from_bytes(&buf)?;
+    let handle = context.primary_image_handle()?;
+    let exif_ids = exif_ids(&handle);
+    assert_eq!(exif_ids.len(), 2);
+    assert_eq!(handle.metadata(exif_ids[0])?, new_exif);
+    assert_eq!(handle.metadata(exif_ids[1])?, b"\0\0\0\0MM\0*FakeExif");
+
+    let mut item_ids = vec![0; 1];
+    assert_eq!(handle.metadata_block_ids(&mut item_ids, b"mime"), 1);
+    assert_eq!(handle.metadata(item_ids[0])?, b"<x:xmpmeta/>");
+    assert_eq!(
+        handle.metadata_content_type(item_ids[0]),
+        Some("application/rdf+xml")
+    );
+    assert_eq!(decode_primary(&context)?, (1652, 1791));
+    Ok(())
+}
+
+#[test]
+fn edit_errors() -> Result<()> {
+    let mut context = HeifContext::edit_from_file("./data/test.heif")?;
+    let handle = context.primary_image_handle()?;
+    // Thumbnail is not a metadata block of the image.
+    let err = context.remove_metadata(&handle, 3).unwrap_err();
+    assert!(matches!(err.code, HeifErrorCode::UsageError));
+
+    let mut context = HeifContext::read_from_file("./data/test.heif")?;
+    let handle = context.primary_image_handle()?;
+    let err = context.remove_metadata(&handle, 2).unwrap_err();
+    assert!(matches!(err.code, HeifErrorCode::UsageError));
+    Ok(())
+}
+
+#[test]
+fn remove_thumbnail() -> Result<()> {
+    let mut context = HeifContext::edit_from_file("./data/test.heif")?;
+    let handle = context.primary_image_handle()?;
+    let mut thumbnail_ids = vec![0; 1];
+    assert_eq!(handle.thumbnail_ids(&mut thumbnail_ids), 1);
+    let thumbnail = handle.thumbnail(thumbnail_ids[0])?;
+    let size = context.write_to_bytes()?.len();
+
+    context.remove_thumbnail(&handle, &thumbnail)?;
+    let buf = context.write_to_bytes()?;
+    assert!(buf.len() < size);
+
+    let context = HeifContext::read_from_bytes(&buf)?;
+    let handle = context.primary_image_handle()?;
+    assert_eq!(handle.number_of_thumbnails(), 0);
+    assert_eq!(exif_ids(&handle), [2]);
+    assert_eq!(decode_primary(&context)?, (1652, 1791));
+    Ok(())
+}
+
+#[test]
+fn remove_item() -> Result<()> {
+    let mut context = HeifContext::edit_from_file("./data/alpha.heif")?;
+    let handle = context.primary_image_handle()?;
+    assert!(handle.has_alpha_channel());
+    let primary_id = handle.item_id();
+    let err = context.remove_item(primary_id).unwrap_err();
+    assert!(matches!(err.code, HeifErrorCode::UsageError));
+
+    // Item with alpha channel.
+    context.remove_item(2)?;
+
+    let handle = context.primary_image_handle()?;
+    assert!(!handle.has_alpha_channel());
+    assert_eq!(decode_primary(&context)?, (256, 256));
+    Ok(())
+}
+
+#[test]
+fn encode_after_editing_of_new_context() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    let mut encoder = lib_heif.encoder_for_format(CompressionFormat::Av1)?;
+    let mut context = HeifContext::new()?;
+    let handle = context.encode_image(&create_image(64, 48)?, &mut encoder, None)?;
+    context.add_exif_metadata(&handle, b"MM\0*FakeExif")?;
+    let handle = context.primary_image_handle()?;
+    let exif_ids = exif_ids(&handle);
+    assert_eq!(exif_ids.len(), 1);
+
+    context.remove_metadata(&handle, exif_ids[0])?;
+    // The context isn't converted into editable one.
+    context.encode_image(&create_image(32, 32)?, &mut encoder, None)?;
+    let buf = context.write_to_bytes()?;
+
+    let context = HeifContext::read_from_bytes(&buf)?;
+    assert_eq!(context.image_ids().len(), 2);
+    let handle = context.primary_image_handle()?;
+    assert_eq!(handle.number_of_metadata_blocks(0), 0);
+    assert_eq!(decode_primary(&context)?, (64, 48));
+    Ok(())
+}
+
+#[test]
+fn encode_after_removing_of_thumbnail() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    let mut encoder = lib_heif.encoder_for_format(CompressionFormat::Av1)?;
+    let mut context = HeifContext::new()?;
+    let image = create_image(640, 480)?;
+    let handle = context.encode_image(&image, &mut encoder, None)?;
+    let thumbnail = context
+        .encode_thumbnail(&image, &handle, 64, &mut encoder, None)?
+        .unwrap();
+    assert_eq!(handle.number_of_thumbnails(), 1);
+
+    context.remove_thumbnail(&handle, &thumbnail)?;
+    context.encode_image(&create_image(32, 32)?, &mut encoder, None)?;
+    let buf = context.write_to_bytes()?;
+
+    let context = HeifContext::read_from_bytes(&buf)?;
+    assert_eq!(context.image_ids().len(), 2);
+    let handle = context.primary_image_handle()?;
+    assert_eq!(handle.number_of_thumbnails(), 0);
+    assert_eq!(decode_primary(&context)?, (640, 480));
+    Ok(())
+}
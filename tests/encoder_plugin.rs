@@ -0,0 +1,291 @@
+use std::collections::VecDeque;
+use std::ffi::CStr;
+
+use libheif_rs::{
+    Channel, Chroma, ColorSpace, CompressionFormat, EncoderParameterInfo, EncoderParameterType,
+    EncoderParameterValue, EncoderPlugin, EncoderQuality, HeifContext, HeifError, HeifErrorCode,
+    HeifErrorSubCode, Image, LibHeif, Result, RgbChroma,
+};
+
+const FAKE_DATA: &[u8] = b"libheif-rs fake coded data";
+
+/// Encoder that returns the same coded data for any image.
+struct FakeEncoder {
+    quality: u8,
+    speed: i32,
+    tune: String,
+    chunks: VecDeque<Vec<u8>>,
+}
+
+impl Default for FakeEncoder {
+    fn default() -> Self {
+        Self {
+            quality: 50,
+            speed: 5,
+            tune: "ssim".to_string(),
+            chunks: VecDeque::new(),
+        }
+    }
+}
+
+impl EncoderPlugin for FakeEncoder {
+    const NAME: &'static CStr = c"Fake encoder";
+    const ID: &'static CStr = c"fake";
+    const FORMAT: CompressionFormat = CompressionFormat::Jpeg;
+    // The lowest priority, so the encoder is used only if it is selected by ID.
+    const PRIORITY: u32 = 0;
+    const SUPPORTS_LOSSY_COMPRESSION: bool = true;
+    const SUPPORTS_LOSSLESS_COMPRESSION: bool = false;
+
+    fn parameters() -> Vec<EncoderParameterInfo> {
+        vec![
+            EncoderParameterInfo {
+                name: "speed".to_string(),
+                parameter_type: EncoderParameterType::Int,
+                default_value: Some(EncoderParameterValue::Int(5)),
+                minimum: Some(0),
+                maximum: Some(9),
+                valid_int_values: vec![],
+                valid_string_values: vec![],
+            },
+            EncoderParameterInfo {
+                name: "tune".to_string(),
+                parameter_type: EncoderParameterType::String,
+                default_value: Some(EncoderParameterValue::String("ssim".to_string())),
+                minimum: None,
+                maximum: None,
+                valid_int_values: vec![],
+                valid_string_values: vec!["psnr".to_string(), "ssim".to_string()],
+            },
+            // Default value of the parameter can't be read.
+            EncoderParameterInfo {
+                name: "threads".to_string(),
+                parameter_type: EncoderParameterType::Int,
+                default_value: Some(EncoderParameterValue::Int(1)),
+                minimum: None,
+                maximum: None,
+                valid_int_values: vec![],
+                valid_string_values: vec![],
+            },
+        ]
+    }
+
+    fn quality(&self) -> u8 {
+        self.quality
+    }
+
+    fn set_quality(&mut self, quality: u8) -> Result<()> {
+        self.quality = quality;
+        Ok(())
+    }
+
+    fn lossless(&self) -> bool {
+        false
+    }
+
+    fn set_lossless(&mut self, lossless: bool) -> Result<()> {
+        if lossless {
+            return Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::InvalidParameterValue,
+                message: "Lossless compression is not supported".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn parameter(&self, name: &str) -> Result<EncoderParameterValue> {
+        match name {
+            "speed" => Ok(EncoderParameterValue::Int(self.speed)),
+            "tune" => Ok(EncoderParameterValue::String(self.tune.clone())),
+            _ => Err(HeifError {
+                code: HeifErrorCode::UsageError,
+                sub_code: HeifErrorSubCode::UnsupportedParameter,
+                message: format!("Parameter '{}' can't be read", name),
+            }),
+        }
+    }
+
+    fn set_parameter(&mut self, name: &str, value: EncoderParameterValue) -> Result<()> {
+        match value {
+            EncoderParameterValue::Int(v) if name == "speed" => self.speed = v,
+            EncoderParameterValue::Int(_) if name == "threads" => {}
+            EncoderParameterValue::String(v) => self.tune = v,
+            _ => unreachable!("Unexpected value of parameter '{}'", name),
+        }
+        Ok(())
+    }
+
+    fn input_color_space(&self, _color_space: ColorSpace) -> ColorSpace {
+        ColorSpace::YCbCr(Chroma::C420)
+    }
+
+    fn encode_image(&mut self, image: &Image) -> Result<()> {
+        assert_eq!(image.color_space(), Some(ColorSpace::YCbCr(Chroma::C420)));
+        assert!(image.has_channel(Channel::Y));
+        let (head, tail) = FAKE_DATA.split_at(10);
+        // Empty chunks are skipped
+        self.chunks = [head.to_vec(), vec![], tail.to_vec()].into();
+        Ok(())
+    }
+
+    fn compressed_data(&mut self) -> Result<Option<Vec<u8>>> {
+        Ok(self.chunks.pop_front())
+    }
+}
+
+/// Encoder with the same ID as [`FakeEncoder`].
+#[derive(Default)]
+struct OtherFakeEncoder(FakeEncoder);
+
+impl EncoderPlugin for OtherFakeEncoder {
+    const NAME: &'static CStr = c"Other fake encoder";
+    const ID: &'static CStr = FakeEncoder::ID;
+    const FORMAT: CompressionFormat = FakeEncoder::FORMAT;
+    const PRIORITY: u32 = 0;
+    const SUPPORTS_LOSSY_COMPRESSION: bool = true;
+    const SUPPORTS_LOSSLESS_COMPRESSION: bool = false;
+
+    fn quality(&self) -> u8 {
+        self.0.quality()
+    }
+
+    fn set_quality(&mut self, quality: u8) -> Result<()> {
+        self.0.set_quality(quality)
+    }
+
+    fn lossless(&self) -> bool {
+        self.0.lossless()
+    }
+
+    fn set_lossless(&mut self, lossless: bool) -> Result<()> {
+        self.0.set_lossless(lossless)
+    }
+
+    fn encode_image(&mut self, image: &Image) -> Result<()> {
+        self.0.encode_image(image)
+    }
+
+    fn compressed_data(&mut self) -> Result<Option<Vec<u8>>> {
+        self.0.compressed_data()
+    }
+}
+
+fn create_image(width: u32, height: u32) -> Result<Image> {
+    let mut image = Image::new(width, height, ColorSpace::Rgb(RgbChroma::Rgb))?;
+    image.create_plane(Channel::Interleaved, width, height, 24)?;
+    let planes = image.planes_mut();
+    let mut plane = planes.interleaved.unwrap();
+    plane.rows_mut().for_each(|row| row.fill(128));
+    Ok(image)
+}
+
+#[test]
+fn encoder_descriptors() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    lib_heif.register_encoder_plugin::<FakeEncoder>()?;
+    // Second registration does nothing
+    lib_heif.register_encoder_plugin::<FakeEncoder>()?;
+
+    let descriptors =
+        lib_heif.encoder_descriptors(100, Some(CompressionFormat::Jpeg), Some("fake"));
+    assert_eq!(descriptors.len(), 1);
+    let descriptor = &descriptors[0];
+    assert_eq!(descriptor.id(), "fake");
+    assert_eq!(descriptor.name(), "Fake encoder");
+    assert_eq!(descriptor.compression_format(), CompressionFormat::Jpeg);
+    assert!(descriptor.supports_lossy_compression());
+    assert!(!descriptor.supports_lossless_compression());
+
+    let err = lib_heif
+        .register_encoder_plugin::<OtherFakeEncoder>()
+        .unwrap_err();
+    assert!(matches!(err.sub_code, HeifErrorSubCode::PluginLoadingError));
+    let descriptors =
+        lib_heif.encoder_descriptors(100, Some(CompressionFormat::Jpeg), Some("fake"));
+    assert_eq!(descriptors.len(), 1);
+    assert_eq!(descriptors[0].name(), "Fake encoder");
+    Ok(())
+}
+
+#[test]
+fn encoder_parameters() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    lib_heif.register_encoder_plugin::<FakeEncoder>()?;
+    let mut descriptors =
+        lib_heif.encoder_descriptors(1, Some(CompressionFormat::Jpeg), Some("fake"));
+    let mut encoder = lib_heif.encoder(descriptors.pop().unwrap())?;
+    assert_eq!(encoder.id().as_deref(), Some("fake"));
+
+    let mut names = encoder.parameters_names();
+    names.sort();
+    assert_eq!(names, ["speed", "threads", "tune"]);
+
+    let speed = encoder.parameter_info("speed").unwrap();
+    assert_eq!(speed.parameter_type, EncoderParameterType::Int);
+    assert_eq!(speed.default_value, Some(EncoderParameterValue::Int(5)));
+    assert_eq!(speed.minimum, Some(0));
+    assert_eq!(speed.maximum, Some(9));
+
+    let tune = encoder.parameter_info("tune").unwrap();
+    assert_eq!(tune.parameter_type, EncoderParameterType::String);
+    assert_eq!(
+        tune.default_value,
+        Some(EncoderParameterValue::String("ssim".to_string()))
+    );
+    assert_eq!(tune.valid_string_values, ["psnr", "ssim"]);
+
+    // Error of reading of default value isn't an error of creating encoder
+    let threads = encoder.parameter_info("threads").unwrap();
+    assert_eq!(threads.parameter_type, EncoderParameterType::Int);
+    assert_eq!(threads.default_value, None);
+
+    encoder.set_parameter_value("speed", EncoderParameterValue::Int(8))?;
+    assert_eq!(
+        encoder.parameter("speed")?,
+        Some(EncoderParameterValue::Int(8))
+    );
+    encoder.set_parameter_value("tune", EncoderParameterValue::String("psnr".to_string()))?;
+    assert_eq!(
+        encoder.parameter("tune")?,
+        Some(EncoderParameterValue::String("psnr".to_string()))
+    );
+    let err = encoder
+        .set_parameter_value("speed", EncoderParameterValue::Int(10))
+        .unwrap_err();
+    assert!(matches!(
+        err.sub_code,
+        HeifErrorSubCode::InvalidParameterValue
+    ));
+    assert!(encoder
+        .set_parameter_value("unknown", EncoderParameterValue::Bool(true))
+        .is_err());
+
+    encoder.set_quality(EncoderQuality::Lossy(80))?;
+    let err = encoder.set_quality(EncoderQuality::LossLess).unwrap_err();
+    assert!(err
+        .message
+        .contains("Lossless compression is not supported"));
+    Ok(())
+}
+
+#[test]
+fn encode_with_plugin() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    lib_heif.register_encoder_plugin::<FakeEncoder>()?;
+    let mut descriptors =
+        lib_heif.encoder_descriptors(1, Some(CompressionFormat::Jpeg), Some("fake"));
+    let mut encoder = lib_heif.encoder(descriptors.pop().unwrap())?;
+
+    let image = create_image(64, 48)?;
+    let mut context = HeifContext::new()?;
+    let handle = context.encode_image(&image, &mut encoder, None)?;
+    assert_eq!(handle.width(), 64);
+    assert_eq!(handle.height(), 48);
+
+    let buf = context.write_to_bytes()?;
+    assert!(buf
+        .windows(FAKE_DATA.len())
+        .any(|window| window == FAKE_DATA));
+    Ok(())
+}
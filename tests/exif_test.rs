@@ -0,0 +1,97 @@
+#[cfg(feature = "exif")]
+mod exif_tests {
+    use std::io::Cursor;
+
+    use exif::experimental::Writer;
+    use exif::{Field, In, Rational, Tag, Value};
+    use libheif_rs::{ExifExt, GpsPosition, HeifContext, Result};
+
+    #[test]
+    fn read_exif() -> Result<()> {
+        let ctx = HeifContext::read_from_file("./data/test.heif")?;
+        let handle = ctx.primary_image_handle()?;
+        let exif = handle.exif()?.unwrap();
+        let model = exif.get_field(Tag::Model, In::PRIMARY).unwrap();
+        assert_eq!(model.display_value().to_string(), "\"iPhone 14 Pro\"");
+
+        assert_eq!(exif.orientation(), Some(1));
+        let time = exif.capture_time().unwrap();
+        assert_eq!(
+            (time.year, time.month, time.day),
+            (2023, 9, 3),
+            "invalid date"
+        );
+        assert_eq!(
+            (time.hour, time.minute, time.second),
+            (9, 28, 14),
+            "invalid time"
+        );
+        assert_eq!(time.nanosecond, Some(307_000_000));
+        assert_eq!(time.offset, Some(180));
+        assert_eq!(exif.gps_position(), None);
+
+        let ctx = HeifContext::read_from_file("./data/alpha.heif")?;
+        let handle = ctx.primary_image_handle()?;
+        assert!(handle.exif()?.is_none());
+        Ok(())
+    }
+
+    fn field(tag: Tag, value: Value) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        }
+    }
+
+    fn rationals(values: &[(u32, u32)]) -> Value {
+        Value::Rational(
+            values
+                .iter()
+                .map(|&(num, denom)| Rational { num, denom })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn read_gps_position() -> Result<()> {
+        let fields = [
+            field(Tag::Orientation, Value::Short(vec![6])),
+            field(Tag::GPSLatitudeRef, Value::Ascii(vec![b"S".to_vec()])),
+            field(
+                Tag::GPSLatitude,
+                rationals(&[(33, 1), (51, 1), (3600, 100)]),
+            ),
+            field(Tag::GPSLongitudeRef, Value::Ascii(vec![b"E".to_vec()])),
+            field(Tag::GPSLongitude, rationals(&[(151, 1), (12, 1), (0, 1)])),
+            field(Tag::GPSAltitudeRef, Value::Byte(vec![1])),
+            field(Tag::GPSAltitude, rationals(&[(125, 10)])),
+        ];
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut tiff = Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        let mut exif_data = vec![0; 4];
+        exif_data.extend(tiff.into_inner());
+
+        let mut ctx = HeifContext::edit_from_file("./data/test.heif")?;
+        let handle = ctx.primary_image_handle()?;
+        ctx.replace_metadata(&handle, 2, &exif_data)?;
+        let handle = ctx.primary_image_handle()?;
+        let exif = handle.exif()?.unwrap();
+
+        assert_eq!(exif.orientation(), Some(6));
+        assert!(exif.capture_time().is_none());
+        let GpsPosition {
+            latitude,
+            longitude,
+            altitude,
+        } = exif.gps_position().unwrap();
+        assert!((latitude + 33.86).abs() < 1e-9);
+        assert!((longitude - 151.2).abs() < 1e-9);
+        assert_eq!(altitude, Some(-12.5));
+        Ok(())
+    }
+}
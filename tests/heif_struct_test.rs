@@ -1,6 +1,8 @@
 use std::collections::HashSet;
 
-use libheif_rs::LibHeif;
+use libheif_rs::{
+    ColorPrimaries, ColorProfileNCLX, LibHeif, MatrixCoefficients, TransferCharacteristics,
+};
 
 #[test]
 fn get_version() {
@@ -53,3 +55,72 @@ fn get_decoder_descriptors() {
     let descriptors = lib_heif.decoder_descriptors(100, None);
     assert!(descriptors.len() >= 2);
 }
+
+#[test]
+fn nclx_profile_presets() {
+    let srgb = ColorProfileNCLX::srgb().unwrap();
+    assert_eq!(srgb.color_primaries(), ColorPrimaries::ITU_R_BT_709_5);
+    assert_eq!(
+        srgb.transfer_characteristics(),
+        TransferCharacteristics::IEC_61966_2_1
+    );
+    assert_eq!(
+        srgb.matrix_coefficients(),
+        MatrixCoefficients::ITU_R_BT_601_6
+    );
+    assert_eq!(srgb.full_range_flag(), 1);
+    assert_eq!(srgb.color_primary_red_x(), 0.64);
+    assert_eq!(srgb.color_primary_white_y(), 0.329);
+    assert!(srgb == ColorProfileNCLX::new().unwrap());
+
+    let rec709 = ColorProfileNCLX::rec709().unwrap();
+    assert_eq!(
+        rec709.matrix_coefficients(),
+        MatrixCoefficients::ITU_R_BT_709_5
+    );
+    assert_eq!(rec709.full_range_flag(), 0);
+    assert!(rec709 != srgb);
+
+    let p3 = ColorProfileNCLX::display_p3().unwrap();
+    assert_eq!(p3.color_primaries(), ColorPrimaries::SMPTE_EG_432_1);
+    assert_eq!(p3.color_primary_red_x(), 0.68);
+    assert_eq!(p3.color_primary_green_y(), 0.69);
+
+    let pq = ColorProfileNCLX::rec2100_pq().unwrap();
+    assert_eq!(
+        pq.color_primaries(),
+        ColorPrimaries::ITU_R_BT_2020_2_and_2100_0
+    );
+    assert_eq!(
+        pq.transfer_characteristics(),
+        TransferCharacteristics::ITU_R_BT_2100_0_PQ
+    );
+    assert_eq!(
+        pq.matrix_coefficients(),
+        MatrixCoefficients::ITU_R_BT_2020_2_NonConstantLuminance
+    );
+    assert_eq!(pq.color_primary_red_x(), 0.708);
+
+    let hlg = ColorProfileNCLX::rec2100_hlg().unwrap();
+    assert_eq!(
+        hlg.transfer_characteristics(),
+        TransferCharacteristics::ITU_R_BT_2100_0_HLG
+    );
+    assert!(hlg != pq);
+
+    let mut cloned = hlg.clone();
+    assert!(cloned == hlg);
+    cloned.set_transfer_characteristics(TransferCharacteristics::ITU_R_BT_2100_0_PQ);
+    assert!(cloned == pq);
+    cloned.set_full_range_flag(0);
+    cloned.set_color_primary_white_x(0.314);
+    assert_eq!(cloned.full_range_flag(), 0);
+    assert_eq!(cloned.color_primary_white_x(), 0.314);
+    assert!(cloned != pq);
+    // Unknown values are ignored
+    cloned.set_matrix_coefficients(MatrixCoefficients::Unknown);
+    assert_eq!(
+        cloned.matrix_coefficients(),
+        MatrixCoefficients::ITU_R_BT_2020_2_NonConstantLuminance
+    );
+}
@@ -0,0 +1,408 @@
+use libheif_rs::{
+    Channel, Chroma, ColorConversionOptions, ColorSpace, HeifContext, Image, LibHeif,
+    MirrorDirection, Rectangle, Result, RgbChroma, Rotation, ScalingFilter, ScalingOptions,
+};
+
+#[test]
+fn rows_and_pixels_of_plane() -> Result<()> {
+    let width = 5;
+    let height = 3;
+    let mut image = Image::new(width, height, ColorSpace::Rgb(RgbChroma::Rgb))?;
+    image.create_plane(Channel::Interleaved, width, height, 8)?;
+
+    let mut planes = image.planes_mut();
+    let plane = planes.interleaved.as_mut().unwrap();
+    assert_eq!(plane.row_size(), 15);
+    assert_eq!(plane.bytes_per_pixel(), 3);
+    for (y, row) in plane.rows_mut().enumerate() {
+        assert_eq!(row.len(), 15);
+        row.fill(y as u8);
+    }
+    plane.pixel_mut(4, 2).unwrap().copy_from_slice(&[1, 2, 3]);
+    assert!(plane.pixel_mut(5, 0).is_none());
+
+    let planes = image.planes();
+    let plane = planes.interleaved.unwrap();
+    let rows: Vec<&[u8]> = plane.rows().collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows[0].iter().all(|&v| v == 0));
+    assert!(rows[1].iter().all(|&v| v == 1));
+    assert_eq!(&rows[2][12..], &[1, 2, 3]);
+    assert_eq!(plane.pixel(1, 1), Some([1u8, 1, 1].as_slice()));
+    assert_eq!(plane.pixel(4, 2), Some([1u8, 2, 3].as_slice()));
+    assert!(plane.pixel(0, 3).is_none());
+    // Plane has 8-bit components
+    assert!(plane.as_u16_rows().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn u16_rows_of_plane() -> Result<()> {
+    let width = 4;
+    let height = 2;
+    for (chroma, big_endian) in [(RgbChroma::HdrRgbBe, true), (RgbChroma::HdrRgbLe, false)] {
+        let mut image = Image::new(width, height, ColorSpace::Rgb(chroma))?;
+        image.create_plane(Channel::Interleaved, width, height, 10)?;
+
+        let mut planes = image.planes_mut();
+        let plane = planes.interleaved.as_mut().unwrap();
+        for mut row in plane.as_u16_rows_mut()? {
+            assert_eq!(row.len(), 12);
+            let values: Vec<u16> = (0..12).map(|i| i * 85).collect();
+            row.copy_from_slice(&values);
+            row.set(11, 0x3ff);
+        }
+
+        let planes = image.planes();
+        let plane = planes.interleaved.unwrap();
+        let expected_pixel = if big_endian {
+            [0x02, 0xfd, 0x03, 0x52, 0x03, 0xff]
+        } else {
+            [0xfd, 0x02, 0x52, 0x03, 0xff, 0x03]
+        };
+        assert_eq!(plane.pixel(3, 1), Some(expected_pixel.as_slice()));
+        for row in plane.as_u16_rows()? {
+            assert_eq!(row.get(0), Some(0));
+            assert_eq!(row.get(10), Some(850));
+            assert_eq!(row.get(11), Some(0x3ff));
+            assert_eq!(row.get(12), None);
+            assert_eq!(row.to_vec().len(), 12);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn u16_rows_of_non_interleaved_plane() -> Result<()> {
+    let mut image = Image::new(3, 2, ColorSpace::Monochrome)?;
+    image.create_plane(Channel::Y, 3, 2, 12)?;
+
+    let mut planes = image.planes_mut();
+    let plane = planes.y.as_mut().unwrap();
+    for (y, mut row) in plane.as_u16_rows_mut()?.enumerate() {
+        for x in 0..row.len() {
+            row.set(x, (y * 1000 + x) as u16);
+        }
+    }
+
+    let planes = image.planes();
+    let plane = planes.y.unwrap();
+    let rows: Vec<Vec<u16>> = plane.as_u16_rows()?.map(|row| row.to_vec()).collect();
+    assert_eq!(rows, vec![vec![0, 1, 2], vec![1000, 1001, 1002]]);
+    assert_eq!(plane.pixel(2, 1), Some(1002u16.to_ne_bytes().as_slice()));
+
+    Ok(())
+}
+
+#[test]
+fn create_image_from_buffers() -> Result<()> {
+    let data: Vec<u8> = (0..4 * 3 * 3).map(|v| v as u8).collect();
+    let image = Image::from_rgb8(4, 3, &data)?;
+    assert_eq!(image.color_space(), Some(ColorSpace::Rgb(RgbChroma::Rgb)));
+    let planes = image.planes();
+    let plane = planes.interleaved.unwrap();
+    let rows: Vec<u8> = plane.rows().flatten().copied().collect();
+    assert_eq!(rows, data);
+    assert!(Image::from_rgb8(4, 3, &data[1..]).is_err());
+
+    let data: Vec<u8> = (0..4 * 3 * 4).map(|v| v as u8).collect();
+    let image = Image::from_rgba8(4, 3, &data)?;
+    assert_eq!(image.color_space(), Some(ColorSpace::Rgb(RgbChroma::Rgba)));
+    assert_eq!(
+        image.planes().interleaved.unwrap().pixel(1, 0),
+        Some(&data[4..8])
+    );
+
+    let data: Vec<u16> = (0..4 * 3 * 4).map(|v| v * 20).collect();
+    let image = Image::from_rgba16(4, 3, &data, 10)?;
+    assert_eq!(image.bits_per_pixel(Channel::Interleaved), Some(10));
+    let planes = image.planes();
+    let plane = planes.interleaved.unwrap();
+    let values: Vec<u16> = plane.as_u16_rows()?.flat_map(|row| row.to_vec()).collect();
+    assert_eq!(values, data);
+    assert!(Image::from_rgba16(4, 3, &data, 8).is_err());
+    assert!(Image::from_rgb16(4, 3, &data, 10).is_err());
+
+    let data = [1u8, 2, 3, 4, 5, 6];
+    let image = Image::from_luma8(3, 2, &data)?;
+    assert_eq!(image.color_space(), Some(ColorSpace::Monochrome));
+    assert_eq!(
+        image.planes().y.unwrap().pixel(0, 1),
+        Some([4u8].as_slice())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn create_image_from_yuv420_planes() -> Result<()> {
+    let y: Vec<u16> = (0..5 * 3).map(|v| v * 10).collect();
+    let u = vec![512u16; 3 * 2];
+    let v = vec![256u16; 3 * 2];
+    let image = Image::from_yuv420_planes(5, 3, &y, &u, &v, 10)?;
+    assert_eq!(image.color_space(), Some(ColorSpace::YCbCr(Chroma::C420)));
+    assert_eq!(image.channel_width(Channel::Cb), Some(3));
+    assert_eq!(image.channel_height(Channel::Cr), Some(2));
+    let planes = image.planes();
+    let values: Vec<u16> = planes
+        .y
+        .unwrap()
+        .as_u16_rows()?
+        .flat_map(|row| row.to_vec())
+        .collect();
+    assert_eq!(values, y);
+    let values: Vec<u16> = planes
+        .cr
+        .unwrap()
+        .as_u16_rows()?
+        .flat_map(|row| row.to_vec())
+        .collect();
+    assert_eq!(values, v);
+
+    // Wrong size of chroma plane
+    assert!(Image::from_yuv420_planes(5, 3, &y, &u[1..], &v, 10).is_err());
+    // 8-bit values
+    let y = vec![16u8; 4 * 4];
+    let uv = vec![128u8; 2 * 2];
+    assert!(Image::from_yuv420_planes(4, 4, &y, &uv, &uv, 8).is_ok());
+    assert!(Image::from_yuv420_planes(4, 4, &y, &uv, &uv, 10).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn scale_image_with_filters() -> Result<()> {
+    for filter in [
+        ScalingFilter::Bilinear,
+        ScalingFilter::Bicubic,
+        ScalingFilter::Lanczos3,
+    ] {
+        let mut options = ScalingOptions::new();
+        options.set_filter(filter);
+        assert_eq!(options.filter(), filter);
+
+        // Interleaved RGBA with constant color
+        let data: Vec<u8> = [10u8, 20, 30, 255].repeat(20 * 10);
+        let mut image = Image::from_rgba8(20, 10, &data)?;
+        image.set_pixel_aspect_ratio(2, 3);
+        let scaled = image.scale(7, 5, Some(options.clone()))?;
+        assert_eq!(scaled.width(), 7);
+        assert_eq!(scaled.height(), 5);
+        assert_eq!(scaled.color_space(), Some(ColorSpace::Rgb(RgbChroma::Rgba)));
+        assert_eq!(scaled.pixel_aspect_ratio(), (2, 3));
+        let planes = scaled.planes();
+        let plane = planes.interleaved.unwrap();
+        for row in plane.rows() {
+            assert_eq!(row, [10u8, 20, 30, 255].repeat(7).as_slice());
+        }
+
+        // YCbCr 4:2:0 with 10-bit planes
+        let y = vec![500u16; 9 * 7];
+        let uv = vec![300u16; 5 * 4];
+        let image = Image::from_yuv420_planes(9, 7, &y, &uv, &uv, 10)?;
+        let scaled = image.scale(17, 13, Some(options))?;
+        assert_eq!(scaled.color_space(), Some(ColorSpace::YCbCr(Chroma::C420)));
+        assert_eq!(scaled.bits_per_pixel(Channel::Y), Some(10));
+        assert_eq!(scaled.channel_width(Channel::Cb), Some(9));
+        assert_eq!(scaled.channel_height(Channel::Cr), Some(7));
+        let planes = scaled.planes();
+        for row in planes.y.unwrap().as_u16_rows()? {
+            assert!(row.iter().all(|v| v == 500));
+        }
+        for row in planes.cb.unwrap().as_u16_rows()? {
+            assert!(row.iter().all(|v| v == 300));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn scale_gradient_with_bilinear_filter() -> Result<()> {
+    let data: Vec<u8> = (0..4).map(|x| x * 60).collect();
+    let image = Image::from_luma8(4, 1, &data)?;
+    let mut options = ScalingOptions::new();
+    options.set_filter(ScalingFilter::Bilinear);
+    let scaled = image.scale(2, 1, Some(options))?;
+    let planes = scaled.planes();
+    let row = planes.y.unwrap().rows().next().unwrap().to_vec();
+    assert_eq!(row, vec![43, 137]);
+
+    Ok(())
+}
+
+fn luma_rows(image: &Image) -> Vec<Vec<u8>> {
+    let planes = image.planes();
+    let plane = planes.y.unwrap();
+    plane.rows().map(|row| row.to_vec()).collect()
+}
+
+#[test]
+fn crop_image() -> Result<()> {
+    // 1 2 3 4
+    // 5 6 7 8
+    // 9 10 11 12
+    let data: Vec<u8> = (1..=12).collect();
+    let mut image = Image::from_luma8(4, 3, &data)?;
+    image.set_pixel_aspect_ratio(4, 3);
+    let cropped = image.crop(Rectangle {
+        left: 1,
+        top: 1,
+        width: 2,
+        height: 2,
+    })?;
+    assert_eq!(luma_rows(&cropped), vec![vec![6, 7], vec![10, 11]]);
+    assert_eq!(cropped.pixel_aspect_ratio(), (4, 3));
+
+    let out_of_image = Rectangle {
+        left: 3,
+        top: 0,
+        width: 2,
+        height: 1,
+    };
+    assert!(image.crop(out_of_image).is_err());
+
+    // Position must be aligned to chroma subsampling.
+    let y = vec![0u8; 6 * 4];
+    let uv: Vec<u8> = (0..3 * 2).collect();
+    let image = Image::from_yuv420_planes(6, 4, &y, &uv, &uv, 8)?;
+    let rect = Rectangle {
+        left: 1,
+        top: 0,
+        width: 2,
+        height: 2,
+    };
+    assert!(image.crop(rect).is_err());
+    let cropped = image.crop(Rectangle { left: 2, ..rect })?;
+    assert_eq!(cropped.width(), 2);
+    assert_eq!(cropped.height(), 2);
+    let planes = cropped.planes();
+    let cb_plane = planes.cb.unwrap();
+    assert_eq!((cb_plane.width, cb_plane.height), (1, 1));
+    assert_eq!(cb_plane.pixel(0, 0), Some([1u8].as_slice()));
+
+    Ok(())
+}
+
+#[test]
+fn rotate_and_mirror_image() -> Result<()> {
+    // 1 2 3
+    // 4 5 6
+    let data: Vec<u8> = (1..=6).collect();
+    let mut image = Image::from_luma8(3, 2, &data)?;
+    image.set_pixel_aspect_ratio(2, 1);
+    image.set_premultiplied_alpha(true);
+
+    let rotated = image.rotate(Rotation::Rotate90Cw)?;
+    assert_eq!(
+        luma_rows(&rotated),
+        vec![vec![4, 1], vec![5, 2], vec![6, 3]]
+    );
+    assert_eq!(rotated.pixel_aspect_ratio(), (1, 2));
+    assert!(rotated.is_premultiplied_alpha());
+
+    let rotated = image.rotate(Rotation::Rotate180)?;
+    assert_eq!(luma_rows(&rotated), vec![vec![6, 5, 4], vec![3, 2, 1]]);
+    assert_eq!(rotated.pixel_aspect_ratio(), (2, 1));
+
+    let rotated = image.rotate(Rotation::Rotate270Cw)?;
+    assert_eq!(
+        luma_rows(&rotated),
+        vec![vec![3, 6], vec![2, 5], vec![1, 4]]
+    );
+
+    let mirrored = image.mirror(MirrorDirection::Horizontal)?;
+    assert_eq!(luma_rows(&mirrored), vec![vec![3, 2, 1], vec![6, 5, 4]]);
+
+    let mirrored = image.mirror(MirrorDirection::Vertical)?;
+    assert_eq!(luma_rows(&mirrored), vec![vec![4, 5, 6], vec![1, 2, 3]]);
+
+    // Interleaved pixels are moved as a whole.
+    let data: Vec<u8> = (0..2 * 3).collect();
+    let image = Image::from_rgb8(2, 1, &data)?;
+    let rotated = image.rotate(Rotation::Rotate90Cw)?;
+    assert_eq!((rotated.width(), rotated.height()), (1, 2));
+    let planes = rotated.planes();
+    let plane = planes.interleaved.unwrap();
+    assert_eq!(plane.pixel(0, 0), Some([0u8, 1, 2].as_slice()));
+    assert_eq!(plane.pixel(0, 1), Some([3u8, 4, 5].as_slice()));
+
+    Ok(())
+}
+
+#[test]
+fn convert_image_color_space() -> Result<()> {
+    let options = ColorConversionOptions::new();
+    let colors: [[u8; 3]; 4] = [[200, 100, 50], [0, 0, 0], [255, 255, 255], [10, 240, 128]];
+    let data: Vec<u8> = colors.repeat(2).concat();
+    let image = Image::from_rgb8(4, 2, &data)?;
+
+    // RGB -> YCbCr 4:4:4 -> RGB
+    let ycbcr = image.convert(ColorSpace::YCbCr(Chroma::C444), 8, &options)?;
+    assert_eq!(ycbcr.color_space(), Some(ColorSpace::YCbCr(Chroma::C444)));
+    let planes = ycbcr.planes();
+    assert_eq!(planes.y.unwrap().pixel(0, 0), Some([124u8].as_slice()));
+    assert_eq!(planes.cb.unwrap().pixel(1, 0), Some([128u8].as_slice()));
+    assert_eq!(planes.cr.unwrap().pixel(0, 1), Some([182u8].as_slice()));
+
+    let rgb = ycbcr.convert(ColorSpace::Rgb(RgbChroma::Rgb), 8, &options)?;
+    let planes = rgb.planes();
+    let plane = planes.interleaved.unwrap();
+    for (x, color) in colors.iter().enumerate() {
+        let pixel = plane.pixel(x as u32, 1).unwrap();
+        for (&v, &expected) in pixel.iter().zip(color) {
+            assert!(v.abs_diff(expected) <= 1, "{:?} != {:?}", pixel, color);
+        }
+    }
+
+    // RGB -> YCbCr 4:2:0 with 10 bits
+    let ycbcr = image.convert(ColorSpace::YCbCr(Chroma::C420), 10, &options)?;
+    assert_eq!(ycbcr.bits_per_pixel(Channel::Y), Some(10));
+    assert_eq!(ycbcr.channel_width(Channel::Cb), Some(2));
+    assert_eq!(ycbcr.channel_height(Channel::Cr), Some(1));
+
+    // RGB -> RGBA with 12 bits
+    let rgba = image.convert(ColorSpace::Rgb(RgbChroma::HdrRgbaLe), 12, &options)?;
+    let planes = rgba.planes();
+    let plane = planes.interleaved.unwrap();
+    let row = plane.as_u16_rows()?.next().unwrap();
+    assert_eq!(row.get(3), Some(4095));
+    assert_eq!(row.get(6), Some(0));
+    assert_eq!(row.get(7), Some(4095));
+
+    // RGB -> Monochrome
+    let gray = image.convert(ColorSpace::Monochrome, 8, &options)?;
+    let planes = gray.planes();
+    let plane = planes.y.unwrap();
+    assert_eq!(plane.pixel(1, 0), Some([0u8].as_slice()));
+    assert_eq!(plane.pixel(2, 0), Some([255u8].as_slice()));
+
+    // Unsupported bit depth
+    assert!(image
+        .convert(ColorSpace::Rgb(RgbChroma::Rgba), 10, &options)
+        .is_err());
+    assert!(image
+        .convert(ColorSpace::Rgb(RgbChroma::HdrRgbLe), 8, &options)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn convert_decoded_image() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    let ctx = HeifContext::read_from_file("./data/test.heif")?;
+    let handle = ctx.primary_image_handle()?;
+    let image = lib_heif.decode(&handle, ColorSpace::YCbCr(Chroma::C420), None)?;
+
+    let options = ColorConversionOptions::new();
+    let rgba = image.convert(ColorSpace::Rgb(RgbChroma::Rgba), 8, &options)?;
+    assert_eq!(rgba.width(), image.width());
+    assert_eq!(rgba.height(), image.height());
+    let ycbcr = image.convert(ColorSpace::YCbCr(Chroma::C444), 8, &options)?;
+    assert_eq!(ycbcr.channel_width(Channel::Cb), Some(image.width()));
+
+    Ok(())
+}
@@ -0,0 +1,101 @@
+#[cfg(feature = "rayon")]
+mod parallel_tests {
+    use std::sync::{Arc, Mutex};
+
+    use libheif_rs::{
+        ColorSpace, DecodingOptions, HeifContext, LibHeif, ProgressStep, Result, RgbChroma,
+    };
+
+    fn thread_pool() -> rayon::ThreadPool {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn decode_all() -> Result<()> {
+        let ctx = HeifContext::read_from_file("./data/collection.heif")?;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let handler_events = events.clone();
+        let mut dec_options = DecodingOptions::new().unwrap();
+        dec_options.set_progress_handler(move |step, current, max| {
+            handler_events.lock().unwrap().push((step, current, max));
+        });
+        let images = thread_pool()
+            .install(|| ctx.decode_all(ColorSpace::Rgb(RgbChroma::Rgb), Some(dec_options)));
+
+        assert_eq!(images.len(), 4);
+        for image in images {
+            let image = image?;
+            assert_eq!((image.width(), image.height()), (480, 360));
+        }
+        let events = events.lock().unwrap();
+        assert_eq!(
+            *events,
+            (0..=4)
+                .map(|i| (ProgressStep::Total, i, 4))
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "v1_19")]
+    #[test]
+    fn decode_tiles_parallel() -> Result<()> {
+        let lib_heif = LibHeif::new();
+        let ctx = HeifContext::read_from_file("./data/grid.heif")?;
+        let handle = ctx.primary_image_handle()?;
+        // Pixels are compared in the original color space, because
+        // conversion of separate tiles may differ at edges of tiles.
+        let color_space = ColorSpace::Undefined;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let handler_events = events.clone();
+        let mut dec_options = DecodingOptions::new().unwrap();
+        dec_options.set_progress_handler(move |step, current, max| {
+            handler_events.lock().unwrap().push((step, current, max));
+        });
+        let image = thread_pool()
+            .install(|| lib_heif.decode_tiles_parallel(&handle, color_space, Some(dec_options)))?;
+        let expected = lib_heif.decode(&handle, color_space, None)?;
+
+        assert_eq!(image.color_space(), expected.color_space());
+        assert_eq!(image.width(), expected.width());
+        assert_eq!(image.height(), expected.height());
+        let planes = image.planes();
+        let expected_planes = expected.planes();
+        for (plane, expected_plane) in [
+            (planes.y, expected_planes.y),
+            (planes.cb, expected_planes.cb),
+            (planes.cr, expected_planes.cr),
+        ] {
+            let (plane, expected_plane) = (plane.unwrap(), expected_plane.unwrap());
+            assert_eq!(plane.width, expected_plane.width);
+            assert!(plane.rows().eq(expected_plane.rows()));
+        }
+
+        let events = events.lock().unwrap();
+        let (step, current, num_tiles) = events[0];
+        assert_eq!((step, current), (ProgressStep::Total, 0));
+        assert!(num_tiles > 1);
+        assert_eq!(events.len(), num_tiles as usize + 1);
+        Ok(())
+    }
+
+    #[cfg(feature = "v1_19")]
+    #[test]
+    fn decode_tiles_parallel_without_grid() -> Result<()> {
+        let lib_heif = LibHeif::new();
+        let ctx = HeifContext::read_from_file("./data/test.heif")?;
+        let handle = ctx.primary_image_handle()?;
+
+        let image = thread_pool().install(|| {
+            lib_heif.decode_tiles_parallel(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+        })?;
+        assert_eq!(image.width(), 1652);
+        assert_eq!(image.height(), 1791);
+        Ok(())
+    }
+}
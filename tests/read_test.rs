@@ -1,12 +1,15 @@
 use std::fs::File;
 use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
 
 use exif::parse_exif;
 use libheif_rs::{
-    check_file_type, color_profile_types, AuxiliaryImagesFilter, Chroma,
-    ChromaDownsamplingAlgorithm, ChromaUpsamplingAlgorithm, ColorPrimaries, ColorProfile,
-    ColorSpace, CompressionFormat, DecodingOptions, FileTypeResult, HeifContext, ImageHandle,
-    ItemId, LibHeif, MatrixCoefficients, Result, RgbChroma, StreamReader, TransferCharacteristics,
+    check_file_type, color_profile_types, icc_color_spaces, icc_device_classes,
+    AuxiliaryImagesFilter, Chroma, ChromaDownsamplingAlgorithm, ChromaUpsamplingAlgorithm,
+    ColorPrimaries, ColorProfile, ColorProfileNCLX, ColorProfileRaw, ColorProfiles, ColorSpace,
+    CompressionFormat, DecodingOptions, EffectiveColorProfile, FileTypeResult, HeifContext,
+    IccRenderingIntent, ImageHandle, ItemId, LibHeif, MatrixCoefficients, ProgressStep, Result,
+    RgbChroma, StreamReader, TransferCharacteristics, WellKnownIccProfile,
 };
 
 fn version(lib_heif: &LibHeif) -> u16 {
@@ -76,6 +79,56 @@ fn read_from_reader() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn handle_outlives_context() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    let data = std::fs::read("./data/test.heif").unwrap();
+    let handle = {
+        let stream_reader = StreamReader::new(std::io::Cursor::new(&data), data.len() as u64);
+        let ctx = HeifContext::read_from_reader(Box::new(stream_reader))?;
+        ctx.primary_image_handle()?
+    };
+
+    let image = lib_heif.decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)?;
+    assert_eq!(image.width(), 1652);
+    assert_eq!(image.height(), 1791);
+
+    Ok(())
+}
+
+#[test]
+fn decode_in_other_thread() -> Result<()> {
+    let data = std::fs::read("./data/test.heif").unwrap();
+
+    // Reader that isn't `Send` is bound to the thread that created it.
+    let data_rc: std::rc::Rc<[u8]> = data.clone().into();
+    let stream_reader = StreamReader::new(std::io::Cursor::new(data_rc), data.len() as u64);
+    let ctx = HeifContext::read_from_reader(Box::new(stream_reader))?;
+    let handle = ctx.primary_image_handle()?;
+    let res = std::thread::spawn(move || {
+        LibHeif::new()
+            .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+            .map(|image| image.width())
+    })
+    .join()
+    .unwrap();
+    assert!(res.is_err());
+
+    let stream_reader = StreamReader::new(std::io::Cursor::new(data.clone()), data.len() as u64);
+    let ctx = HeifContext::read_from_reader_send(Box::new(stream_reader))?;
+    let handle = ctx.primary_image_handle()?;
+    let width = std::thread::spawn(move || {
+        LibHeif::new()
+            .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+            .map(|image| image.width())
+    })
+    .join()
+    .unwrap()?;
+    assert_eq!(width, 1652);
+
+    Ok(())
+}
+
 #[test]
 fn get_image_handler() -> Result<()> {
     let ctx = HeifContext::read_from_file("./data/test.heif")?;
@@ -96,6 +149,7 @@ fn get_image_handler() -> Result<()> {
     let mut image_ids: Vec<ItemId> = vec![0; 1];
     let count = handle.depth_image_ids(&mut image_ids);
     assert_eq!(count, 0);
+    assert_eq!(handle.depth_images().count(), 0);
     Ok(())
 }
 
@@ -119,6 +173,11 @@ fn get_thumbnail() -> Result<()> {
 
     assert_eq!(thumb_handle.width(), 236);
     assert_eq!(thumb_handle.height(), 256);
+
+    let handle = ctx.primary_image_handle()?;
+    let thumbnails = handle.thumbnails().collect::<Result<Vec<_>>>()?;
+    assert_eq!(thumbnails.len(), 1);
+    assert_eq!(thumbnails[0].item_id(), thumb_handle.item_id());
     Ok(())
 }
 
@@ -168,6 +227,16 @@ fn get_all_metadata() -> Result<()> {
     assert_eq!(exif.content_type, "");
     assert_eq!(exif.raw_data.len(), 2330);
     assert_eq!(exif.raw_data[0..8], [0, 0, 0, 0, b'M', b'M', 0, b'*']);
+
+    let blocks = handle
+        .metadata_blocks(b"Exif")
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(blocks.len(), 1);
+    let (item_id, metadata) = &blocks[0];
+    assert_eq!(*item_id, 2);
+    assert_eq!(metadata.item_type, b"Exif".into());
+    assert_eq!(metadata.raw_data, exif.raw_data);
+    assert_eq!(handle.metadata_blocks(b"Unkn").count(), 0);
     Ok(())
 }
 
@@ -265,6 +334,10 @@ fn image_collection() -> Result<()> {
         assert_eq!(handle.height(), 360);
     }
 
+    let handles = ctx.image_handles()?;
+    let item_ids: Vec<ItemId> = handles.iter().map(|h| h.item_id()).collect();
+    assert_eq!(item_ids, image_ids[0..4]);
+    assert_eq!(ctx.top_level_images().count(), 4);
     Ok(())
 }
 
@@ -306,6 +379,53 @@ fn test_raw_color_profile_of_image() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_color_profiles_of_image_handle() -> Result<()> {
+    let ctx = HeifContext::read_from_file("./data/test.heif")?;
+    let handle = ctx.primary_image_handle()?;
+    let profiles = handle.color_profiles();
+    assert!(!profiles.is_empty());
+    let icc = profiles.icc.as_ref().unwrap();
+    assert_eq!(icc.profile_type(), color_profile_types::PROF);
+    assert_eq!(profiles.effective(), Some(EffectiveColorProfile::Icc(icc)));
+
+    // NCLX profile is effective only if ICC profile is absent.
+    let nclx_only = ColorProfiles {
+        icc: None,
+        nclx: profiles.nclx.clone().or_else(ColorProfileNCLX::srgb),
+    };
+    assert!(matches!(
+        nclx_only.effective(),
+        Some(EffectiveColorProfile::Nclx(_))
+    ));
+    assert_eq!(ColorProfiles::default().effective(), None);
+    Ok(())
+}
+
+#[test]
+fn test_icc_info_of_raw_color_profile() -> Result<()> {
+    let ctx = HeifContext::read_from_file("./data/test.heif")?;
+    let handle = ctx.primary_image_handle()?;
+    let raw_profile = handle.color_profile_raw().unwrap();
+
+    let info = raw_profile.icc_info()?;
+    assert_eq!(info.description.as_deref(), Some("Display P3"));
+    assert_eq!(info.device_class, icc_device_classes::DISPLAY);
+    assert_eq!(info.color_space, icc_color_spaces::RGB);
+    assert_eq!(info.pcs, icc_color_spaces::XYZ);
+    assert_eq!(info.version.major, 4);
+    assert_eq!(info.version.minor, 0);
+    assert_eq!(info.rendering_intent, IccRenderingIntent::Perceptual);
+    assert_eq!(info.well_known, Some(WellKnownIccProfile::DisplayP3));
+
+    let truncated =
+        ColorProfileRaw::new(color_profile_types::PROF, raw_profile.data[..200].to_vec());
+    assert!(truncated.icc_info().is_err());
+    let not_icc = ColorProfileRaw::new(color_profile_types::NCLX, raw_profile.data.clone());
+    assert!(not_icc.icc_info().is_err());
+    Ok(())
+}
+
 #[cfg(feature = "v1_21")]
 #[test]
 fn test_nclx_color_profile_of_handle_and_image() -> Result<()> {
@@ -514,3 +634,120 @@ fn get_auxiliary_images() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn decode_grid_with_progress_handler() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    let ctx = HeifContext::read_from_file("./data/grid.heif")?;
+    let handle = ctx.primary_image_handle()?;
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let handler_events = events.clone();
+    let mut dec_options = DecodingOptions::new().unwrap();
+    dec_options.set_progress_handler(move |step, current, max| {
+        handler_events.lock().unwrap().push((step, current, max));
+    });
+    lib_heif.decode(&handle, ColorSpace::Undefined, Some(dec_options))?;
+
+    let events = events.lock().unwrap();
+    assert!(events.len() > 2);
+    let (step, current, max) = events[0];
+    assert_eq!(step, ProgressStep::Total);
+    assert_eq!(current, 0);
+    assert!(max > 1);
+    assert_eq!(events.last(), Some(&(ProgressStep::Total, max, max)));
+    assert!(events.windows(2).all(|w| w[0].1 < w[1].1));
+
+    Ok(())
+}
+
+#[test]
+fn decode_with_panicking_progress_handler() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    let ctx = HeifContext::read_from_file("./data/grid.heif")?;
+    let handle = ctx.primary_image_handle()?;
+
+    let calls = Arc::new(Mutex::new(0));
+    let handler_calls = calls.clone();
+    let mut dec_options = DecodingOptions::new().unwrap();
+    dec_options.set_progress_handler(move |_, _, _| {
+        *handler_calls.lock().unwrap() += 1;
+        panic!("Progress handler panicked");
+    });
+    // The panic doesn't unwind into libheif and doesn't abort decoding.
+    let image = lib_heif.decode(&handle, ColorSpace::Undefined, Some(dec_options))?;
+    assert_eq!(image.width(), handle.width());
+    assert_eq!(*calls.lock().unwrap(), 1);
+
+    Ok(())
+}
+
+#[cfg(feature = "v1_19")]
+#[test]
+fn cancel_decoding() -> Result<()> {
+    use libheif_rs::{CancellationToken, HeifErrorCode};
+
+    let lib_heif = LibHeif::new();
+    let ctx = HeifContext::read_from_file("./data/grid.heif")?;
+    let handle = ctx.primary_image_handle()?;
+
+    let token = CancellationToken::new();
+    let mut dec_options = DecodingOptions::new().unwrap();
+    dec_options.set_cancellation_token(Some(token.clone()));
+    assert!(dec_options.cancellation_token().is_some());
+    token.cancel();
+    assert!(dec_options.cancellation_token().unwrap().is_cancelled());
+
+    let err = lib_heif
+        .decode(&handle, ColorSpace::Undefined, Some(dec_options))
+        .err()
+        .unwrap();
+    assert!(matches!(err.code, HeifErrorCode::Canceled));
+
+    Ok(())
+}
+
+#[test]
+fn decode_into_buffer() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    let ctx = HeifContext::read_from_file("./data/test.heif")?;
+    let handle = ctx.primary_image_handle()?;
+    let color_space = ColorSpace::Rgb(RgbChroma::Rgb);
+    let width = handle.width() as usize;
+    let height = handle.height() as usize;
+    let row_size = width * 3;
+    let stride = row_size + 16;
+
+    let mut buf = vec![0u8; stride * height];
+    lib_heif.decode_into(&handle, color_space, None, &mut buf, stride)?;
+
+    let image = lib_heif.decode(&handle, color_space, None)?;
+    let plane = image.planes().interleaved.unwrap();
+    for (y, src_row) in plane.rows().enumerate() {
+        let dst_row = &buf[y * stride..y * stride + row_size];
+        assert_eq!(src_row, dst_row);
+        // Padding at the end of rows must stay untouched.
+        assert!(buf[y * stride + row_size..(y + 1) * stride]
+            .iter()
+            .all(|&v| v == 0));
+    }
+
+    // Stride is less than size of row
+    let res = lib_heif.decode_into(&handle, color_space, None, &mut buf, row_size - 1);
+    assert!(res.is_err());
+    // Buffer is too small
+    let mut small_buf = vec![0u8; row_size * (height - 1)];
+    let res = lib_heif.decode_into(&handle, color_space, None, &mut small_buf, row_size);
+    assert!(res.is_err());
+    // Color space with several planes is not supported
+    let res = lib_heif.decode_into(
+        &handle,
+        ColorSpace::YCbCr(Chroma::C420),
+        None,
+        &mut buf,
+        stride,
+    );
+    assert!(res.is_err());
+
+    Ok(())
+}
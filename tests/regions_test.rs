@@ -3,7 +3,8 @@ mod write_test;
 #[cfg(feature = "v1_18")]
 mod v1_18 {
     use libheif_rs::{
-        regions, CompressionFormat, EncoderQuality, EncodingOptions, HeifContext, LibHeif, Result,
+        regions, CompressionFormat, EncoderQuality, EncodingOptions, HeifContext, HeifErrorCode,
+        LibHeif, Result,
     };
 
     use super::*;
@@ -80,4 +81,29 @@ mod v1_18 {
 
         Ok(())
     }
+
+    #[test]
+    fn remove_region_item() -> Result<()> {
+        let image = write_test::create_image(640, 480)?;
+        let lib_heif = LibHeif::new();
+        let mut ctx = HeifContext::new()?;
+        let mut encoder = lib_heif.encoder_for_format(CompressionFormat::Av1)?;
+        let mut handle = ctx.encode_image(&image, &mut encoder, None)?;
+        let mut region_item = handle.add_region_item(640, 480)?;
+        region_item.add_point(1, 2)?;
+        let region_item_id = region_item.id();
+
+        let err = ctx
+            .remove_region_item(&handle, handle.item_id())
+            .unwrap_err();
+        assert!(matches!(err.code, HeifErrorCode::UsageError));
+
+        ctx.remove_region_item(&handle, region_item_id)?;
+        let bytes = ctx.write_to_bytes()?;
+
+        let ctx = HeifContext::read_from_bytes(&bytes)?;
+        let handle = ctx.primary_image_handle()?;
+        assert!(handle.region_items().is_empty());
+        Ok(())
+    }
 }
@@ -1,7 +1,12 @@
+use std::sync::{Arc, Mutex};
+
 use libheif_rs::{
-    Channel, ChromaDownsamplingAlgorithm, ChromaUpsamplingAlgorithm, ColorSpace, CompressionFormat,
-    EncoderParameterValue, EncoderQuality, EncodingOptions, HeifContext, Image, ImageOrientation,
-    LibHeif, Result, RgbChroma,
+    AomConfig, Channel, ChromaDownsamplingAlgorithm, ChromaUpsamplingAlgorithm, ColorPrimaries,
+    ColorProfileNCLX, ColorProfiles, ColorSpace, CompressionFormat, EffectiveColorProfile,
+    EncoderChroma, EncoderParameterType, EncoderParameterValue, EncoderQuality, EncodingOptions,
+    HeifContext, HeifErrorCode, HeifErrorSubCode, Image, ImageOrientation, LibHeif,
+    MatrixCoefficients, ProgressStep, Result, RgbChroma, TransferCharacteristics, X265Config,
+    X265Preset,
 };
 
 pub fn create_image(width: u32, height: u32) -> Result<Image> {
@@ -57,6 +62,83 @@ fn create_and_encode_image() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn encode_hdr_image_with_nclx_profile() -> Result<()> {
+    let width = 64;
+    let height = 32;
+    let pixels: Vec<u16> = (0..width * height * 3).map(|i| (i % 1024) as u16).collect();
+    let mut image = Image::from_rgb16(width, height, &pixels, 10)?;
+    let nclx = ColorProfileNCLX::rec2100_pq().unwrap();
+    image.set_color_profile_nclx(&nclx)?;
+
+    let lib_heif = LibHeif::new();
+    let mut context = HeifContext::new()?;
+    let mut encoder = lib_heif.encoder_for_format(CompressionFormat::Av1)?;
+    context.encode_image(&image, &mut encoder, None)?;
+    let buf = context.write_to_bytes()?;
+
+    let context = HeifContext::read_from_bytes(&buf)?;
+    let handle = context.primary_image_handle()?;
+    assert_eq!(handle.luma_bits_per_pixel(), 10);
+    let profile = handle.color_profile_nclx().unwrap();
+    assert_eq!(
+        profile.color_primaries(),
+        ColorPrimaries::ITU_R_BT_2020_2_and_2100_0
+    );
+    assert_eq!(
+        profile.transfer_characteristics(),
+        TransferCharacteristics::ITU_R_BT_2100_0_PQ
+    );
+    assert_eq!(
+        profile.matrix_coefficients(),
+        MatrixCoefficients::ITU_R_BT_2020_2_NonConstantLuminance
+    );
+    Ok(())
+}
+
+#[test]
+fn encode_image_with_icc_and_nclx_profiles() -> Result<()> {
+    let src_context = HeifContext::read_from_file("./data/test.heif")?;
+    let icc = src_context.primary_image_handle()?.color_profile_raw();
+    assert!(icc.is_some());
+    let profiles = ColorProfiles {
+        icc,
+        nclx: ColorProfileNCLX::display_p3(),
+    };
+    let mut image = create_image(64, 32)?;
+    image.set_color_profiles(&profiles)?;
+    let image_profiles = image.color_profiles();
+    assert_eq!(image_profiles.icc, profiles.icc);
+    assert!(matches!(
+        image_profiles.effective(),
+        Some(EffectiveColorProfile::Icc(_))
+    ));
+
+    let lib_heif = LibHeif::new();
+    for save_two_boxes in [false, true] {
+        let mut context = HeifContext::new()?;
+        let mut encoder = lib_heif.encoder_for_format(CompressionFormat::Av1)?;
+        let mut encoding_options = EncodingOptions::new()?;
+        encoding_options.set_save_two_colr_boxes_when_icc_and_nclx_available(save_two_boxes);
+        context.encode_image(&image, &mut encoder, Some(encoding_options))?;
+        let buf = context.write_to_bytes()?;
+
+        let context = HeifContext::read_from_bytes(&buf)?;
+        let handle = context.primary_image_handle()?;
+        let handle_profiles = handle.color_profiles();
+        assert_eq!(handle_profiles.icc, profiles.icc);
+        assert_eq!(handle_profiles.nclx.is_some(), save_two_boxes);
+        if let Some(nclx) = handle_profiles.nclx.as_ref() {
+            assert_eq!(nclx.color_primaries(), ColorPrimaries::SMPTE_EG_432_1);
+        }
+        assert_eq!(
+            handle_profiles.effective(),
+            Some(EffectiveColorProfile::Icc(profiles.icc.as_ref().unwrap()))
+        );
+    }
+    Ok(())
+}
+
 #[test]
 fn create_and_encode_monochrome_image() -> Result<()> {
     let width = 640;
@@ -241,6 +323,135 @@ fn test_encoder_av1() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_encoder_parameters_info() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    let encoder = lib_heif.encoder_for_format(CompressionFormat::Av1)?;
+
+    let infos = encoder.parameters_info();
+    assert_eq!(infos.len(), encoder.parameters_names().len());
+    assert!(infos.windows(2).all(|w| w[0].name < w[1].name));
+
+    let speed = encoder.parameter_info("speed").unwrap();
+    assert_eq!(speed.parameter_type, EncoderParameterType::Int);
+    assert_eq!(speed.default_value, Some(EncoderParameterValue::Int(6)));
+    assert_eq!(speed.minimum, Some(0));
+    assert!(speed.maximum.unwrap() >= 8);
+    assert!(speed.valid_string_values.is_empty());
+
+    let chroma = encoder.parameter_info("chroma").unwrap();
+    assert_eq!(chroma.parameter_type, EncoderParameterType::String);
+    assert_eq!(
+        chroma.default_value,
+        Some(EncoderParameterValue::String("420".to_string()))
+    );
+    assert!(chroma.valid_string_values.contains(&"444".to_string()));
+
+    let lossless = encoder.parameter_info("lossless").unwrap();
+    assert_eq!(
+        lossless.default_value,
+        Some(EncoderParameterValue::Bool(false))
+    );
+    assert!(encoder.parameter_info("unknown").is_none());
+
+    // Validation of values
+    encoder.set_parameter_value("speed", EncoderParameterValue::Int(3))?;
+    let err = encoder
+        .set_parameter_value("speed", EncoderParameterValue::Int(100))
+        .unwrap_err();
+    assert!(matches!(err.code, HeifErrorCode::UsageError));
+    assert!(matches!(
+        err.sub_code,
+        HeifErrorSubCode::InvalidParameterValue
+    ));
+    assert!(err.message.contains("speed"));
+    assert_eq!(
+        encoder.parameter("speed")?,
+        Some(EncoderParameterValue::Int(3))
+    );
+
+    encoder.set_parameter_value("chroma", EncoderParameterValue::String("444".into()))?;
+    let err = encoder
+        .set_parameter_value("chroma", EncoderParameterValue::String("411".into()))
+        .unwrap_err();
+    assert!(matches!(
+        err.sub_code,
+        HeifErrorSubCode::InvalidParameterValue
+    ));
+    let err = encoder
+        .set_parameter_value("chroma", EncoderParameterValue::Int(444))
+        .unwrap_err();
+    assert!(matches!(
+        err.sub_code,
+        HeifErrorSubCode::InvalidParameterValue
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_apply_encoder_config() -> Result<()> {
+    let lib_heif = LibHeif::new();
+    let mut encoder = lib_heif.encoder_for_format(CompressionFormat::Av1)?;
+    assert_eq!(encoder.id().as_deref(), Some("aom"));
+
+    let config = AomConfig {
+        speed: Some(4),
+        chroma: Some(EncoderChroma::C444),
+        min_q: Some(10),
+        max_q: Some(40),
+        ..Default::default()
+    };
+    let skipped = encoder.apply_config(&config)?;
+    assert!(skipped.is_empty());
+    assert_eq!(
+        encoder.parameter("speed")?,
+        Some(EncoderParameterValue::Int(4))
+    );
+    assert_eq!(
+        encoder.parameter("chroma")?,
+        Some(EncoderParameterValue::String("444".to_string()))
+    );
+    assert_eq!(
+        encoder.parameter("max-q")?,
+        Some(EncoderParameterValue::Int(40))
+    );
+
+    // Invalid value
+    let config = AomConfig {
+        speed: Some(100),
+        max_q: Some(50),
+        ..Default::default()
+    };
+    let err = encoder.apply_config(&config).unwrap_err();
+    assert!(matches!(
+        err.sub_code,
+        HeifErrorSubCode::InvalidParameterValue
+    ));
+    assert!(err.message.contains("speed"));
+    // Valid values are not applied if some value was rejected
+    assert_eq!(
+        encoder.parameter("max-q")?,
+        Some(EncoderParameterValue::Int(40))
+    );
+
+    // Configuration of another encoder
+    let config = X265Config {
+        preset: Some(X265Preset::Fast),
+        ..Default::default()
+    };
+    let err = encoder.apply_config(&config).unwrap_err();
+    assert!(matches!(err.code, HeifErrorCode::UsageError));
+    assert_eq!(
+        encoder.parameter("speed")?,
+        Some(EncoderParameterValue::Int(4))
+    );
+
+    let image = create_image(64, 64)?;
+    let mut context = HeifContext::new()?;
+    context.encode_image(&image, &mut encoder, None)?;
+    Ok(())
+}
+
 #[test]
 fn test_encoding_options() -> Result<()> {
     let enc_options = EncodingOptions::new().unwrap();
@@ -265,6 +476,28 @@ fn test_encoding_options() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn encode_image_with_progress_handler() -> Result<()> {
+    let image = create_image(64, 48)?;
+    let lib_heif = LibHeif::new();
+    let mut context = HeifContext::new()?;
+    let mut encoder = lib_heif.encoder_for_format(CompressionFormat::Av1)?;
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let handler_events = events.clone();
+    let mut encoding_options = EncodingOptions::new()?;
+    encoding_options.set_progress_handler(move |step, current, max| {
+        handler_events.lock().unwrap().push((step, current, max));
+    });
+    context.encode_image(&image, &mut encoder, Some(encoding_options))?;
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        [(ProgressStep::Total, 0, 1), (ProgressStep::Total, 1, 1)]
+    );
+    Ok(())
+}
+
 #[cfg(feature = "v1_18")]
 mod v1_18 {
     use super::*;
@@ -274,7 +507,7 @@ mod v1_18 {
     fn test_encode_grid() -> Result<()> {
         let lib_heif = LibHeif::new();
         let collection_ctx = HeifContext::read_from_file("./data/collection.heif")?;
-        let handles = collection_ctx.top_level_image_handles();
+        let handles = collection_ctx.image_handles()?;
         assert_eq!(handles.len(), 4);
 
         let mut tiles = Vec::with_capacity(4);
@@ -314,3 +547,148 @@ mod v1_18 {
         Ok(())
     }
 }
+
+#[cfg(feature = "v1_19")]
+mod v1_19 {
+    use std::num::NonZeroU16;
+
+    use libheif_rs::{CancellationToken, HeifErrorCode};
+
+    use super::*;
+
+    #[test]
+    fn encode_grid_with_progress_handler() -> Result<()> {
+        let lib_heif = LibHeif::new();
+        let tiles = (0..4)
+            .map(|_| create_image(64, 48))
+            .collect::<Result<Vec<_>>>()?;
+        let mut encoder = lib_heif.encoder_for_format(CompressionFormat::Av1)?;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let handler_events = events.clone();
+        let mut encoding_options = EncodingOptions::new()?;
+        encoding_options.set_progress_handler(move |step, current, max| {
+            handler_events.lock().unwrap().push((step, current, max));
+        });
+
+        let mut grid_ctx = HeifContext::new()?;
+        grid_ctx.encode_grid(
+            &tiles,
+            NonZeroU16::new(2).unwrap(),
+            &mut encoder,
+            Some(encoding_options),
+        )?;
+        // Older versions of libheif don't apply encoding options
+        // to separately added tiles, so the grid is encoded at once.
+        let expected: Vec<_> = if lib_heif.version() >= [1, 23, 0] {
+            (0..=4).map(|i| (ProgressStep::Total, i, 4)).collect()
+        } else {
+            vec![(ProgressStep::Total, 0, 1), (ProgressStep::Total, 1, 1)]
+        };
+        assert_eq!(*events.lock().unwrap(), expected);
+
+        let buf = grid_ctx.write_to_bytes()?;
+        let context = HeifContext::read_from_bytes(&buf)?;
+        let handle = context.primary_image_handle()?;
+        assert_eq!(handle.width(), 64 * 2);
+        assert_eq!(handle.height(), 48 * 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cancel_encoding() -> Result<()> {
+        let lib_heif = LibHeif::new();
+        let image = create_image(64, 48)?;
+        let mut encoder = lib_heif.encoder_for_format(CompressionFormat::Av1)?;
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let mut context = HeifContext::new()?;
+        let mut encoding_options = EncodingOptions::new()?;
+        encoding_options.set_cancellation_token(Some(token.clone()));
+        let err = context
+            .encode_image(&image, &mut encoder, Some(encoding_options))
+            .err()
+            .unwrap();
+        assert!(matches!(err.code, HeifErrorCode::Canceled));
+
+        let tiles = [create_image(64, 48)?, create_image(64, 48)?];
+        let mut encoding_options = EncodingOptions::new()?;
+        encoding_options.set_cancellation_token(Some(token));
+        let err = context
+            .encode_grid(
+                &tiles,
+                NonZeroU16::new(1).unwrap(),
+                &mut encoder,
+                Some(encoding_options),
+            )
+            .err()
+            .unwrap();
+        assert!(matches!(err.code, HeifErrorCode::Canceled));
+
+        Ok(())
+    }
+
+    #[test]
+    fn remux_precompressed_image() -> Result<()> {
+        let lib_heif = LibHeif::new();
+        let image = create_image(64, 48)?;
+        let mut encoder = lib_heif.encoder_for_format(CompressionFormat::Av1)?;
+        let mut context = HeifContext::new()?;
+        let handle = context.encode_image(&image, &mut encoder, None)?;
+
+        let compressed = handle.compressed_data()?;
+        assert_eq!(compressed.format, CompressionFormat::Av1);
+        assert_eq!(compressed.config[0], 0x81);
+        assert!(!compressed.data.is_empty());
+        // Parsed properties of items are updated after modification of the context
+        let other_image = create_image(32, 32)?;
+        let other_handle = context.encode_image(&other_image, &mut encoder, None)?;
+        assert_eq!(other_handle.compressed_data()?.config[0], 0x81);
+        assert_eq!(handle.compressed_data()?, compressed);
+
+        let mut remuxed_ctx = HeifContext::new()?;
+        let remuxed_handle = remuxed_ctx.add_precompressed_image(
+            compressed.format,
+            &compressed.config,
+            &compressed.data,
+            64,
+            48,
+        )?;
+        assert!(remuxed_handle.is_primary());
+        // Internal encoder of pre-compressed data is hidden
+        let descriptors = lib_heif.encoder_descriptors(usize::MAX, None, None);
+        assert!(descriptors
+            .iter()
+            .all(|descriptor| !descriptor.id().contains("precompressed-")));
+        let descriptors = lib_heif.encoder_descriptors(
+            1,
+            Some(CompressionFormat::Av1),
+            Some("libheif-rs-precompressed-av1"),
+        );
+        assert!(descriptors.is_empty());
+
+        let buf = remuxed_ctx.write_to_bytes()?;
+        let context = HeifContext::read_from_bytes(&buf)?;
+        let handle = context.primary_image_handle()?;
+        assert_eq!(handle.width(), 64);
+        assert_eq!(handle.height(), 48);
+        assert_eq!(handle.compressed_data()?.data, compressed.data);
+        let image = lib_heif.decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)?;
+        assert_eq!(image.width(), 64);
+
+        let err = remuxed_ctx
+            .add_precompressed_image(CompressionFormat::Jpeg, &[], &compressed.data, 64, 48)
+            .err()
+            .unwrap();
+        assert!(matches!(err.code, HeifErrorCode::UnsupportedFeature));
+        let err = remuxed_ctx
+            .add_precompressed_image(CompressionFormat::Hevc, &[1, 2], &[], 64, 48)
+            .err()
+            .unwrap();
+        assert!(matches!(err.code, HeifErrorCode::InvalidInput));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+#[cfg(feature = "xmp")]
+mod xmp_tests {
+    use libheif_rs::{xmp_namespaces, HeifContext, HeifErrorCode, Result, Xmp, XmpValue};
+
+    const APPLE_XMP: &[u8] = br#"<x:xmpmeta xmlns:x="adobe:ns:meta/" x:xmptk="XMP Core 6.0.0">
+   <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+      <rdf:Description rdf:about=""
+            xmlns:HDRGainMap="http://ns.apple.com/HDRGainMap/1.0/"
+            xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+            xmp:Rating="3">
+         <HDRGainMap:HDRGainMapVersion>65536</HDRGainMap:HDRGainMapVersion>
+         <HDRGainMap:HDRGainMapHeadroom>2.418</HDRGainMap:HDRGainMapHeadroom>
+      </rdf:Description>
+      <rdf:Description rdf:about=""
+            xmlns:dc="http://purl.org/dc/elements/1.1/">
+         <dc:subject>
+            <rdf:Bag>
+               <rdf:li>sea &amp; sky</rdf:li>
+               <rdf:li>sunset</rdf:li>
+            </rdf:Bag>
+         </dc:subject>
+         <dc:title>
+            <rdf:Alt>
+               <rdf:li xml:lang="x-default">Beach</rdf:li>
+               <rdf:li xml:lang="de">Strand</rdf:li>
+            </rdf:Alt>
+         </dc:title>
+      </rdf:Description>
+   </rdf:RDF>
+</x:xmpmeta>"#;
+
+    #[test]
+    fn parse_and_serialize() -> Result<()> {
+        let mut xmp = Xmp::parse(APPLE_XMP)?;
+        assert_eq!(xmp.rating(), Some(3));
+        assert_eq!(xmp.keywords(), ["sea & sky", "sunset"]);
+        assert_eq!(xmp.apple_hdr_gain_map_version(), Some(65536));
+        assert_eq!(xmp.apple_hdr_gain_map_headroom(), Some(2.418));
+        assert_eq!(
+            xmp.property(xmp_namespaces::DC, "title"),
+            Some(&XmpValue::LangAlt(vec![
+                ("x-default".to_string(), "Beach".to_string()),
+                ("de".to_string(), "Strand".to_string()),
+            ]))
+        );
+
+        xmp.set_rating(5);
+        xmp.set_keywords(["<new>"]);
+        xmp.set_property(
+            "http://example.com/ns/",
+            "Steps",
+            XmpValue::Seq(vec!["one".to_string(), "two".to_string()]),
+        )?;
+        assert!(xmp.remove_property(xmp_namespaces::DC, "title").is_some());
+
+        let parsed = Xmp::parse(&xmp.to_bytes())?;
+        assert_eq!(parsed.properties(), xmp.properties());
+        assert_eq!(parsed.rating(), Some(5));
+        assert_eq!(parsed.keywords(), ["<new>"]);
+        Ok(())
+    }
+
+    const HISTORY_XMP: &[u8] = br#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:xmpMM="http://ns.adobe.com/xap/1.0/mm/"
+    xmlns:stEvt="http://ns.adobe.com/xap/1.0/sType/ResourceEvent#"
+    xmlns:stRef="http://ns.adobe.com/xap/1.0/sType/ResourceRef#"
+    xmp:Rating="1">
+   <xmpMM:History>
+    <rdf:Seq>
+     <rdf:li rdf:parseType="Resource">
+      <stEvt:action>created</stEvt:action>
+      <stEvt:when>2024-05-01T10:00:00Z</stEvt:when>
+     </rdf:li>
+    </rdf:Seq>
+   </xmpMM:History>
+   <xmpMM:DerivedFrom>
+    <rdf:Description stRef:documentID="xmp.did:1234"/>
+   </xmpMM:DerivedFrom>
+   <xmp:BaseURL rdf:resource="http://example.com/"/>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+    #[test]
+    fn keep_unsupported_values() -> Result<()> {
+        let mut xmp = Xmp::parse(HISTORY_XMP)?;
+        assert_eq!(xmp.rating(), Some(1));
+        // Fields of nested description are not properties of the packet
+        assert_eq!(xmp.properties().len(), 4);
+        for name in ["History", "DerivedFrom"] {
+            let value = xmp.property(xmp_namespaces::XMP_MM, name);
+            assert!(matches!(value, Some(XmpValue::Raw { .. })));
+        }
+        let Some(XmpValue::Raw { xml, namespaces }) = xmp.property(xmp_namespaces::XMP, "BaseURL")
+        else {
+            panic!("Value of 'xmp:BaseURL' is not raw");
+        };
+        assert_eq!(xml, r#"<xmp:BaseURL rdf:resource="http://example.com/"/>"#);
+        assert_eq!(
+            namespaces,
+            &[
+                (
+                    "rdf".to_string(),
+                    "http://www.w3.org/1999/02/22-rdf-syntax-ns#".to_string()
+                ),
+                ("xmp".to_string(), xmp_namespaces::XMP.to_string()),
+            ]
+        );
+
+        xmp.set_rating(5);
+        let parsed = Xmp::parse(&xmp.to_bytes())?;
+        assert_eq!(parsed.rating(), Some(5));
+        for (namespace, name) in [
+            (xmp_namespaces::XMP_MM, "History"),
+            (xmp_namespaces::XMP_MM, "DerivedFrom"),
+            (xmp_namespaces::XMP, "BaseURL"),
+        ] {
+            assert_eq!(
+                parsed.property(namespace, name),
+                xmp.property(namespace, name)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_xmp() {
+        let err = Xmp::parse(b"<x:xmpmeta>").err().unwrap();
+        assert!(matches!(err.code, HeifErrorCode::InvalidInput));
+
+        let mut xmp = Xmp::new();
+        let err = xmp
+            .set_property(xmp_namespaces::XMP, "a:b", XmpValue::Simple(String::new()))
+            .err()
+            .unwrap();
+        assert!(matches!(err.code, HeifErrorCode::UsageError));
+    }
+
+    #[test]
+    fn write_xmp_into_file() -> Result<()> {
+        let mut ctx = HeifContext::edit_from_file("./data/test.heif")?;
+        let handle = ctx.primary_image_handle()?;
+        assert!(handle.xmp()?.is_none());
+
+        let mut xmp = Xmp::new();
+        xmp.set_rating(4);
+        xmp.set_keywords(["cat", "dog"]);
+        ctx.add_xmp_metadata(&handle, &xmp.to_bytes())?;
+        let buf = ctx.write_to_bytes()?;
+
+        let ctx = HeifContext::read_from_bytes(&buf)?;
+        let handle = ctx.primary_image_handle()?;
+        assert_eq!(handle.xmp()?, Some(xmp));
+        Ok(())
+    }
+}